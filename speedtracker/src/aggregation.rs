@@ -0,0 +1,204 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::{Datelike, Timelike, Weekday};
+use serde::Serialize;
+
+use crate::json_parser::{ParsedEntry, Performance};
+use crate::lib_constants::*;
+use crate::statistics::Summary;
+
+/// measurements of one hour of the day (of one weekday)
+#[derive(Debug, Serialize)]
+pub struct Bucket {
+    /// only for the weekday x hour buckets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weekday: Option<Weekday>,
+    pub hour: u32,
+    pub count: usize,
+    /// none if there is no measurement in the bucket:
+    pub median: Option<f64>,
+    pub p10: Option<f64>,
+}
+
+/// buckets of one metric
+#[derive(Debug, Serialize)]
+pub struct MetricAggregation {
+    pub metric: &'static str,
+    pub unit: &'static str,
+    pub higher_is_better: bool,
+    /// 24 buckets, hour 0 first
+    pub by_hour: Vec<Bucket>,
+    /// 7 x 24 buckets, monday 0:00 first
+    pub by_weekday_hour: Vec<Bucket>,
+}
+
+/// measurements grouped by hour of day and by weekday x hour, e.g. to find the evening slump
+#[derive(Debug, Serialize)]
+pub struct Aggregation {
+    pub metrics: Vec<MetricAggregation>,
+}
+
+/// id, unit, higher is better and the value in the unit
+pub(crate) type Metric = (
+    &'static str,
+    &'static str,
+    bool,
+    fn(&Performance) -> Option<f64>,
+);
+
+pub(crate) const METRICS: [Metric; 4] = [
+    (ID_LATENCY, UNIT_MS, false, |p| p.latency.map(f64::from)),
+    (ID_JITTER, UNIT_MS, false, |p| p.jitter.map(f64::from)),
+    (ID_DOWNLOAD, UNIT_MBIT, true, |p| {
+        p.download.map(|v| v / MEGA_BIT_FACTOR)
+    }),
+    (ID_UPLOAD, UNIT_MBIT, true, |p| {
+        p.upload.map(|v| v / MEGA_BIT_FACTOR)
+    }),
+];
+
+const WEEKDAYS: [Weekday; DAYS_PER_WEEK] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// group the measured values of every metric, failed runs are left out
+pub fn aggregate(data: &[ParsedEntry]) -> Aggregation {
+    let metrics = METRICS
+        .iter()
+        .map(|(metric, unit, higher_is_better, value)| {
+            let mut by_hour: Vec<Vec<f64>> = vec![Vec::new(); HOURS_PER_DAY];
+            let mut by_weekday_hour: Vec<Vec<f64>> =
+                vec![Vec::new(); DAYS_PER_WEEK * HOURS_PER_DAY];
+            for d in data {
+                if let Some(v) = d.performance.as_ref().and_then(value) {
+                    let hour = d.timestamp.hour() as usize;
+                    let weekday = d.timestamp.weekday().num_days_from_monday() as usize;
+                    by_hour[hour].push(v);
+                    by_weekday_hour[weekday * HOURS_PER_DAY + hour].push(v);
+                }
+            }
+            MetricAggregation {
+                metric,
+                unit,
+                higher_is_better: *higher_is_better,
+                by_hour: by_hour
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, values)| bucket(None, i, values))
+                    .collect(),
+                by_weekday_hour: by_weekday_hour
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, values)| {
+                        bucket(Some(WEEKDAYS[i / HOURS_PER_DAY]), i % HOURS_PER_DAY, values)
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    Aggregation { metrics }
+}
+
+fn bucket(weekday: Option<Weekday>, hour: usize, values: Vec<f64>) -> Bucket {
+    let summary = Summary::new(values);
+    Bucket {
+        weekday,
+        hour: hour as u32,
+        count: summary.count(),
+        median: summary.median(),
+        p10: summary.percentile(10.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_parser::JsonParser;
+
+    /// a run on the day of october 2026 at the time, without download if it failed
+    fn entry(day: u32, time: &str, download: Option<f64>) -> ParsedEntry {
+        let performance = match download {
+            Some(d) => format!(
+                r#","performance":{{"latency":10,"download":{:?}}}"#,
+                d * MEGA_BIT_FACTOR
+            ),
+            None => String::new(),
+        };
+        JsonParser::parse(&format!(
+            r#"{{"timestamp":"2026-10-{:02} {}"{}}}"#,
+            day, time, performance
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn runs_are_grouped_by_weekday_and_hour() {
+        // 2026-10-12 is a monday:
+        let data = [
+            entry(12, "08:00:00", Some(300.0)),
+            entry(12, "20:10:00", Some(100.0)),
+            entry(12, "20:20:00", None),
+            entry(12, "20:50:00", Some(200.0)),
+            entry(13, "20:30:00", Some(50.0)),
+            entry(19, "20:40:00", Some(400.0)),
+        ];
+        let aggregation = aggregate(&data);
+        let download = aggregation
+            .metrics
+            .iter()
+            .find(|m| m.metric == ID_DOWNLOAD)
+            .unwrap();
+        assert_eq!(download.by_hour.len(), HOURS_PER_DAY);
+        assert_eq!(
+            download.by_weekday_hour.len(),
+            DAYS_PER_WEEK * HOURS_PER_DAY
+        );
+
+        let evening = &download.by_hour[20];
+        assert_eq!(
+            (evening.weekday, evening.hour, evening.count),
+            (None, 20, 4)
+        );
+        assert_eq!(evening.median, Some(150.0));
+        assert_eq!(download.by_hour[8].count, 1);
+
+        let monday_evening = &download.by_weekday_hour[20];
+        assert_eq!(monday_evening.weekday, Some(Weekday::Mon));
+        assert_eq!(monday_evening.hour, 20);
+        // both mondays, the failed run is left out:
+        assert_eq!(monday_evening.count, 3);
+        assert_eq!(monday_evening.median, Some(200.0));
+        let tuesday_evening = &download.by_weekday_hour[HOURS_PER_DAY + 20];
+        assert_eq!(tuesday_evening.weekday, Some(Weekday::Tue));
+        assert_eq!(tuesday_evening.median, Some(50.0));
+        let empty = &download.by_weekday_hour[2 * HOURS_PER_DAY + 20];
+        assert_eq!((empty.count, empty.median, empty.p10), (0, None, None));
+    }
+}
@@ -0,0 +1,236 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::json_parser::naive_date_time_to_str;
+use crate::lib_constants::{ANOMALY_MIN_BASELINE, MAD_TO_STANDARD_DEVIATION};
+use crate::statistics::median;
+
+/// single measurement far from the usual values of its hour of the week
+#[derive(Debug, Serialize)]
+pub struct Anomaly {
+    /// label of the chart
+    pub metric: String,
+    #[serde(serialize_with = "naive_date_time_to_str")]
+    pub timestamp: NaiveDateTime,
+    /// measured value (in the unit of the chart)
+    pub value: f64,
+    /// median of the same hour of the week in the baseline,
+    /// or of the same hour of the day or all measurements if the hour has too few
+    pub baseline: f64,
+    /// robust z-score, negative below the baseline
+    pub score: f64,
+}
+
+/// baseline and robust z-score of every sample: the baseline is the median of the baseline samples
+/// (usually some weeks of history) of the same weekday and hour, the score the distance to it in MADs
+/// of these samples (scaled to standard deviations), so hours with a usually large spread are not flagged.
+/// hours of the week with less than ANOMALY_MIN_BASELINE samples use the samples of the same hour
+/// of the day, if these are too few as well all baseline samples. hours whose samples do not vary
+/// use the MAD of all baseline samples. none if all baseline samples are equal
+pub fn anomaly_scores(
+    samples: &[(NaiveDateTime, f64)],
+    baseline: &[(NaiveDateTime, f64)],
+) -> Vec<Option<(f64, f64)>> {
+    let values: Vec<f64> = baseline.iter().map(|(_, v)| *v).collect();
+    let global = match median_and_mad(&values) {
+        Some((median, mad)) if mad > 0.0 => (median, mad),
+        _ => return vec![None; samples.len()],
+    };
+    let hour_of_week = |t: &NaiveDateTime| (t.weekday().num_days_from_monday(), t.hour());
+    let mut by_hour_of_week: HashMap<(u32, u32), Vec<f64>> = HashMap::new();
+    let mut by_hour_of_day: HashMap<u32, Vec<f64>> = HashMap::new();
+    for (t, v) in baseline {
+        by_hour_of_week.entry(hour_of_week(t)).or_default().push(*v);
+        by_hour_of_day.entry(t.hour()).or_default().push(*v);
+    }
+    let usual = |values: Option<&Vec<f64>>| {
+        let values = values.filter(|v| v.len() >= ANOMALY_MIN_BASELINE)?;
+        let (median, mad) = median_and_mad(values)?;
+        Some((median, if mad > 0.0 { mad } else { global.1 }))
+    };
+    let of_week: HashMap<(u32, u32), (f64, f64)> = by_hour_of_week
+        .iter()
+        .filter_map(|(hour, values)| Some((*hour, usual(Some(values))?)))
+        .collect();
+    let of_day: HashMap<u32, (f64, f64)> = by_hour_of_day
+        .iter()
+        .filter_map(|(hour, values)| Some((*hour, usual(Some(values))?)))
+        .collect();
+    samples
+        .iter()
+        .map(|(t, v)| {
+            let (median, mad) = of_week
+                .get(&hour_of_week(t))
+                .or_else(|| of_day.get(&t.hour()))
+                .copied()
+                .unwrap_or(global);
+            Some((median, (v - median) / (MAD_TO_STANDARD_DEVIATION * mad)))
+        })
+        .collect()
+}
+
+/// median and median absolute deviation from it
+fn median_and_mad(values: &[f64]) -> Option<(f64, f64)> {
+    let median_value = median(values)?;
+    let distances: Vec<f64> = values.iter().map(|v| (v - median_value).abs()).collect();
+    Some((median_value, median(&distances)?))
+}
+
+/// samples with an absolute robust z-score of at least threshold against the baseline samples,
+/// none for threshold 0
+pub fn detect_anomalies(
+    metric: &str,
+    samples: &[(NaiveDateTime, f64)],
+    baseline: &[(NaiveDateTime, f64)],
+    threshold: f64,
+) -> Vec<Anomaly> {
+    if threshold <= 0.0 {
+        return Vec::new();
+    }
+    samples
+        .iter()
+        .zip(anomaly_scores(samples, baseline))
+        .filter_map(|(&(timestamp, value), score)| {
+            let (baseline, score) = score?;
+            if score.abs() < threshold {
+                return None;
+            }
+            Some(Anomaly {
+                metric: String::from(metric),
+                timestamp,
+                value,
+                baseline,
+                score,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    fn start() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 9, 7)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .unwrap()
+    }
+
+    /// one sample per hour for 5 weeks, steady at night and spread in the evening:
+    /// every hour of the week varies by -2 to 2 units around the level of its time of day
+    fn daily_cycle() -> Vec<(NaiveDateTime, f64)> {
+        (0..5 * 7 * 24)
+            .map(|i| {
+                let t = start() + Duration::hours(i);
+                let (level, unit) = match t.hour() {
+                    0..=6 => (100.0, 0.5),
+                    18..=23 => (50.0, 10.0),
+                    _ => (75.0, 5.0),
+                };
+                let week = i / (7 * 24);
+                let deviation = ((week + i64::from(t.hour())) % 5 - 2) as f64;
+                (t, level + deviation * unit)
+            })
+            .collect()
+    }
+
+    /// one sample per hour over the days, about 170 but about 60 at 20:00 and 22:00 every evening
+    fn evening_dips(days: i64) -> Vec<(NaiveDateTime, f64)> {
+        (0..days * 24)
+            .map(|i| {
+                let t = start() + Duration::hours(i);
+                let noise = ((i * 7) % 5 - 2) as f64 * 2.0;
+                match t.hour() {
+                    20 | 22 => (t, 60.0 + noise),
+                    _ => (t, 170.0 + noise),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn usual_spread_of_an_hour_is_no_anomaly() {
+        let samples = daily_cycle();
+        assert!(detect_anomalies("download", &samples, &samples, 3.5).is_empty());
+    }
+
+    #[test]
+    fn small_drop_in_a_steady_hour_is_an_anomaly() {
+        let mut samples = daily_cycle();
+        // wednesday of the third week at 03:00:
+        let i = (2 * 7 + 2) * 24 + 3;
+        samples[i].1 = 95.0;
+        let anomalies = detect_anomalies("download", &samples, &samples, 3.5);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].timestamp, samples[i].0);
+        assert_eq!(anomalies[0].baseline, 100.0);
+        assert!(anomalies[0].score < -3.5);
+    }
+
+    #[test]
+    fn regular_evening_dip_is_no_anomaly() {
+        // 14 days shown, the baseline are the last 8 weeks:
+        let baseline = evening_dips(8 * 7);
+        let samples = &baseline[6 * 7 * 24..];
+        assert!(detect_anomalies("download", samples, &baseline, 3.5).is_empty());
+        let scores = anomaly_scores(samples, &baseline);
+        let dip = samples.iter().position(|(t, _)| t.hour() == 20).unwrap();
+        let (usual, _) = scores[dip].unwrap();
+        assert!((usual - 60.0).abs() <= 4.0);
+    }
+
+    #[test]
+    fn hours_of_the_week_with_few_samples_use_the_hour_of_the_day() {
+        // two weeks: two samples per hour of the week, 14 per hour of the day
+        let mut samples = evening_dips(14);
+        assert!(detect_anomalies("download", &samples, &samples, 3.5).is_empty());
+        let night = samples.iter().position(|(t, _)| t.hour() == 3).unwrap();
+        samples[night].1 = 60.0;
+        let anomalies = detect_anomalies("download", &samples, &samples, 3.5);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].timestamp, samples[night].0);
+        assert_eq!(anomalies[0].baseline, 170.0);
+    }
+
+    #[test]
+    fn sparse_hours_use_the_median_and_mad_of_all_samples() {
+        // three days, every hour of the day has three samples:
+        let samples: Vec<(NaiveDateTime, f64)> = (0..3 * 24)
+            .map(|i| (start() + Duration::hours(i), 100.0 + (i % 5) as f64))
+            .collect();
+        let scores = anomaly_scores(&samples, &samples);
+        assert!(scores
+            .iter()
+            .all(|s| matches!(s, Some((b, _)) if *b == 102.0)));
+        let mut samples = samples;
+        samples[10].1 = 50.0;
+        let anomalies = detect_anomalies("download", &samples, &samples, 3.5);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].timestamp, samples[10].0);
+    }
+}
@@ -0,0 +1,563 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::chart_config::ChartConfig;
+use crate::json_parser::{naive_date_time_to_str, ParsedEntry};
+use crate::lib_constants::*;
+
+/// speeds of the contract in Mbit/s
+#[derive(Debug)]
+pub struct ContractSpeeds {
+    pub maximum: f64,
+    pub normal: f64,
+    pub minimum: f64,
+}
+
+/// contract to evaluate a measurement campaign against
+/// (rules of the Bundesnetzagentur for a price reduction)
+#[derive(Debug)]
+pub struct Contract {
+    pub download: ContractSpeeds,
+    pub upload: ContractSpeeds,
+}
+
+/// labels of the tiers of download_chart and upload_chart with the contract speeds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampaignConfig {
+    #[serde(default = "default_maximum_tier")]
+    pub maximum: String,
+    #[serde(default = "default_normal_tier")]
+    pub normal: String,
+    #[serde(default = "default_minimum_tier")]
+    pub minimum: String,
+}
+
+fn default_maximum_tier() -> String {
+    String::from(DEFAULT_CAMPAIGN_MAXIMUM_TIER)
+}
+
+fn default_normal_tier() -> String {
+    String::from(DEFAULT_CAMPAIGN_NORMAL_TIER)
+}
+
+fn default_minimum_tier() -> String {
+    String::from(DEFAULT_CAMPAIGN_MINIMUM_TIER)
+}
+
+impl Default for CampaignConfig {
+    fn default() -> Self {
+        CampaignConfig {
+            maximum: default_maximum_tier(),
+            normal: default_normal_tier(),
+            minimum: default_minimum_tier(),
+        }
+    }
+}
+
+impl CampaignConfig {
+    /// contract speeds in force on the date from the tiers of the expected values
+    /// of the download and upload chart
+    pub fn contract(
+        &self,
+        download_chart: &ChartConfig<f64>,
+        upload_chart: &ChartConfig<f64>,
+        date: NaiveDate,
+    ) -> Result<Contract, String> {
+        Ok(Contract {
+            download: self.speeds(download_chart, KEY_DOWNLOAD_CHART, date)?,
+            upload: self.speeds(upload_chart, KEY_UPLOAD_CHART, date)?,
+        })
+    }
+
+    fn speeds(
+        &self,
+        chart: &ChartConfig<f64>,
+        key: &str,
+        date: NaiveDate,
+    ) -> Result<ContractSpeeds, String> {
+        let tier = |label: &str| {
+            chart
+                .expected_value
+                .as_ref()
+                .and_then(|e| e.tiers.iter().find(|t| t.label == label))
+                .map(|t| t.value_at(date))
+                .ok_or_else(|| {
+                    format!(
+                        "the campaign needs a tier '{}' in '{}.{}.tiers'",
+                        label, key, KEY_EXPECTED_VALUE
+                    )
+                })
+        };
+        Ok(ContractSpeeds {
+            maximum: tier(&self.maximum)?,
+            normal: tier(&self.normal)?,
+            minimum: tier(&self.minimum)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CampaignStatus {
+    /// all contract speeds are met
+    Pass,
+    /// at least one contract speed is not met, a price reduction can be claimed
+    Fail,
+    /// not enough measurements following the rules
+    Incomplete,
+}
+
+impl fmt::Display for CampaignStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CampaignStatus::Pass => write!(f, "pass"),
+            CampaignStatus::Fail => write!(f, "fail"),
+            CampaignStatus::Incomplete => write!(f, "incomplete"),
+        }
+    }
+}
+
+/// measurement used for the campaign, speeds in Mbit/s
+#[derive(Debug, Serialize)]
+pub struct CampaignMeasurement {
+    #[serde(serialize_with = "naive_date_time_to_str")]
+    pub timestamp: NaiveDateTime,
+    pub download: f64,
+    pub upload: f64,
+}
+
+/// entry that is not part of the campaign
+#[derive(Debug, Serialize)]
+pub struct Exclusion {
+    #[serde(serialize_with = "naive_date_time_to_str")]
+    pub timestamp: NaiveDateTime,
+    pub reason: String,
+}
+
+/// evaluation of one contract speed
+#[derive(Debug, Serialize)]
+pub struct Criterion {
+    /// 'download' or 'upload'
+    pub metric: &'static str,
+    /// 'maximum', 'normal' or 'minimum'
+    pub speed: &'static str,
+    /// in Mbit/s
+    pub contract_value: f64,
+    pub rule: String,
+    pub result: String,
+    pub met: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CampaignReport {
+    pub status: CampaignStatus,
+    pub message: String,
+    pub days: Vec<NaiveDate>,
+    pub criteria: Vec<Criterion>,
+    pub used: Vec<CampaignMeasurement>,
+    pub excluded: Vec<Exclusion>,
+}
+
+/// pick a campaign from the entries and evaluate it against the contract.
+/// the most recent days following the rules are used:
+/// CAMPAIGN_DAYS days with CAMPAIGN_MEASUREMENTS_PER_DAY measurements each
+pub fn evaluate_campaign(data: &[ParsedEntry], contract: &Contract) -> CampaignReport {
+    let mut excluded: Vec<Exclusion> = Vec::new();
+    let mut per_day: BTreeMap<NaiveDate, Vec<CampaignMeasurement>> = BTreeMap::new();
+
+    let mut entries: Vec<&ParsedEntry> = data.iter().collect();
+    entries.sort_by_key(|e| e.timestamp);
+    for entry in entries {
+        match measurement(entry) {
+            Ok(m) => per_day.entry(m.timestamp.date()).or_default().push(m),
+            Err(reason) => excluded.push(Exclusion {
+                timestamp: entry.timestamp,
+                reason,
+            }),
+        }
+    }
+
+    // measurements of every day following the spacing rules:
+    let mut qualifying: BTreeMap<NaiveDate, Vec<CampaignMeasurement>> = BTreeMap::new();
+    for (day, measurements) in per_day {
+        let (picked, mut day_excluded) = pick_day(measurements);
+        if picked.len() == CAMPAIGN_MEASUREMENTS_PER_DAY {
+            qualifying.insert(day, picked);
+        } else {
+            day_excluded.extend(picked.into_iter().map(|m| Exclusion {
+                timestamp: m.timestamp,
+                reason: format!(
+                    "day has less than {} measurements following the spacing rules",
+                    CAMPAIGN_MEASUREMENTS_PER_DAY
+                ),
+            }));
+        }
+        excluded.extend(day_excluded);
+    }
+
+    let days = pick_days(&qualifying.keys().copied().collect::<Vec<NaiveDate>>());
+    let mut used: Vec<CampaignMeasurement> = Vec::new();
+    for (day, measurements) in qualifying {
+        if days.contains(&day) {
+            used.extend(measurements);
+        } else {
+            excluded.extend(measurements.into_iter().map(|m| Exclusion {
+                timestamp: m.timestamp,
+                reason: String::from("day is not part of the campaign"),
+            }));
+        }
+    }
+    excluded.sort_by_key(|e| e.timestamp);
+
+    if days.len() < CAMPAIGN_DAYS {
+        return CampaignReport {
+            status: CampaignStatus::Incomplete,
+            message: format!(
+                "found {} of {} measurement days with {} measurements each, \
+                 with at least {} day(s) between them and within {} days",
+                days.len(),
+                CAMPAIGN_DAYS,
+                CAMPAIGN_MEASUREMENTS_PER_DAY,
+                CAMPAIGN_MIN_DAYS_BETWEEN,
+                CAMPAIGN_MAX_PERIOD_DAYS
+            ),
+            days,
+            criteria: Vec::new(),
+            used,
+            excluded,
+        };
+    }
+
+    let mut criteria = evaluate_speeds("download", &contract.download, &used, |m| m.download);
+    criteria.extend(evaluate_speeds("upload", &contract.upload, &used, |m| {
+        m.upload
+    }));
+    let (status, message) = if criteria.iter().all(|c| c.met) {
+        (
+            CampaignStatus::Pass,
+            String::from("the contract speeds are met"),
+        )
+    } else {
+        (
+            CampaignStatus::Fail,
+            String::from("the contract speeds are not met, a price reduction can be claimed"),
+        )
+    };
+    CampaignReport {
+        status,
+        message,
+        days,
+        criteria,
+        used,
+        excluded,
+    }
+}
+
+fn measurement(entry: &ParsedEntry) -> Result<CampaignMeasurement, String> {
+    if entry.is_failed_run() {
+        return Err(String::from("failed run"));
+    }
+    let performance = entry
+        .performance
+        .as_ref()
+        .ok_or_else(|| String::from("no measurement"))?;
+    match (performance.download, performance.upload) {
+        (Some(download), Some(upload)) => Ok(CampaignMeasurement {
+            timestamp: entry.timestamp,
+            download: download / MEGA_BIT_FACTOR,
+            upload: upload / MEGA_BIT_FACTOR,
+        }),
+        _ => Err(String::from("download or upload missing")),
+    }
+}
+
+/// measurements of one day in the order of time: at least CAMPAIGN_MIN_SPACING_MINUTES apart
+/// and a break of CAMPAIGN_BREAK_HOURS after the first half
+fn pick_day(measurements: Vec<CampaignMeasurement>) -> (Vec<CampaignMeasurement>, Vec<Exclusion>) {
+    let mut picked: Vec<CampaignMeasurement> = Vec::new();
+    let mut excluded: Vec<Exclusion> = Vec::new();
+    for m in measurements {
+        let reason = match picked.last() {
+            _ if picked.len() == CAMPAIGN_MEASUREMENTS_PER_DAY => Some(format!(
+                "day has already {} measurements",
+                CAMPAIGN_MEASUREMENTS_PER_DAY
+            )),
+            Some(last)
+                if picked.len() == CAMPAIGN_BREAK_AFTER
+                    && m.timestamp - last.timestamp < Duration::hours(CAMPAIGN_BREAK_HOURS) =>
+            {
+                Some(format!(
+                    "less than {} hours after measurement {} of the day",
+                    CAMPAIGN_BREAK_HOURS, CAMPAIGN_BREAK_AFTER
+                ))
+            }
+            Some(last)
+                if m.timestamp - last.timestamp
+                    < Duration::minutes(CAMPAIGN_MIN_SPACING_MINUTES) =>
+            {
+                Some(format!(
+                    "less than {} minutes after the previous measurement",
+                    CAMPAIGN_MIN_SPACING_MINUTES
+                ))
+            }
+            _ => None,
+        };
+        match reason {
+            Some(reason) => excluded.push(Exclusion {
+                timestamp: m.timestamp,
+                reason,
+            }),
+            None => picked.push(m),
+        }
+    }
+    (picked, excluded)
+}
+
+/// the most recent CAMPAIGN_DAYS days that are CAMPAIGN_MIN_DAYS_BETWEEN days apart
+/// and within CAMPAIGN_MAX_PERIOD_DAYS, none if there are not enough
+fn pick_days(qualifying: &[NaiveDate]) -> Vec<NaiveDate> {
+    let mut best: Vec<NaiveDate> = Vec::new();
+    for (index, last) in qualifying.iter().enumerate().rev() {
+        let mut days = vec![*last];
+        for day in qualifying[..index].iter().rev() {
+            let previous = *days.last().unwrap();
+            if *last - *day >= Duration::days(CAMPAIGN_MAX_PERIOD_DAYS) {
+                break;
+            }
+            if previous - *day > Duration::days(CAMPAIGN_MIN_DAYS_BETWEEN) {
+                days.push(*day);
+                if days.len() == CAMPAIGN_DAYS {
+                    days.reverse();
+                    return days;
+                }
+            }
+        }
+        if days.len() > best.len() {
+            best = days;
+        }
+    }
+    best.reverse();
+    best
+}
+
+/// rules of the Bundesnetzagentur for the maximum, normal and minimum speed
+fn evaluate_speeds(
+    metric: &'static str,
+    contract: &ContractSpeeds,
+    used: &[CampaignMeasurement],
+    value: impl Fn(&CampaignMeasurement) -> f64,
+) -> Vec<Criterion> {
+    let maximum_limit = contract.maximum * CAMPAIGN_MAXIMUM_SHARE;
+    let best = used.iter().map(&value).fold(0.0, f64::max);
+    let days_reaching_maximum: BTreeSet<NaiveDate> = used
+        .iter()
+        .filter(|m| value(m) >= maximum_limit)
+        .map(|m| m.timestamp.date())
+        .collect();
+
+    let normal_count = used.iter().filter(|m| value(m) >= contract.normal).count();
+    let normal_share = normal_count as f64 / used.len() as f64;
+
+    let mut days_below: Vec<NaiveDate> = used
+        .iter()
+        .filter(|m| value(m) < contract.minimum)
+        .map(|m| m.timestamp.date())
+        .collect();
+    days_below.dedup();
+
+    vec![
+        Criterion {
+            metric,
+            speed: "maximum",
+            contract_value: contract.maximum,
+            rule: format!(
+                "{:.0}% of the maximum speed ({:.1} Mbit/s) is reached on at least {} of {} days",
+                CAMPAIGN_MAXIMUM_SHARE * 100.0,
+                maximum_limit,
+                CAMPAIGN_MAXIMUM_DAYS,
+                CAMPAIGN_DAYS
+            ),
+            result: format!(
+                "reached on {} day(s), best measurement {:.1} Mbit/s",
+                days_reaching_maximum.len(),
+                best
+            ),
+            met: days_reaching_maximum.len() >= CAMPAIGN_MAXIMUM_DAYS,
+        },
+        Criterion {
+            metric,
+            speed: "normal",
+            contract_value: contract.normal,
+            rule: format!(
+                "the normal speed is reached in {:.0}% of the measurements",
+                CAMPAIGN_NORMAL_SHARE * 100.0
+            ),
+            result: format!(
+                "reached in {} of {} measurements ({:.1}%)",
+                normal_count,
+                used.len(),
+                normal_share * 100.0
+            ),
+            met: normal_share >= CAMPAIGN_NORMAL_SHARE,
+        },
+        Criterion {
+            metric,
+            speed: "minimum",
+            contract_value: contract.minimum,
+            rule: format!(
+                "the minimum speed is not undercut on {} of {} days",
+                CAMPAIGN_MINIMUM_DAYS, CAMPAIGN_DAYS
+            ),
+            result: format!("undercut on {} day(s)", days_below.len()),
+            met: days_below.len() < CAMPAIGN_MINIMUM_DAYS,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chart_config::ExpectedChange;
+
+    const CONTRACT: ContractSpeeds = ContractSpeeds {
+        maximum: 100.0,
+        normal: 80.0,
+        minimum: 50.0,
+    };
+
+    /// 10 measurements on each of 3 days with the download of the day
+    fn measurements(downloads: [f64; 3]) -> Vec<CampaignMeasurement> {
+        let first = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+        downloads
+            .iter()
+            .enumerate()
+            .flat_map(|(day, download)| {
+                (0..10).map(move |i| CampaignMeasurement {
+                    timestamp: (first + Duration::days(2 * day as i64))
+                        .and_hms_opt(8 + i, 0, 0)
+                        .unwrap(),
+                    download: *download,
+                    upload: 0.0,
+                })
+            })
+            .collect()
+    }
+
+    fn maximum_met(downloads: [f64; 3]) -> bool {
+        let criteria = evaluate_speeds("download", &CONTRACT, &measurements(downloads), |m| {
+            m.download
+        });
+        criteria.iter().find(|c| c.speed == "maximum").unwrap().met
+    }
+
+    #[test]
+    fn maximum_reached_on_one_day_is_not_met() {
+        assert!(!maximum_met([95.0, 85.0, 85.0]));
+    }
+
+    #[test]
+    fn maximum_reached_on_two_days_is_met() {
+        assert!(maximum_met([95.0, 85.0, 90.0]));
+    }
+
+    /// a chart with the (label, value) tiers as expected value
+    fn chart(tiers: &[(&str, f64)]) -> ChartConfig<f64> {
+        let mut config = String::from(
+            "label = 'download'\nfill = false\nborder_color = 'green'\ndefault_value = 0.0\n\
+             [expected_value]\nlabel = 'expected'\nfill = false\nborder_color = 'lime'\n",
+        );
+        for (label, value) in tiers {
+            config.push_str(&format!(
+                "[[expected_value.tiers]]\nlabel = '{}'\nfill = false\nborder_color = 'blue'\nvalue = {:?}\n",
+                label, value
+            ));
+        }
+        toml::from_str(&config).unwrap()
+    }
+
+    #[test]
+    fn contract_speeds_are_taken_from_the_tiers() {
+        let download = chart(&[("maximum", 250.0), ("normal", 200.0), ("minimum", 150.0)]);
+        let upload = chart(&[("minimum", 20.0), ("maximum", 40.0), ("normal", 30.0)]);
+        let contract = CampaignConfig::default()
+            .contract(&download, &upload, date())
+            .unwrap();
+        assert_eq!(
+            (
+                contract.download.maximum,
+                contract.download.normal,
+                contract.download.minimum
+            ),
+            (250.0, 200.0, 150.0)
+        );
+        assert_eq!(
+            (
+                contract.upload.maximum,
+                contract.upload.normal,
+                contract.upload.minimum
+            ),
+            (40.0, 30.0, 20.0)
+        );
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 18).unwrap()
+    }
+
+    #[test]
+    fn contract_speeds_are_the_tier_values_in_force() {
+        let mut download = chart(&[("maximum", 250.0), ("normal", 200.0), ("minimum", 150.0)]);
+        let upload = chart(&[("maximum", 40.0), ("normal", 30.0), ("minimum", 20.0)]);
+        download.expected_value.as_mut().unwrap().tiers[0].history = vec![ExpectedChange {
+            valid_from: date(),
+            value: 500.0,
+        }];
+        let contract_at = |date: NaiveDate| {
+            CampaignConfig::default()
+                .contract(&download, &upload, date)
+                .unwrap()
+                .download
+                .maximum
+        };
+        assert_eq!(contract_at(date().pred_opt().unwrap()), 250.0);
+        assert_eq!(contract_at(date()), 500.0);
+    }
+
+    #[test]
+    fn a_missing_tier_is_an_error() {
+        let download = chart(&[("maximum", 250.0), ("normal", 200.0), ("minimum", 150.0)]);
+        let upload = chart(&[("maximum", 40.0), ("minimum", 20.0)]);
+        let msg = CampaignConfig::default()
+            .contract(&download, &upload, date())
+            .unwrap_err();
+        assert_eq!(
+            msg,
+            "the campaign needs a tier 'normal' in 'upload_chart.expected_value.tiers'"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::json_parser::naive_date_time_to_str;
+use crate::lib_constants::{CHANGE_THRESHOLD, MAD_TO_STANDARD_DEVIATION};
+use crate::statistics::median;
+
+/// when a shift of the level counts as change
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeSettings {
+    /// samples needed before and after a change
+    pub min_samples: usize,
+    /// difference of the medians before and after in percent of the median before
+    pub min_shift_percent: f64,
+}
+
+/// shift of the level of a chart
+#[derive(Debug, Serialize)]
+pub struct Change {
+    /// label of the chart
+    pub metric: String,
+    /// last sample before the change
+    #[serde(serialize_with = "naive_date_time_to_str")]
+    pub last_before: NaiveDateTime,
+    /// first sample after the change
+    #[serde(serialize_with = "naive_date_time_to_str")]
+    pub first_after: NaiveDateTime,
+    /// median since the previous change (in the unit of the chart)
+    pub median_before: f64,
+    /// median until the next change (in the unit of the chart)
+    pub median_after: f64,
+    pub change_percent: f64,
+}
+
+/// find the changes of the level in the samples (sorted by timestamp) by binary segmentation:
+/// the split with the largest CUSUM of the deviations from the mean is a change
+/// if the medians before and after differ significantly (compared with the MAD of the parts)
+/// and by at least min_shift_percent, both parts are searched again
+pub fn detect_changes(
+    metric: &str,
+    samples: &[(NaiveDateTime, f64)],
+    settings: &ChangeSettings,
+) -> Vec<Change> {
+    let values: Vec<f64> = samples.iter().map(|(_, v)| *v).collect();
+    let mut splits = Vec::new();
+    find_splits(&values, 0, settings, &mut splits);
+    splits.sort_unstable();
+
+    let bounds: Vec<usize> = std::iter::once(0)
+        .chain(splits.iter().copied())
+        .chain(std::iter::once(values.len()))
+        .collect();
+    bounds
+        .windows(3)
+        .filter_map(|w| {
+            let median_before = median(&values[w[0]..w[1]])?;
+            let median_after = median(&values[w[1]..w[2]])?;
+            Some(Change {
+                metric: String::from(metric),
+                last_before: samples[w[1] - 1].0,
+                first_after: samples[w[1]].0,
+                median_before,
+                median_after,
+                change_percent: shift_percent(median_before, median_after),
+            })
+        })
+        .collect()
+}
+
+fn find_splits(values: &[f64], offset: usize, settings: &ChangeSettings, splits: &mut Vec<usize>) {
+    let n = values.len();
+    let min_samples = settings.min_samples.max(1);
+    if n < 2 * min_samples {
+        return;
+    }
+    let mean = values.iter().sum::<f64>() / n as f64;
+    let mut cusum = 0.0;
+    let (mut k, mut max_cusum) = (min_samples, -1.0);
+    for (i, v) in values.iter().enumerate().take(n - min_samples) {
+        cusum += v - mean;
+        if i + 1 >= min_samples && f64::abs(cusum) > max_cusum {
+            k = i + 1;
+            max_cusum = f64::abs(cusum);
+        }
+    }
+    let (before, after) = values.split_at(k);
+    if is_change(before, after, settings) {
+        find_splits(before, offset, settings, splits);
+        splits.push(offset + k);
+        find_splits(after, offset + k, settings, splits);
+    }
+}
+
+fn is_change(before: &[f64], after: &[f64], settings: &ChangeSettings) -> bool {
+    let (median_before, median_after) = match (median(before), median(after)) {
+        (Some(b), Some(a)) => (b, a),
+        _ => return false,
+    };
+    let (n1, n2) = (before.len() as f64, after.len() as f64);
+    // pooled robust standard deviation of both parts:
+    let scale = MAD_TO_STANDARD_DEVIATION
+        * (mad(before, median_before) * n1 + mad(after, median_after) * n2)
+        / (n1 + n2);
+    let difference = (median_after - median_before).abs();
+    let significant = if scale > 0.0 {
+        difference / (scale * (1.0 / n1 + 1.0 / n2).sqrt()) >= CHANGE_THRESHOLD
+    } else {
+        difference > 0.0
+    };
+    significant && shift_percent(median_before, median_after).abs() >= settings.min_shift_percent
+}
+
+/// median absolute deviation
+fn mad(values: &[f64], median_value: f64) -> f64 {
+    let deviations: Vec<f64> = values.iter().map(|v| (v - median_value).abs()).collect();
+    median(&deviations).unwrap_or(0.0)
+}
+
+fn shift_percent(before: f64, after: f64) -> f64 {
+    if before == 0.0 {
+        // from nothing to something is a full change:
+        return if after == 0.0 { 0.0 } else { 100.0 };
+    }
+    (after - before) / before.abs() * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    const SETTINGS: ChangeSettings = ChangeSettings {
+        min_samples: 10,
+        min_shift_percent: 10.0,
+    };
+
+    /// one sample per hour, 30 samples per level varying by -2 to 2 around it
+    fn levels(levels: &[f64]) -> Vec<(NaiveDateTime, f64)> {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 1)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .unwrap();
+        levels
+            .iter()
+            .flat_map(|level| (0..30).map(move |_| *level))
+            .enumerate()
+            .map(|(i, level)| {
+                let noise = ((i * 7) % 5) as f64 - 2.0;
+                (start + Duration::hours(i as i64), level + noise)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_step_is_found_with_its_medians() {
+        let samples = levels(&[240.0, 170.0]);
+        let changes = detect_changes("download", &samples, &SETTINGS);
+        assert_eq!(changes.len(), 1);
+        let change = &changes[0];
+        assert_eq!(change.metric, "download");
+        assert_eq!(change.last_before, samples[29].0);
+        assert_eq!(change.first_after, samples[30].0);
+        assert_eq!(change.median_before, 240.0);
+        assert_eq!(change.median_after, 170.0);
+        assert!((change.change_percent - -70.0 / 2.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn flat_series_has_no_change() {
+        let samples = levels(&[240.0, 240.0, 240.0]);
+        assert!(detect_changes("download", &samples, &SETTINGS).is_empty());
+    }
+
+    #[test]
+    fn too_few_samples_or_a_small_shift_are_no_change() {
+        let samples = levels(&[240.0, 170.0]);
+        let few = ChangeSettings {
+            min_samples: 31,
+            ..SETTINGS
+        };
+        assert!(detect_changes("download", &samples, &few).is_empty());
+        let small = ChangeSettings {
+            min_shift_percent: 30.0,
+            ..SETTINGS
+        };
+        assert!(detect_changes("download", &samples, &small).is_empty());
+    }
+
+    #[test]
+    fn successive_shifts_are_found() {
+        let samples = levels(&[240.0, 170.0, 250.0]);
+        let changes = detect_changes("download", &samples, &SETTINGS);
+        let found: Vec<(NaiveDateTime, f64, f64)> = changes
+            .iter()
+            .map(|c| (c.first_after, c.median_before, c.median_after))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(samples[30].0, 240.0, 170.0), (samples[60].0, 170.0, 250.0)]
+        );
+    }
+}
@@ -21,9 +21,16 @@
 // SOFTWARE.
 //
 
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::changes::ChangeSettings;
+use crate::lib_constants::{
+    DEFAULT_ANOMALY_THRESHOLD, DEFAULT_MISSING_VALUES, DEFAULT_STATISTICS, DEFAULT_WINDOW_UNIT,
+};
+use crate::statistics::Statistic;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChartConfig<T> {
     /// label for the data
     pub label: String,
@@ -33,11 +40,88 @@ pub struct ChartConfig<T> {
     pub border_color: String,
     /// default value is a value is missing
     pub default_value: T,
+    /// how missing values and failed runs are drawn
+    #[serde(default = "default_missing_values")]
+    pub missing_values: MissingValues,
+    /// columns of the statistic table, e.g. ['median', 'p10', 'n']
+    #[serde(default = "default_statistics")]
+    pub statistics: Vec<Statistic>,
+    /// robust z-score from which a measurement is an anomaly, 0 for none
+    #[serde(default = "default_anomaly_threshold")]
+    pub anomaly_threshold: f64,
     /// expect data:
     pub expected_value: Option<ExpectedConfig<T>>,
+    /// trend line: median of a sliding window
+    pub rolling_median: Option<TrendConfig>,
+    /// trend line: exponentially weighted moving average
+    pub ewma: Option<TrendConfig>,
+    /// projection of the next days
+    pub forecast: Option<ForecastConfig>,
+}
+
+/// policy for runs without a value for the chart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissingValues {
+    /// break the line
+    Gap,
+    /// draw the default_value of the chart
+    Default,
+    /// repeat the last measured value
+    CarryForward,
+    /// leave the value out of the line and mark it on the x axis
+    Marker,
+}
+
+fn default_missing_values() -> MissingValues {
+    DEFAULT_MISSING_VALUES
+}
+
+fn default_statistics() -> Vec<Statistic> {
+    DEFAULT_STATISTICS.to_vec()
+}
+
+fn default_anomaly_threshold() -> f64 {
+    DEFAULT_ANOMALY_THRESHOLD
+}
+
+/// a line derived from the measured values
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendConfig {
+    /// label for the line
+    pub label: String,
+    /// color of the line
+    pub border_color: String,
+    /// size of the window
+    pub window: u32,
+    /// window counted in samples (runs with a value) or hours
+    #[serde(default = "default_window_unit")]
+    pub window_unit: WindowUnit,
+}
+
+/// Holt-Winters forecast with daily seasonality, drawn dashed with a confidence band
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForecastConfig {
+    /// label for the line
+    pub label: String,
+    /// color of the line and the band
+    pub border_color: String,
+    /// number of days to project
+    pub days: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowUnit {
+    Samples,
+    Hours,
+}
+
+fn default_window_unit() -> WindowUnit {
+    DEFAULT_WINDOW_UNIT
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExpectedConfig<T> {
     /// label for the data
     pub label: String,
@@ -45,6 +129,128 @@ pub struct ExpectedConfig<T> {
     pub fill: bool,
     /// color of the line
     pub border_color: String,
-    /// default value is a value is missing
+    /// single expected value, optional if tiers are configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<T>,
+    /// named contract speeds, e.g. maximum, normal and minimum
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub tiers: Vec<TierConfig<T>>,
+    /// label of the tier the compliance levels refer to when no single value is in force
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// changes of the single value, e.g. after a contract upgrade
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<ExpectedChange<T>>,
+}
+
+impl<T: Copy> ExpectedConfig<T> {
+    /// single value in force on the date: the latest change valid from the date or before,
+    /// before the first change the configured value
+    pub fn value_at(&self, date: NaiveDate) -> Option<T> {
+        value_in_force(&self.history, date).or(self.value)
+    }
+
+    /// value compliance levels refer to on the date: the single value in force or else the reference tier
+    pub fn reference_value_at(&self, date: NaiveDate) -> Option<T> {
+        self.value_at(date).or_else(|| {
+            let reference = self.reference.as_ref()?;
+            self.tiers
+                .iter()
+                .find(|t| &t.label == reference)
+                .map(|t| t.value_at(date))
+        })
+    }
+}
+
+/// value of the latest change valid from the date or before
+fn value_in_force<T: Copy>(history: &[ExpectedChange<T>], date: NaiveDate) -> Option<T> {
+    history
+        .iter()
+        .filter(|c| c.valid_from <= date)
+        .max_by_key(|c| c.valid_from)
+        .map(|c| c.value)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpectedChange<T> {
+    /// first day the value is in force, e.g. '2026-06-01'
+    pub valid_from: NaiveDate,
+    /// expected value from that day on
+    pub value: T,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TierConfig<T> {
+    /// label for the tier
+    pub label: String,
+    /// fill the data or not
+    pub fill: bool,
+    /// color of the line
+    pub border_color: String,
+    /// contract value of the tier
     pub value: T,
+    /// changes of the contract value, e.g. after a contract upgrade
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<ExpectedChange<T>>,
+}
+
+impl<T: Copy> TierConfig<T> {
+    /// contract value in force on the date: the latest change valid from the date or before,
+    /// before the first change the configured value
+    pub fn value_at(&self, date: NaiveDate) -> T {
+        value_in_force(&self.history, date).unwrap_or(self.value)
+    }
+}
+
+/// when the charts show rollups instead of one point per run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rollup {
+    /// by the length of the time range, see rollup_daily_after_days and rollup_weekly_after_days
+    Auto,
+    /// always one point per run
+    None,
+    Daily,
+    Weekly,
+}
+
+/// bucket size of a rollup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollupPeriod {
+    Daily,
+    Weekly,
+}
+
+impl Rollup {
+    /// period of the rollup for a time range of the number of days, none for raw points
+    pub fn period(
+        &self,
+        days: i64,
+        daily_after_days: u32,
+        weekly_after_days: u32,
+    ) -> Option<RollupPeriod> {
+        match self {
+            Rollup::None => None,
+            Rollup::Daily => Some(RollupPeriod::Daily),
+            Rollup::Weekly => Some(RollupPeriod::Weekly),
+            Rollup::Auto if days > i64::from(weekly_after_days) => Some(RollupPeriod::Weekly),
+            Rollup::Auto if days > i64::from(daily_after_days) => Some(RollupPeriod::Daily),
+            Rollup::Auto => None,
+        }
+    }
+}
+
+/// configuration of all charts
+#[derive(Debug)]
+pub struct ChartConfigs {
+    pub latency: ChartConfig<u32>,
+    pub jitter: ChartConfig<u32>,
+    pub download: ChartConfig<f64>,
+    pub upload: ChartConfig<f64>,
+    /// none if every run is drawn
+    pub rollup: Option<RollupPeriod>,
+    /// maximal number of points of the measured series, 0 for every point
+    pub max_points: usize,
+    /// when a shift of the level is shown as change
+    pub changes: ChangeSettings,
 }
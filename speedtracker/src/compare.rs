@@ -0,0 +1,149 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::fmt;
+
+use crate::aggregation::METRICS;
+use crate::json_parser::ParsedEntry;
+use crate::lib_constants::COMPARE_SIGNIFICANCE_LEVEL;
+use crate::statistics::{mann_whitney_u, median, MannWhitney};
+
+/// time range of a comparison, both days included
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Period {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} - {}", self.from, self.to)
+    }
+}
+
+/// result of the comparison of one metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Verdict {
+    Better,
+    Worse,
+    /// the difference may be chance
+    NotSignificant,
+    /// too few measurements to test
+    Unknown,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Verdict::Better => "better",
+            Verdict::Worse => "worse",
+            Verdict::NotSignificant => "not significant",
+            Verdict::Unknown => "n/a",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// medians of a metric in both time ranges, the difference and its significance
+#[derive(Debug, Serialize)]
+pub struct MetricComparison {
+    pub metric: &'static str,
+    pub unit: &'static str,
+    pub first_count: usize,
+    pub first_median: Option<f64>,
+    pub second_count: usize,
+    pub second_median: Option<f64>,
+    /// second median - first median
+    pub delta: Option<f64>,
+    /// delta in percent of the first median
+    pub change_percent: Option<f64>,
+    /// Mann-Whitney U test of the measurements of both time ranges
+    pub test: Option<MannWhitney>,
+    /// second time range compared with the first one
+    pub verdict: Verdict,
+}
+
+/// comparison of two time ranges, e.g. before and after a router swap
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub first: Period,
+    pub second: Period,
+    pub metrics: Vec<MetricComparison>,
+}
+
+/// compare every metric of the successful runs of both time ranges
+pub fn compare(
+    first: Period,
+    first_data: &[ParsedEntry],
+    second: Period,
+    second_data: &[ParsedEntry],
+) -> Comparison {
+    let metrics = METRICS
+        .iter()
+        .map(|(metric, unit, higher_is_better, value)| {
+            let values = |data: &[ParsedEntry]| -> Vec<f64> {
+                data.iter()
+                    .filter_map(|d| d.performance.as_ref().and_then(value))
+                    .collect()
+            };
+            let (a, b) = (values(first_data), values(second_data));
+            let (first_median, second_median) = (median(&a), median(&b));
+            let delta = match (first_median, second_median) {
+                (Some(f), Some(s)) => Some(s - f),
+                _ => None,
+            };
+            let change_percent = match (delta, first_median) {
+                (Some(d), Some(f)) if f != 0.0 => Some(d / f.abs() * 100.0),
+                _ => None,
+            };
+            let test = mann_whitney_u(&b, &a);
+            let verdict = match test {
+                None => Verdict::Unknown,
+                Some(t) if t.p_value >= COMPARE_SIGNIFICANCE_LEVEL => Verdict::NotSignificant,
+                // z > 0: the second time range tends to larger values
+                Some(t) if (t.z > 0.0) == *higher_is_better => Verdict::Better,
+                Some(_) => Verdict::Worse,
+            };
+            MetricComparison {
+                metric,
+                unit,
+                first_count: a.len(),
+                first_median,
+                second_count: b.len(),
+                second_median,
+                delta,
+                change_percent,
+                test,
+                verdict,
+            }
+        })
+        .collect();
+    Comparison {
+        first,
+        second,
+        metrics,
+    }
+}
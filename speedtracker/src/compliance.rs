@@ -0,0 +1,298 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+
+use crate::lib_constants::*;
+use crate::statistics::median;
+
+/// how often a metric was measured and how often it met the expected value
+#[derive(Debug, Default)]
+pub struct Compliance {
+    /// all runs including failed ones
+    pub runs: usize,
+    /// runs with a usable value (a download of 0 is not usable)
+    pub available: usize,
+    /// only if an expected value is configured
+    pub expected: Option<ExpectedCompliance>,
+}
+
+#[derive(Debug)]
+pub struct ExpectedCompliance {
+    /// expected values in force during the time range, in order of their change
+    pub values: Vec<f64>,
+    /// measurements compared with an expected value
+    pub compared: usize,
+    /// percentage of the measurements reaching a level (in percent) of the expected value,
+    /// for each of COMPLIANCE_LEVELS
+    pub reached: Vec<(u32, f64)>,
+    /// days with at least one measurement
+    pub days: usize,
+    /// days where the median of the day met the expected value
+    pub days_met: usize,
+    /// share of the measurements meeting each configured tier
+    pub tiers: Vec<TierCompliance>,
+}
+
+#[derive(Debug)]
+pub struct TierCompliance {
+    pub label: String,
+    /// values of the tier in force during the time range, in order of their change
+    pub values: Vec<f64>,
+    /// percentage of the measurements meeting the value of the tier
+    pub met: f64,
+}
+
+impl Compliance {
+    /// available runs in percent of all runs
+    pub fn availability(&self) -> f64 {
+        percent(self.available, self.runs)
+    }
+}
+
+impl ExpectedCompliance {
+    /// days met in percent of all days
+    pub fn days_met_share(&self) -> f64 {
+        percent(self.days_met, self.days)
+    }
+}
+
+/// one run of a metric: the measured value (if any) and the expected value in force at its time
+#[derive(Debug)]
+pub struct Sample {
+    pub date: NaiveDate,
+    pub value: Option<f64>,
+    pub expected: Option<f64>,
+    /// values of the tiers in force at its time, in the order of the tier labels
+    pub tiers: Vec<f64>,
+}
+
+/// compliance of one metric, samples contain one entry per run.
+/// for latency and jitter lower values are better: reaching 90% means value <= expected / 0.9.
+/// tiers are the labels of the tier values of the samples, their shares are only computed
+/// if an expected value is given
+pub fn compliance(samples: &[Sample], tiers: &[String], higher_is_better: bool) -> Compliance {
+    let measured: Vec<&Sample> = samples.iter().filter(|s| s.value.is_some()).collect();
+    let available = measured
+        .iter()
+        .filter(|s| !higher_is_better || s.value.unwrap_or_default() > 0.0)
+        .count();
+    let has_expected = samples.iter().any(|s| s.expected.is_some());
+    Compliance {
+        runs: samples.len(),
+        available,
+        expected: if has_expected {
+            Some(expected_compliance(&measured, tiers, higher_is_better))
+        } else {
+            None
+        },
+    }
+}
+
+fn expected_compliance(
+    measured: &[&Sample],
+    tiers: &[String],
+    higher_is_better: bool,
+) -> ExpectedCompliance {
+    // level as fraction of the expected value:
+    let reaches = |value: f64, target: f64, level: f64| {
+        if higher_is_better {
+            value >= target * level
+        } else {
+            value * level <= target
+        }
+    };
+    // (value, expected value in force) of every measurement under a contract:
+    let compared: Vec<(NaiveDate, f64, f64)> = measured
+        .iter()
+        .filter_map(|s| match (s.value, s.expected) {
+            (Some(v), Some(e)) => Some((s.date, v, e)),
+            _ => None,
+        })
+        .collect();
+    let reached = COMPLIANCE_LEVELS
+        .iter()
+        .map(|level| {
+            let count = compared
+                .iter()
+                .filter(|(_, v, e)| reaches(*v, *e, f64::from(*level) / 100.0))
+                .count();
+            (*level, percent(count, compared.len()))
+        })
+        .collect();
+    let tiers = tiers
+        .iter()
+        .enumerate()
+        .map(|(i, label)| {
+            let count = measured
+                .iter()
+                .filter(|s| match (s.value, s.tiers.get(i)) {
+                    (Some(v), Some(tier)) => reaches(v, *tier, 1.0),
+                    _ => false,
+                })
+                .count();
+            TierCompliance {
+                label: label.clone(),
+                values: changing_values(measured.iter().filter_map(|s| s.tiers.get(i))),
+                met: percent(count, measured.len()),
+            }
+        })
+        .collect();
+
+    // the expected value can only change from one day to the next:
+    let mut days: BTreeMap<NaiveDate, (f64, Vec<f64>)> = BTreeMap::new();
+    for (date, value, expected) in &compared {
+        days.entry(*date)
+            .or_insert_with(|| (*expected, Vec::new()))
+            .1
+            .push(*value);
+    }
+    let days_met = days
+        .values_mut()
+        .filter_map(|(expected, values)| median(values).map(|m| (*expected, m)))
+        .filter(|(expected, median)| reaches(*median, *expected, 1.0))
+        .count();
+    ExpectedCompliance {
+        values: changing_values(compared.iter().map(|(_, _, expected)| expected)),
+        compared: compared.len(),
+        reached,
+        days: days.len(),
+        days_met,
+        tiers,
+    }
+}
+
+/// the values without repetitions, in order of their change
+fn changing_values<'a>(all: impl Iterator<Item = &'a f64>) -> Vec<f64> {
+    let mut values: Vec<f64> = Vec::new();
+    for value in all {
+        if values.last() != Some(value) {
+            values.push(*value);
+        }
+    }
+    values
+}
+
+fn percent(part: usize, all: usize) -> f64 {
+    if all == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / all as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap()
+    }
+
+    /// a measurement on the day with the expected value and the values of the tiers in force
+    fn sample(date: NaiveDate, value: f64, expected: f64, tiers: &[f64]) -> Sample {
+        Sample {
+            date,
+            value: Some(value),
+            expected: Some(expected),
+            tiers: tiers.to_vec(),
+        }
+    }
+
+    #[test]
+    fn shares_of_the_levels_of_the_expected_value() {
+        let samples: Vec<Sample> = [100.0, 95.0, 60.0, 40.0]
+            .iter()
+            .map(|v| sample(day(1), *v, 100.0, &[]))
+            .collect();
+        let e = compliance(&samples, &[], true).expected.unwrap();
+        assert_eq!(e.compared, 4);
+        assert_eq!(e.reached, vec![(100, 25.0), (90, 50.0), (50, 75.0)]);
+        assert_eq!(e.values, vec![100.0]);
+    }
+
+    #[test]
+    fn lower_latency_is_better() {
+        // 11 ms reaches 90% of 10 ms (11 * 0.9 <= 10), 19 ms reaches 50%, 25 ms nothing:
+        let samples: Vec<Sample> = [10.0, 11.0, 19.0, 25.0]
+            .iter()
+            .map(|v| sample(day(1), *v, 10.0, &[]))
+            .collect();
+        let e = compliance(&samples, &[], false).expected.unwrap();
+        assert_eq!(e.reached, vec![(100, 25.0), (90, 50.0), (50, 75.0)]);
+    }
+
+    #[test]
+    fn a_day_is_met_if_its_median_is() {
+        let samples: Vec<Sample> = [
+            (1, 120.0),
+            (1, 80.0),
+            (1, 110.0),
+            (2, 50.0),
+            (2, 90.0),
+            (2, 130.0),
+        ]
+        .iter()
+        .map(|(d, v)| sample(day(*d), *v, 100.0, &[]))
+        .collect();
+        let e = compliance(&samples, &[], true).expected.unwrap();
+        assert_eq!((e.days, e.days_met), (2, 1));
+        assert_eq!(e.days_met_share(), 50.0);
+    }
+
+    #[test]
+    fn failed_runs_and_zero_downloads_are_not_available() {
+        let run = |value: Option<f64>| Sample {
+            date: day(1),
+            value,
+            expected: None,
+            tiers: Vec::new(),
+        };
+        let samples = [run(Some(100.0)), run(Some(0.0)), run(None), run(Some(50.0))];
+        let download = compliance(&samples, &[], true);
+        assert_eq!((download.runs, download.available), (4, 2));
+        assert_eq!(download.availability(), 50.0);
+        assert!(download.expected.is_none());
+        // a latency of 0 is a value:
+        assert_eq!(compliance(&samples, &[], false).available, 3);
+    }
+
+    #[test]
+    fn tiers_are_compared_with_the_value_in_force() {
+        // the maximum tier is raised from 100 to 200 on the second day:
+        let samples = [
+            sample(day(1), 150.0, 100.0, &[100.0, 50.0]),
+            sample(day(1), 120.0, 100.0, &[100.0, 50.0]),
+            sample(day(2), 150.0, 200.0, &[200.0, 50.0]),
+            sample(day(2), 210.0, 200.0, &[200.0, 50.0]),
+        ];
+        let tiers = [String::from("maximum"), String::from("minimum")];
+        let e = compliance(&samples, &tiers, true).expected.unwrap();
+        assert_eq!(e.tiers[0].label, "maximum");
+        assert_eq!(e.tiers[0].values, vec![100.0, 200.0]);
+        assert_eq!(e.tiers[0].met, 75.0);
+        assert_eq!(e.tiers[1].values, vec![50.0]);
+        assert_eq!(e.tiers[1].met, 100.0);
+    }
+}
@@ -0,0 +1,325 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::fmt;
+use toml::value::Table;
+use toml::Value;
+
+use crate::error::SpeedTrackerError;
+use crate::lib_constants::*;
+
+/// merge overlay into base, tables are merged recursively,
+/// all other values of the overlay replace the value of the base
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base_table), Value::Table(overlay_table)) => {
+            for (key, overlay_value) in overlay_table {
+                match base_table.get_mut(&key) {
+                    Some(base_value) => merge(base_value, overlay_value),
+                    None => {
+                        base_table.insert(key, overlay_value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// merge the config file over the defaults. in contrast to merge, optional tables of the
+/// defaults are removed if the file has the surrounding table but leaves them out:
+/// e.g. a download_chart without expected_value must not get the default expected_value,
+/// the same holds for the optional keys of an expected_value (only tiers, no single value)
+pub fn merge_file(defaults: &mut Value, file: Value) {
+    if let (Value::Table(default_table), Value::Table(file_table)) = (&mut *defaults, &file) {
+        for (key, file_value) in file_table {
+            if let Some(default_value) = default_table.get_mut(key) {
+                remove_missing_tables(key, default_value, file_value);
+            }
+        }
+    }
+    merge(defaults, file);
+}
+
+fn remove_missing_tables(name: &str, default: &mut Value, file: &Value) {
+    if let (Value::Table(default_table), Value::Table(file_table)) = (default, file) {
+        let is_optional = |key: &str, v: &Value| {
+            v.is_table() || (name == KEY_EXPECTED_VALUE && EXPECTED_OPTIONAL_KEYS.contains(&key))
+        };
+        let missing: Vec<String> = default_table
+            .iter()
+            .filter(|(key, v)| is_optional(key, v) && !file_table.contains_key(*key))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in missing {
+            default_table.remove(&key);
+        }
+        for (key, file_value) in file_table {
+            if let Some(default_value) = default_table.get_mut(key) {
+                remove_missing_tables(key, default_value, file_value);
+            }
+        }
+    }
+}
+
+/// apply 'SPEEDTRACKER_<KEY>' environment variables to the config,
+/// nested keys are separated by '__', e.g. 'SPEEDTRACKER_DOWNLOAD_CHART__EXPECTED_VALUE__VALUE'.
+/// variables with an unknown key are ignored, a warning for each of them is returned
+pub fn apply_env_overrides<I>(config: &mut Value, vars: I) -> Result<Vec<String>, SpeedTrackerError>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut warnings = Vec::new();
+    for (name, raw) in vars {
+        let key = match name.strip_prefix(ENV_OVERRIDE_PREFIX) {
+            Some(k) if !ENV_RESERVED.contains(&name.as_str()) && !k.is_empty() => k,
+            _ => continue,
+        };
+        let path: Vec<String> = key.split("__").map(|k| k.to_lowercase()).collect();
+        match set_value(config, &path, &raw) {
+            Ok(()) => (),
+            Err(e @ SetError::UnknownKey(_)) => {
+                warnings.push(format!("environment variable {} ignored: {}", name, e))
+            }
+            Err(e) => return Err(SpeedTrackerError::Config(format!("{}: {}", name, e))),
+        }
+    }
+    Ok(warnings)
+}
+
+/// apply 'key.nested_key=value' overrides given on the command line
+pub fn apply_cli_overrides(
+    config: &mut Value,
+    overrides: &[String],
+) -> Result<(), SpeedTrackerError> {
+    for assignment in overrides {
+        let (key, raw) = assignment.split_once('=').ok_or_else(|| {
+            SpeedTrackerError::Config(format!("'{}' is not in the form key=value", assignment))
+        })?;
+        let path: Vec<String> = key.trim().split('.').map(String::from).collect();
+        set_value(config, &path, raw.trim())
+            .map_err(|e| SpeedTrackerError::Config(format!("--set {}: {}", assignment, e)))?;
+    }
+    Ok(())
+}
+
+/// copy of the config where values of sensitive keys (passwords, tokens ...) are hidden
+pub fn masked(value: &Value) -> Value {
+    match value {
+        Value::Table(table) => Value::Table(
+            table
+                .iter()
+                .map(|(key, v)| {
+                    let is_sensitive = SENSITIVE_KEY_PARTS.iter().any(|part| key.contains(part));
+                    if is_sensitive {
+                        (key.clone(), Value::String(String::from(MASKED_VALUE)))
+                    } else {
+                        (key.clone(), masked(v))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(masked).collect()),
+        v => v.clone(),
+    }
+}
+
+/// why a value could not be set
+enum SetError {
+    UnknownKey(String),
+    Invalid(String),
+}
+
+impl fmt::Display for SetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetError::UnknownKey(key) => write!(f, "unknown key '{}'", key),
+            SetError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<String> for SetError {
+    fn from(msg: String) -> Self {
+        SetError::Invalid(msg)
+    }
+}
+
+/// set the value at the path, the raw value is converted to the type of the existing value.
+/// new keys are only allowed in new optional tables (e.g. an 'expected_value' that was not configured)
+/// and for the optional keys of an expected_value, other missing tables are unknown keys
+fn set_value(config: &mut Value, path: &[String], raw: &str) -> Result<(), SetError> {
+    let (last, parents) = path.split_last().ok_or_else(|| String::from("empty key"))?;
+    let mut table: &mut Table = config
+        .as_table_mut()
+        .ok_or_else(|| String::from("config is not a table"))?;
+    let mut is_new_table = false;
+    for (depth, key) in parents.iter().enumerate() {
+        if !table.contains_key(key) {
+            if !is_optional_table(depth, key) {
+                return Err(SetError::UnknownKey(path.join(".")));
+            }
+            table.insert(key.clone(), Value::Table(Table::new()));
+            is_new_table = true;
+        }
+        table = table
+            .get_mut(key)
+            .and_then(Value::as_table_mut)
+            .ok_or_else(|| format!("'{}' is not a table", key))?;
+    }
+    let value = match table.get(last) {
+        Some(existing) => convert(existing, raw)?,
+        None if is_new_table || is_optional_expected_key(parents, last) => parse_untyped(raw),
+        None => return Err(SetError::UnknownKey(path.join("."))),
+    };
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+/// optional table on top level or of a chart
+fn is_optional_table(depth: usize, key: &str) -> bool {
+    match depth {
+        0 => OPTIONAL_TABLES.contains(&key),
+        1 => CHART_OPTIONAL_TABLES.contains(&key),
+        _ => false,
+    }
+}
+
+fn is_optional_expected_key(parents: &[String], key: &str) -> bool {
+    matches!(parents.last(), Some(p) if p == KEY_EXPECTED_VALUE)
+        && EXPECTED_OPTIONAL_KEYS.contains(&key)
+}
+
+fn convert(existing: &Value, raw: &str) -> Result<Value, String> {
+    let invalid = |type_name: &str| format!("'{}' is not a valid {}", raw, type_name);
+    match existing {
+        Value::String(_) => Ok(Value::String(String::from(raw))),
+        Value::Integer(_) => raw
+            .parse::<i64>()
+            .map(Value::Integer)
+            .map_err(|_| invalid("integer")),
+        Value::Float(_) => raw
+            .parse::<f64>()
+            .map(Value::Float)
+            .map_err(|_| invalid("number")),
+        Value::Boolean(_) => raw
+            .parse::<bool>()
+            .map(Value::Boolean)
+            .map_err(|_| invalid("boolean (true or false)")),
+        _ => Ok(parse_untyped(raw)),
+    }
+}
+
+/// interpret the raw value as toml value, fall back to a plain string
+fn parse_untyped(raw: &str) -> Value {
+    format!("v = {}", raw)
+        .parse::<Value>()
+        .ok()
+        .and_then(|mut v| v.as_table_mut().and_then(|t| t.remove("v")))
+        .unwrap_or_else(|| Value::String(String::from(raw)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (String::from(*k), String::from(*v)))
+            .collect()
+    }
+
+    #[test]
+    fn unknown_env_keys_are_ignored_with_a_warning() {
+        let mut config: Value = toml::from_str("run_interval_minutes = 120").unwrap();
+        let warnings = apply_env_overrides(
+            &mut config,
+            vars(&[
+                ("SPEEDTRACKER_HOME", "/opt/speedtracker"),
+                ("SPEEDTRACKER_RUN_INTERVAL_MINUTES", "60"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.get("run_interval_minutes"),
+            Some(&Value::Integer(60))
+        );
+        assert_eq!(config.get("home"), None);
+        assert_eq!(
+            warnings,
+            vec!["environment variable SPEEDTRACKER_HOME ignored: unknown key 'home'"]
+        );
+    }
+
+    #[test]
+    fn typos_in_nested_keys_create_no_tables() {
+        let mut config: Value = toml::from_str(
+            "[download_chart]\nlabel = 'Download'\n[download_chart.ewma]\nalpha = 0.3",
+        )
+        .unwrap();
+        let warnings = apply_env_overrides(
+            &mut config,
+            vars(&[("SPEEDTRACKER_DOWNLAOD_CHART__FILL", "true")]),
+        )
+        .unwrap();
+        assert_eq!(
+            warnings,
+            vec!["environment variable SPEEDTRACKER_DOWNLAOD_CHART__FILL ignored: unknown key 'downlaod_chart.fill'"]
+        );
+        assert!(apply_cli_overrides(&mut config, &[String::from("typo.x=1")]).is_err());
+        assert!(
+            apply_cli_overrides(&mut config, &[String::from("download_chart.ewmaa.alpha=1")])
+                .is_err()
+        );
+        assert_eq!(config.get("downlaod_chart"), None);
+        assert_eq!(config.get("typo"), None);
+        assert_eq!(config["download_chart"].get("ewmaa"), None);
+        // known optional tables may be added:
+        apply_cli_overrides(
+            &mut config,
+            &[
+                String::from("download_chart.expected_value.value=250.0"),
+                String::from("runner.backend=ookla"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            config["download_chart"]["expected_value"].get("value"),
+            Some(&Value::Float(250.0))
+        );
+        assert_eq!(
+            config["runner"].get("backend"),
+            Some(&Value::String(String::from("ookla")))
+        );
+    }
+
+    #[test]
+    fn invalid_env_values_are_an_error() {
+        let mut config: Value = toml::from_str("run_interval_minutes = 120").unwrap();
+        let result = apply_env_overrides(
+            &mut config,
+            vars(&[("SPEEDTRACKER_RUN_INTERVAL_MINUTES", "hourly")]),
+        );
+        assert!(result.is_err());
+    }
+}
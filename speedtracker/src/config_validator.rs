@@ -0,0 +1,753 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::NaiveDate;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use toml::Value;
+
+use crate::campaign::{CampaignConfig, ContractSpeeds};
+use crate::chart_config::*;
+use crate::init::cron_line;
+use crate::lib_constants::*;
+use crate::runner::{Backend, RunnerConfig};
+use crate::{file_access_problems, layer_config, path_access_problems, Config};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// one finding of the config validation
+#[derive(Debug)]
+pub struct ConfigProblem {
+    pub severity: Severity,
+    /// line and column (starting with 1) in the config file if known
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.position {
+            Some((line, column)) => {
+                write!(f, "{}:{}: {}: {}", line, column, severity, self.message)
+            }
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// validate the config file and the effective config (incl. environment and command
+/// line overrides), all found problems are returned at once
+pub fn validate_config_file(path: &Path, cli_overrides: &[String]) -> Vec<ConfigProblem> {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => return vec![problem(Severity::Error, None, format!("{:?}: {}", path, e))],
+    };
+    let file_value: Value = match toml::from_str(&content) {
+        Ok(v) => v,
+        Err(e) => {
+            // syntax errors stop the validation, everything after it is unknown:
+            let position = e.line_col().map(|(line, col)| (line + 1, col + 1));
+            return vec![problem(Severity::Error, position, e.to_string())];
+        }
+    };
+    let positions = key_positions(&content);
+    let mut rs = Vec::new();
+    check_structure(&schema(), &file_value, "", &positions, &mut rs);
+    if rs.iter().any(|p| p.severity == Severity::Error) {
+        // config can not be deserialized, values can not be checked:
+        return rs;
+    }
+    match layer_config(file_value, cli_overrides) {
+        Ok((config, warnings)) => {
+            for warning in warnings {
+                rs.push(problem(Severity::Warning, None, warning));
+            }
+            check_values(&config, &positions, &mut rs)
+        }
+        Err(e) => rs.push(problem(Severity::Error, None, e.to_string())),
+    }
+    // problems without position (from overrides) at the end:
+    rs.sort_by_key(|p| p.position.unwrap_or((usize::MAX, usize::MAX)));
+    rs
+}
+
+fn problem(severity: Severity, position: Option<(usize, usize)>, message: String) -> ConfigProblem {
+    ConfigProblem {
+        severity,
+        position,
+        message,
+    }
+}
+
+/// a config where every optional part is set, used to know all keys and their types
+fn schema() -> Value {
+    let mut config = Config::default();
+    config.latency_chart.expected_value = Some(schema_expected(DEFAULT_LATENCY_VALUE));
+    config.jitter_chart.expected_value = Some(schema_expected(DEFAULT_JITTER_VALUE));
+    config.download_chart.expected_value = Some(schema_expected(DEFAULT_DOWNLOAD_VALUE));
+    config.upload_chart.expected_value = Some(schema_expected(DEFAULT_UPLOAD_VALUE));
+    config.latency_chart.forecast = Some(schema_forecast());
+    config.jitter_chart.forecast = Some(schema_forecast());
+    config.download_chart.forecast = Some(schema_forecast());
+    config.upload_chart.forecast = Some(schema_forecast());
+    for chart_trends in [
+        (
+            &mut config.latency_chart.rolling_median,
+            &mut config.latency_chart.ewma,
+        ),
+        (
+            &mut config.jitter_chart.rolling_median,
+            &mut config.jitter_chart.ewma,
+        ),
+        (
+            &mut config.download_chart.rolling_median,
+            &mut config.download_chart.ewma,
+        ),
+        (
+            &mut config.upload_chart.rolling_median,
+            &mut config.upload_chart.ewma,
+        ),
+    ] {
+        *chart_trends.0 = Some(schema_trend());
+        *chart_trends.1 = Some(schema_trend());
+    }
+    config.campaign = Some(CampaignConfig::default());
+    config.runner.args = vec![String::new()];
+    Value::try_from(config).expect("default config is serializable")
+}
+
+fn schema_forecast() -> ForecastConfig {
+    ForecastConfig {
+        label: String::new(),
+        border_color: String::new(),
+        days: 0,
+    }
+}
+
+fn schema_trend() -> TrendConfig {
+    TrendConfig {
+        label: String::new(),
+        border_color: String::new(),
+        window: 0,
+        window_unit: DEFAULT_WINDOW_UNIT,
+    }
+}
+
+fn schema_expected<T: Copy>(value: T) -> ExpectedConfig<T> {
+    ExpectedConfig {
+        label: String::new(),
+        fill: DEFAULT_FILL,
+        border_color: String::new(),
+        value: Some(value),
+        tiers: vec![TierConfig {
+            label: String::new(),
+            fill: DEFAULT_FILL,
+            border_color: String::new(),
+            value,
+            history: vec![ExpectedChange {
+                valid_from: NaiveDate::MIN,
+                value,
+            }],
+        }],
+        reference: Some(String::new()),
+        history: vec![ExpectedChange {
+            valid_from: NaiveDate::MIN,
+            value,
+        }],
+    }
+}
+
+/// compare keys and types of the file with the schema
+fn check_structure(
+    schema: &Value,
+    file: &Value,
+    path: &str,
+    positions: &HashMap<String, (usize, usize)>,
+    rs: &mut Vec<ConfigProblem>,
+) {
+    let position = positions.get(path).copied();
+    match (schema, file) {
+        (Value::Table(schema_table), Value::Table(file_table)) => {
+            for (key, file_value) in file_table {
+                let key_path = join_key(path, key);
+                match schema_table.get(key) {
+                    Some(schema_value) => {
+                        check_structure(schema_value, file_value, &key_path, positions, rs)
+                    }
+                    None => rs.push(problem(
+                        Severity::Error,
+                        positions.get(&key_path).copied(),
+                        format!("unknown key '{}'", key_path),
+                    )),
+                }
+            }
+            for (key, schema_value) in schema_table {
+                // nested tables are optional, only the charts on top level are expected:
+                let is_optional = (schema_value.is_table()
+                    && (!path.is_empty() || OPTIONAL_TABLES.contains(&key.as_str())))
+                    || (path.ends_with(KEY_EXPECTED_VALUE)
+                        && EXPECTED_OPTIONAL_KEYS.contains(&key.as_str()))
+                    || (path.ends_with(KEY_TIERS) && TIER_OPTIONAL_KEYS.contains(&key.as_str()));
+                if !is_optional && !file_table.contains_key(key) {
+                    rs.push(problem(
+                        Severity::Warning,
+                        position,
+                        if schema_value.is_table() {
+                            format!("table [{}] is missing, the defaults are used", key)
+                        } else {
+                            format!(
+                                "key '{}' is missing, the default {} is used",
+                                join_key(path, key),
+                                schema_value
+                            )
+                        },
+                    ));
+                }
+            }
+        }
+        (Value::Array(schema_values), Value::Array(file_values)) => {
+            if let Some(schema_value) = schema_values.first() {
+                for file_value in file_values {
+                    check_structure(schema_value, file_value, path, positions, rs);
+                }
+            }
+        }
+        (Value::Integer(_), Value::Integer(i)) if *i < 0 => rs.push(problem(
+            Severity::Error,
+            position,
+            format!("'{}' = {} must not be negative", path, i),
+        )),
+        // floats can be written as integer:
+        (Value::Float(_), Value::Integer(_)) => (),
+        (schema_value, file_value) if schema_value.type_str() != file_value.type_str() => {
+            rs.push(problem(
+                Severity::Error,
+                position,
+                format!(
+                    "'{}' must be {}, found {} {}",
+                    path,
+                    type_name(schema_value),
+                    type_name(file_value),
+                    file_value
+                ),
+            ))
+        }
+        _ => (),
+    }
+}
+
+/// check the values of the effective config
+fn check_values(
+    config: &Config,
+    positions: &HashMap<String, (usize, usize)>,
+    rs: &mut Vec<ConfigProblem>,
+) {
+    let mut error = |key: &str, message: String| {
+        rs.push(problem(
+            Severity::Error,
+            positions.get(key).copied(),
+            message,
+        ))
+    };
+    if config.output_xdays == 0 {
+        error(
+            "output_xdays",
+            String::from("'output_xdays' must be at least 1"),
+        );
+    }
+    if cron_line(config.run_interval_minutes, Path::new("")).is_none() {
+        error(
+            "run_interval_minutes",
+            format!(
+                "'run_interval_minutes' = {} can not be scheduled by cron \
+                 (use a divisor of 60 or a multiple of 60 that divides 1440)",
+                config.run_interval_minutes
+            ),
+        );
+    }
+    if config.outage_missed_intervals == 0 {
+        error(
+            "outage_missed_intervals",
+            String::from("'outage_missed_intervals' must be at least 1"),
+        );
+    }
+    if config.rollup_daily_after_days == 0 {
+        error(
+            "rollup_daily_after_days",
+            String::from("'rollup_daily_after_days' must be at least 1"),
+        );
+    }
+    if config.rollup_weekly_after_days < config.rollup_daily_after_days {
+        error(
+            "rollup_weekly_after_days",
+            format!(
+                "'rollup_weekly_after_days' = {} must not be less than 'rollup_daily_after_days' = {}",
+                config.rollup_weekly_after_days, config.rollup_daily_after_days
+            ),
+        );
+    }
+    if config.max_points != 0 && config.max_points < DOWNSAMPLING_MIN_POINTS {
+        error(
+            "max_points",
+            format!(
+                "'max_points' must be 0 (every point) or at least {}",
+                DOWNSAMPLING_MIN_POINTS
+            ),
+        );
+    }
+    if config.change_min_samples == 0 {
+        error(
+            "change_min_samples",
+            String::from("'change_min_samples' must be at least 1"),
+        );
+    }
+    if config.change_min_shift_percent < 0.0 {
+        error(
+            "change_min_shift_percent",
+            String::from("'change_min_shift_percent' must not be negative"),
+        );
+    }
+    for msg in path_access_problems(Path::new(&config.data_dir)) {
+        error("data_dir", format!("'data_dir': {}", msg));
+    }
+    for msg in file_access_problems(Path::new(&config.output_file)) {
+        error("output_file", format!("'output_file': {}", msg));
+    }
+    for msg in file_access_problems(Path::new(&config.log_file)) {
+        error("log_file", format!("'log_file': {}", msg));
+    }
+    check_chart(&config.latency_chart, KEY_LATENCY_CHART, &mut error);
+    check_chart(&config.jitter_chart, KEY_JITTER_CHART, &mut error);
+    check_chart(&config.download_chart, KEY_DOWNLOAD_CHART, &mut error);
+    check_chart(&config.upload_chart, KEY_UPLOAD_CHART, &mut error);
+    if let Some(campaign) = &config.campaign {
+        // the contract speeds can change with every change of a tier:
+        let mut dates: Vec<NaiveDate> = [&config.download_chart, &config.upload_chart]
+            .iter()
+            .filter_map(|c| c.expected_value.as_ref())
+            .flat_map(|e| e.tiers.iter().flat_map(|t| t.history.iter()))
+            .map(|c| c.valid_from)
+            .chain(std::iter::once(NaiveDate::MIN))
+            .collect();
+        dates.sort();
+        dates.dedup();
+        for date in dates {
+            match campaign.contract(&config.download_chart, &config.upload_chart, date) {
+                Ok(contract) => {
+                    check_contract(&contract.download, KEY_DOWNLOAD_CHART, &mut error);
+                    check_contract(&contract.upload, KEY_UPLOAD_CHART, &mut error);
+                }
+                Err(msg) => {
+                    error(KEY_CAMPAIGN, format!("'{}': {}", KEY_CAMPAIGN, msg));
+                    break;
+                }
+            }
+        }
+    }
+    check_runner(&config.runner, &mut error);
+}
+
+/// iperf3 needs an own server, speedtestJson can not choose one
+fn check_runner(runner: &RunnerConfig, error: &mut dyn FnMut(&str, String)) {
+    let key = join_key(KEY_RUNNER, "server");
+    match runner.backend {
+        Backend::Iperf3 if runner.server.is_empty() => error(
+            &key,
+            format!("'{}' is needed by backend '{}'", key, runner.backend),
+        ),
+        Backend::SpeedtestJson if !runner.server.is_empty() => error(
+            &key,
+            format!(
+                "'{}' is not supported by backend '{}', leave it empty",
+                key, runner.backend
+            ),
+        ),
+        _ => (),
+    }
+}
+
+/// contract speeds have to be ordered: minimum <= normal <= maximum
+fn check_contract(speeds: &ContractSpeeds, chart_key: &str, error: &mut dyn FnMut(&str, String)) {
+    if speeds.minimum <= 0.0 || speeds.minimum > speeds.normal || speeds.normal > speeds.maximum {
+        error(
+            KEY_CAMPAIGN,
+            format!(
+                "the campaign tiers of '{}' need 0 < minimum <= normal <= maximum, found {} / {} / {}",
+                chart_key, speeds.minimum, speeds.normal, speeds.maximum
+            ),
+        );
+    }
+}
+
+fn check_chart<T: Into<f64> + Copy>(
+    chart: &ChartConfig<T>,
+    key: &str,
+    error: &mut dyn FnMut(&str, String),
+) {
+    check_color(&chart.border_color, &join_key(key, "border_color"), error);
+    if chart.statistics.is_empty() {
+        let statistics_key = join_key(key, "statistics");
+        error(
+            &statistics_key,
+            format!("'{}' needs at least one statistic", statistics_key),
+        );
+    }
+    for (trend, trend_key) in [
+        (&chart.rolling_median, KEY_ROLLING_MEDIAN),
+        (&chart.ewma, KEY_EWMA),
+    ] {
+        if let Some(trend) = trend {
+            let trend_key = join_key(key, trend_key);
+            check_color(
+                &trend.border_color,
+                &join_key(&trend_key, "border_color"),
+                error,
+            );
+            if trend.window == 0 {
+                let window_key = join_key(&trend_key, "window");
+                error(&window_key, format!("'{}' must be at least 1", window_key));
+            }
+        }
+    }
+    if let Some(forecast) = &chart.forecast {
+        let forecast_key = join_key(key, KEY_FORECAST);
+        check_color(
+            &forecast.border_color,
+            &join_key(&forecast_key, "border_color"),
+            error,
+        );
+        if forecast.days == 0 {
+            let days_key = join_key(&forecast_key, "days");
+            error(&days_key, format!("'{}' must be at least 1", days_key));
+        }
+    }
+    if chart.anomaly_threshold < 0.0 {
+        let threshold_key = join_key(key, "anomaly_threshold");
+        error(
+            &threshold_key,
+            format!("'{}' must not be negative", threshold_key),
+        );
+    }
+    if let Some(expected) = &chart.expected_value {
+        let expected_key = join_key(key, KEY_EXPECTED_VALUE);
+        check_color(
+            &expected.border_color,
+            &join_key(&expected_key, "border_color"),
+            error,
+        );
+        if expected.value.is_none() && expected.history.is_empty() {
+            if expected.tiers.is_empty() {
+                error(
+                    &expected_key,
+                    format!("'{}' needs a 'value', 'tiers' or 'history'", expected_key),
+                );
+            } else if expected.reference.is_none() {
+                error(
+                    &expected_key,
+                    format!(
+                        "'{}' needs a 'value' or the 'reference' tier the compliance levels refer to",
+                        expected_key
+                    ),
+                );
+            }
+        }
+        if let Some(reference) = &expected.reference {
+            if !expected.tiers.iter().any(|t| &t.label == reference) {
+                let reference_key = join_key(&expected_key, "reference");
+                error(
+                    &reference_key,
+                    format!(
+                        "'{}' = '{}' is not the label of one of the tiers",
+                        reference_key, reference
+                    ),
+                );
+            }
+        }
+        let value_key = join_key(&expected_key, "value");
+        if matches!(expected.value, Some(v) if v.into() <= 0.0) {
+            error(
+                &value_key,
+                format!("'{}' must be greater than 0", value_key),
+            );
+        }
+        let history_key = join_key(&expected_key, "history");
+        check_history(&expected.history, &history_key, &history_key, error);
+        let tiers_key = join_key(&expected_key, KEY_TIERS);
+        for tier in &expected.tiers {
+            let tier_key = format!("{} '{}'", tiers_key, tier.label);
+            check_color(&tier.border_color, &tier_key, error);
+            if tier.value.into() <= 0.0 {
+                error(
+                    &tiers_key,
+                    format!("value of '{}' must be greater than 0", tier_key),
+                );
+            }
+            check_history(
+                &tier.history,
+                &tiers_key,
+                &format!("{} history", tier_key),
+                error,
+            );
+        }
+    }
+}
+
+/// values of a history have to be positive, with one value per day at most,
+/// errors are reported at the key and name the history
+fn check_history<T: Into<f64> + Copy>(
+    history: &[ExpectedChange<T>],
+    key: &str,
+    history_key: &str,
+    error: &mut dyn FnMut(&str, String),
+) {
+    for (i, change) in history.iter().enumerate() {
+        if change.value.into() <= 0.0 {
+            error(
+                key,
+                format!(
+                    "value of '{}' valid from {} must be greater than 0",
+                    history_key, change.valid_from
+                ),
+            );
+        }
+        if history[..i]
+            .iter()
+            .any(|c| c.valid_from == change.valid_from)
+        {
+            error(
+                key,
+                format!(
+                    "'{}' has more than one value valid from {}",
+                    history_key, change.valid_from
+                ),
+            );
+        }
+    }
+}
+
+fn check_color(color: &str, key: &str, error: &mut dyn FnMut(&str, String)) {
+    if !is_chart_js_color(color) {
+        error(
+            key,
+            format!(
+                "'{}' = '{}' is no color Chart.js understands (use a name like 'green', \
+                 '#00ff00', 'rgb(0,255,0)' or 'hsl(120,100%,50%)')",
+                key, color
+            ),
+        );
+    }
+}
+
+/// colors as understood by chartjs-color: names, #rgb, #rrggbb, rgb(a), hsl(a) and hwb
+fn is_chart_js_color(color: &str) -> bool {
+    let color = color.trim().to_lowercase();
+    if CSS_COLOR_NAMES.contains(&color.as_str()) {
+        return true;
+    }
+    let color_regex = Regex::new(COLOR_REGEX).unwrap();
+    color_regex.is_match(&color)
+}
+
+/// position of every table and key in the file, e.g. "download_chart.expected_value.value"
+fn key_positions(content: &str) -> HashMap<String, (usize, usize)> {
+    let mut rs = HashMap::new();
+    let mut table = String::new();
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let column = line.len() - trimmed.len() + 1;
+        if trimmed.starts_with('#') || trimmed.is_empty() {
+            continue;
+        }
+        if let Some(header) = trimmed.strip_prefix('[') {
+            let name = header
+                .trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or_default();
+            table = name
+                .split('.')
+                .map(unquote)
+                .collect::<Vec<&str>>()
+                .join(".");
+            rs.insert(table.clone(), (index + 1, column));
+        } else if let Some((key, _)) = trimmed.split_once('=') {
+            let key = key.split('.').map(unquote).collect::<Vec<&str>>().join(".");
+            rs.insert(join_key(&table, &key), (index + 1, column));
+        }
+    }
+    rs
+}
+
+fn unquote(key: &str) -> &str {
+    key.trim().trim_matches('"').trim_matches('\'')
+}
+
+fn join_key(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        String::from(key)
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::String(_) => "a string",
+        Value::Integer(_) => "an integer",
+        Value::Float(_) => "a number",
+        Value::Boolean(_) => "a boolean",
+        Value::Datetime(_) => "a date",
+        Value::Array(_) => "a list",
+        Value::Table(_) => "a table",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// the findings for the content written to a temporary config file
+    fn validate(name: &str, content: &str) -> Vec<ConfigProblem> {
+        let file = env::temp_dir().join(format!(
+            "speedtracker-validator-{}-{}.toml",
+            std::process::id(),
+            name
+        ));
+        fs::write(&file, content).unwrap();
+        let rs = validate_config_file(&file, &[]);
+        fs::remove_file(&file).unwrap();
+        rs
+    }
+
+    /// the defaults with all paths in the temporary directory
+    fn clean_config() -> Config {
+        let dir = env::temp_dir();
+        Config {
+            data_dir: dir.to_string_lossy().into_owned(),
+            output_file: dir.join("index.html").to_string_lossy().into_owned(),
+            log_file: dir.join("speedtracker.log").to_string_lossy().into_owned(),
+            ..Config::default()
+        }
+    }
+
+    fn to_toml(config: &Config) -> String {
+        Value::try_from(config).unwrap().to_string()
+    }
+
+    fn messages(problems: &[ConfigProblem]) -> Vec<String> {
+        problems.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn clean_config_has_no_findings() {
+        let problems = validate("clean", &to_toml(&clean_config()));
+        assert!(problems.is_empty(), "{:?}", messages(&problems));
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_with_their_position() {
+        let mut content = format!("colour = 'red'\n{}", to_toml(&clean_config()));
+        let line = content.lines().count() + 1;
+        content.push_str("[download_chart.ewmaa]\n  alpha = 0.3\n");
+        let problems = validate("unknown", &content);
+        assert_eq!(
+            messages(&problems),
+            vec![
+                String::from("1:1: error: unknown key 'colour'"),
+                format!("{}:1: error: unknown key 'download_chart.ewmaa'", line),
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_types_are_errors() {
+        let content =
+            to_toml(&clean_config()).replace("output_xdays = 14", "output_xdays = 'two weeks'");
+        let line = content
+            .lines()
+            .position(|l| l.starts_with("output_xdays"))
+            .unwrap()
+            + 1;
+        let problems = validate("types", &content);
+        assert_eq!(problems.len(), 1, "{:?}", messages(&problems));
+        assert_eq!(problems[0].severity, Severity::Error);
+        assert_eq!(problems[0].position, Some((line, 1)));
+        assert!(problems[0]
+            .message
+            .starts_with("'output_xdays' must be an integer"));
+    }
+
+    #[test]
+    fn colors_chart_js_does_not_know_are_errors() {
+        let mut config = clean_config();
+        config.download_chart.border_color = String::from("grean");
+        config.upload_chart.border_color = String::from("#12345");
+        let problems = validate("colors", &to_toml(&config));
+        let messages = messages(&problems);
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert!(messages[0].contains("'download_chart.border_color' = 'grean' is no color"));
+        assert!(messages[1].contains("'upload_chart.border_color' = '#12345' is no color"));
+    }
+
+    #[test]
+    fn intervals_must_be_positive() {
+        let mut config = clean_config();
+        config.output_xdays = 0;
+        config.outage_missed_intervals = 0;
+        config.run_interval_minutes = 0;
+        config.download_chart.rolling_median = Some(TrendConfig {
+            label: String::from("median"),
+            border_color: String::from("red"),
+            window: 0,
+            window_unit: DEFAULT_WINDOW_UNIT,
+        });
+        let problems = validate("intervals", &to_toml(&config));
+        assert!(problems.iter().all(|p| p.severity == Severity::Error));
+        let messages = messages(&problems);
+        for expected in [
+            "'output_xdays' must be at least 1",
+            "'run_interval_minutes' = 0 can not be scheduled by cron",
+            "'outage_missed_intervals' must be at least 1",
+            "'download_chart.rolling_median.window' must be at least 1",
+        ] {
+            assert!(
+                messages.iter().any(|m| m.contains(expected)),
+                "{} not in {:?}",
+                expected,
+                messages
+            );
+        }
+    }
+}
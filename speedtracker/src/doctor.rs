@@ -0,0 +1,542 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::{Local, NaiveDate};
+use faccess::PathExt;
+use regex::Regex;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::file_locator::*;
+use crate::json_parser::JsonParser;
+use crate::lib_constants::*;
+use crate::runner::{Backend, RunnerConfig};
+use crate::{
+    file_access_problems, get_data_file_name, locate_config, path_access_problems,
+    read_config_file, read_data_file_paths, read_output_file, Config,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// result of one check of 'doctor'
+#[derive(Debug)]
+pub struct DoctorCheck {
+    pub status: CheckStatus,
+    /// what was checked, e.g. 'template'
+    pub name: &'static str,
+    pub message: String,
+    /// suggested fix if the check did not pass
+    pub fix: Option<String>,
+}
+
+/// check the whole installation, all checks are done even if one fails
+pub fn run_doctor(locator: &FileLocator, cli_overrides: &[String]) -> Vec<DoctorCheck> {
+    let mut rs = Vec::new();
+    let config = check_config(locator, cli_overrides, &mut rs);
+    rs.push(check_template(locator));
+    let runner = match &config {
+        Some(c) => c.runner.clone(),
+        None => Config::default().runner,
+    };
+    rs.push(check_speed_test_cmd(locator, &runner));
+    if let Some(config) = config {
+        rs.push(check_data_dir(&config));
+        rs.push(check_output_file(&config));
+        rs.push(check_last_entry(&config));
+        rs.push(check_recent_runs(&config));
+        rs.push(check_current_data_file(&config));
+    }
+    rs
+}
+
+fn pass(name: &'static str, message: String) -> DoctorCheck {
+    DoctorCheck {
+        status: CheckStatus::Pass,
+        name,
+        message,
+        fix: None,
+    }
+}
+
+fn warn(name: &'static str, message: String, fix: &str) -> DoctorCheck {
+    DoctorCheck {
+        status: CheckStatus::Warn,
+        name,
+        message,
+        fix: Some(String::from(fix)),
+    }
+}
+
+fn fail(name: &'static str, message: String, fix: &str) -> DoctorCheck {
+    DoctorCheck {
+        status: CheckStatus::Fail,
+        name,
+        message,
+        fix: Some(String::from(fix)),
+    }
+}
+
+fn check_config(
+    locator: &FileLocator,
+    cli_overrides: &[String],
+    rs: &mut Vec<DoctorCheck>,
+) -> Option<Config> {
+    let name = "config";
+    let config_file = match locate_config(locator) {
+        Ok(f) => f,
+        Err(e) => {
+            rs.push(fail(
+                name,
+                e.to_string(),
+                "run 'speedtracker init' or use '--config FILE'",
+            ));
+            return None;
+        }
+    };
+    match read_config_file(&config_file, cli_overrides) {
+        Ok((config, warnings)) => {
+            rs.push(pass(name, format!("{} parses", config_file.display())));
+            for warning in warnings {
+                rs.push(warn(
+                    name,
+                    warning,
+                    "remove the environment variable or correct its name",
+                ));
+            }
+            Some(config)
+        }
+        Err(e) => {
+            rs.push(fail(
+                name,
+                e.to_string(),
+                "run 'speedtracker validate-config' to see all problems of the config",
+            ));
+            None
+        }
+    }
+}
+
+fn check_template(locator: &FileLocator) -> DoctorCheck {
+    let name = "template";
+    match locator.locate(LocatedFile::Template) {
+        Ok(template_file) => check_template_file(&template_file),
+        Err(searched) => fail(
+            name,
+            format!(
+                "{} not found, {}",
+                TEMPLATE_FILENAME,
+                format_searched(&searched)
+            ),
+            "copy 'pi_files/template.html' next to the config or run 'speedtracker init'",
+        ),
+    }
+}
+
+/// the template has to contain every placeholder and no unknown one
+fn check_template_file(template_file: &Path) -> DoctorCheck {
+    let name = "template";
+    let content = match fs::read_to_string(template_file) {
+        Ok(c) => c,
+        Err(e) => {
+            return fail(
+                name,
+                format!("could not read {:?}: {}", template_file, e),
+                "make the template readable for the user running speedtracker",
+            )
+        }
+    };
+    let find_replacement = Regex::new(REPLACEMENT_REGEX).unwrap();
+    let found: Vec<&str> = find_replacement
+        .find_iter(&content)
+        .map(|mat| &content[mat.start() + 2..mat.end() - 1])
+        .collect();
+    let missing: Vec<String> = TEMPLATE_PLACEHOLDERS
+        .iter()
+        .filter(|id| !found.contains(id))
+        .map(|id| format!("${{{}}}", id))
+        .collect();
+    let unknown: Vec<String> = found
+        .iter()
+        .filter(|id| !TEMPLATE_PLACEHOLDERS.contains(id))
+        .map(|id| format!("${{{}}}", id))
+        .collect();
+    if !missing.is_empty() {
+        warn(
+            name,
+            format!(
+                "{} does not contain {}, this part of the page stays empty",
+                template_file.display(),
+                missing.join(", ")
+            ),
+            "compare the template with 'pi_files/template.html'",
+        )
+    } else if !unknown.is_empty() {
+        warn(
+            name,
+            format!(
+                "{} contains unknown placeholders {}, they are not replaced",
+                template_file.display(),
+                unknown.join(", ")
+            ),
+            "check the spelling of the placeholders",
+        )
+    } else {
+        pass(
+            name,
+            format!("{} contains all placeholders", template_file.display()),
+        )
+    }
+}
+
+fn check_speed_test_cmd(locator: &FileLocator, runner: &RunnerConfig) -> DoctorCheck {
+    let name = "speed test";
+    let fix = match runner.backend {
+        Backend::SpeedtestJson if runner.command.is_empty() => {
+            String::from("compile SpeedTest and copy speedtestJson next to speedtracker")
+        }
+        backend => format!("install the program of {} or set 'runner.command'", backend),
+    };
+    let program = match runner.program(locator) {
+        Ok(p) => p,
+        Err(msg) => return fail(name, msg, &fix),
+    };
+    match find_program(&program) {
+        Some(cmd) if !cmd.is_file() => {
+            fail(name, format!("{} does not exist", cmd.display()), &fix)
+        }
+        Some(cmd) if cmd.executable() => pass(
+            name,
+            format!("{} ({}) is executable", cmd.display(), runner.backend),
+        ),
+        Some(cmd) => fail(
+            name,
+            format!("{} is not executable", cmd.display()),
+            &format!("chmod +x {}", cmd.display()),
+        ),
+        None => fail(
+            name,
+            format!("{} not found in PATH", program.display()),
+            &fix,
+        ),
+    }
+}
+
+/// a program given by name is searched in PATH like the shell does
+fn find_program(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return Some(program.to_path_buf());
+    }
+    env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join(program))
+            .find(|p| p.is_file())
+    })
+}
+
+fn check_data_dir(config: &Config) -> DoctorCheck {
+    let name = "data dir";
+    let problems = path_access_problems(Path::new(&config.data_dir));
+    if problems.is_empty() {
+        pass(name, format!("{} is writable", config.data_dir))
+    } else {
+        fail(
+            name,
+            problems.join(", "),
+            "create the directory and give the cron user write permission or correct 'data_dir'",
+        )
+    }
+}
+
+fn check_output_file(config: &Config) -> DoctorCheck {
+    let name = "output file";
+    let problems = file_access_problems(Path::new(&config.output_file));
+    if problems.is_empty() {
+        pass(name, format!("{} is writable", config.output_file))
+    } else {
+        fail(
+            name,
+            problems.join(", "),
+            "give the cron user write permission on the webserver directory or correct 'output_file'",
+        )
+    }
+}
+
+/// the newest entry should not be older than two run intervals
+fn check_last_entry(config: &Config) -> DoctorCheck {
+    let name = "last entry";
+    let fix = "check the cron job ('crontab -l') and the recent runs below";
+    let now = Local::now().naive_local();
+    let data_files = match read_data_file_paths(
+        Path::new(&config.data_dir),
+        &get_data_file_name(&NaiveDate::MIN),
+        &get_data_file_name(&now.date()),
+    ) {
+        Ok(files) => files,
+        Err(e) => return fail(name, e.to_string(), fix),
+    };
+    // newest file with a valid entry:
+    let last_entry = data_files.iter().rev().find_map(|file| {
+        read_output_file(Path::new(file), &NaiveDate::MIN, &NaiveDate::MAX)
+            .ok()?
+            .into_iter()
+            .map(|(_, entry)| entry.timestamp)
+            .max()
+    });
+    let last_entry = match last_entry {
+        Some(t) => t,
+        None => return warn(name, format!("no entry in {}", config.data_dir), fix),
+    };
+    let age_minutes = (now - last_entry).num_minutes();
+    let message = format!(
+        "last entry written at {} ({})",
+        last_entry.format(DATE_TIME_FORMAT),
+        format_age(age_minutes)
+    );
+    if age_minutes <= 2 * i64::from(config.run_interval_minutes) {
+        pass(name, message)
+    } else {
+        warn(
+            name,
+            format!(
+                "{}, expected every {} minutes",
+                message, config.run_interval_minutes
+            ),
+            fix,
+        )
+    }
+}
+
+fn format_age(minutes: i64) -> String {
+    if minutes < 120 {
+        format!("{} minutes ago", minutes)
+    } else if minutes < 48 * 60 {
+        format!("{} hours ago", minutes / 60)
+    } else {
+        format!("{} days ago", minutes / (24 * 60))
+    }
+}
+
+/// look for the last runs of the speed test in the log files
+fn check_recent_runs(config: &Config) -> DoctorCheck {
+    let name = "recent runs";
+    let mut log_files: Vec<_> = match fs::read_dir(&config.log_file) {
+        Ok(entries) => entries.filter_map(|e| Some(e.ok()?.path())).collect(),
+        Err(e) => {
+            return warn(
+                name,
+                format!("could not read log directory {}: {}", config.log_file, e),
+                "run 'speedtracker run' once or correct 'log_file'",
+            )
+        }
+    };
+    // rotated files are numbered, the current file is the newest:
+    log_files.sort_by_key(|f| fs::metadata(f).and_then(|m| m.modified()).ok());
+    let runs: Vec<String> = log_files
+        .iter()
+        .filter_map(|f| fs::read_to_string(f).ok())
+        .flat_map(|content| {
+            content
+                .lines()
+                .filter(|l| l.contains(RUN_LOG_OK) || l.contains(RUN_LOG_ERROR))
+                .map(String::from)
+                .collect::<Vec<String>>()
+        })
+        .collect();
+    let recent = &runs[runs.len().saturating_sub(DOCTOR_RECENT_RUNS)..];
+    let failed: Vec<&String> = recent
+        .iter()
+        .filter(|l| l.contains(RUN_LOG_ERROR))
+        .collect();
+    let fix = "run 'speedtracker run' manually and check the output of speedtestJson";
+    match (recent.last(), failed.last()) {
+        (None, _) => warn(
+            name,
+            format!("no speed test run found in {}", config.log_file),
+            "check the cron job ('crontab -l')",
+        ),
+        (Some(_), None) => pass(name, format!("last {} runs succeeded", recent.len())),
+        (Some(last), Some(last_failed)) => {
+            let message = format!(
+                "{} of the last {} runs failed, last failure: {}",
+                failed.len(),
+                recent.len(),
+                last_failed
+            );
+            if last.contains(RUN_LOG_ERROR) {
+                fail(name, message, fix)
+            } else {
+                warn(name, message, fix)
+            }
+        }
+    }
+}
+
+/// count the lines of the current monthly file that can not be parsed
+fn check_current_data_file(config: &Config) -> DoctorCheck {
+    let name = "data file";
+    let today = Local::now().naive_local().date();
+    let data_file = Path::new(&config.data_dir).join(get_data_file_name(&today));
+    let content = match fs::read_to_string(&data_file) {
+        Ok(c) => c,
+        Err(_) if !data_file.exists() => {
+            return pass(name, format!("{} does not exist yet", data_file.display()))
+        }
+        Err(e) => {
+            return fail(
+                name,
+                format!("could not read {}: {}", data_file.display(), e),
+                "give the cron user read and write permission on the data files",
+            )
+        }
+    };
+    let invalid_lines: Vec<String> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && JsonParser::parse(line).is_err())
+        .map(|(index, _)| (index + 1).to_string())
+        .collect();
+    if invalid_lines.is_empty() {
+        pass(name, format!("all lines of {} parse", data_file.display()))
+    } else {
+        warn(
+            name,
+            format!(
+                "{} line(s) of {} do not parse (line {}), they are ignored",
+                invalid_lines.len(),
+                data_file.display(),
+                invalid_lines.join(", ")
+            ),
+            "remove or repair these lines, e.g. after a disk full",
+        )
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDateTime};
+
+    /// empty temporary directory for the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "speedtracker-doctor-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn placeholders(ids: &[&str]) -> String {
+        ids.iter()
+            .map(|id| format!("<div>${{{}}}</div>", id))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn template_needs_all_known_placeholders() {
+        let dir = temp_dir("template");
+        let template = dir.join(TEMPLATE_FILENAME);
+        fs::write(&template, placeholders(&TEMPLATE_PLACEHOLDERS)).unwrap();
+        assert_eq!(check_template_file(&template).status, CheckStatus::Pass);
+
+        fs::write(&template, placeholders(&TEMPLATE_PLACEHOLDERS[1..])).unwrap();
+        let check = check_template_file(&template);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check
+            .message
+            .contains(&format!("${{{}}}", TEMPLATE_PLACEHOLDERS[0])));
+
+        let mut ids = TEMPLATE_PLACEHOLDERS.to_vec();
+        ids.push("STATISTIC");
+        fs::write(&template, placeholders(&ids)).unwrap();
+        let check = check_template_file(&template);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.message.contains("unknown placeholders ${STATISTIC}"));
+
+        assert_eq!(
+            check_template_file(&dir.join("missing.html")).status,
+            CheckStatus::Fail
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// config with the data dir and a run every 2 hours
+    fn config(data_dir: &Path) -> Config {
+        Config {
+            data_dir: data_dir.to_string_lossy().into_owned(),
+            run_interval_minutes: 120,
+            ..Config::default()
+        }
+    }
+
+    /// data file with one entry written at the time
+    fn write_entry(data_dir: &Path, timestamp: NaiveDateTime) {
+        fs::write(
+            data_dir.join(get_data_file_name(&timestamp.date())),
+            format!(
+                "{{\"timestamp\":\"{}\",\"performance\":{{\"latency\":10,\"download\":1e8,\"upload\":1e7}}}}\n",
+                timestamp.format(DATE_TIME_FORMAT)
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn last_entry_must_be_newer_than_two_run_intervals() {
+        let dir = temp_dir("last-entry");
+        let config = config(&dir);
+        assert_eq!(check_data_dir(&config).status, CheckStatus::Pass);
+        assert_eq!(check_last_entry(&config).status, CheckStatus::Warn);
+
+        let now = Local::now().naive_local();
+        write_entry(&dir, now - Duration::days(3));
+        let check = check_last_entry(&config);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.message.contains("3 days ago"), "{}", check.message);
+
+        write_entry(&dir, now - Duration::minutes(30));
+        assert_eq!(check_last_entry(&config).status, CheckStatus::Pass);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(check_data_dir(&config).status, CheckStatus::Fail);
+    }
+}
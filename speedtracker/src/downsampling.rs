@@ -0,0 +1,129 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::cmp::Ordering;
+
+use crate::lib_constants::DOWNSAMPLING_MIN_POINTS;
+
+/// indices of the points to draw, at most max_points:
+/// Largest-Triangle-Three-Buckets keeps the shape of the series
+/// and the minimum and maximum are always kept, so dips and peaks stay visible.
+/// points are (x, y) sorted by x, every point is kept if there are not more than max_points.
+pub fn downsample(points: &[(f64, f64)], max_points: usize) -> Vec<usize> {
+    if points.len() <= max_points || max_points < DOWNSAMPLING_MIN_POINTS {
+        return (0..points.len()).collect();
+    }
+    let by_y = |&i: &usize, &j: &usize| compare(points[i].1, points[j].1);
+    let min = (0..points.len()).min_by(by_y);
+    let max = (0..points.len()).max_by(by_y);
+
+    // two places are reserved for the minimum and the maximum:
+    let mut selected = lttb(points, max_points - 2);
+    selected.extend(min.into_iter().chain(max));
+    selected.sort_unstable();
+    selected.dedup();
+    selected
+}
+
+/// Largest-Triangle-Three-Buckets (Sveinn Steinarsson, 2013):
+/// first and last point are kept, of every bucket in between the point
+/// with the largest triangle to the previous selected point and the average of the next bucket
+fn lttb(points: &[(f64, f64)], threshold: usize) -> Vec<usize> {
+    let n = points.len();
+    let bucket_size = (n - 2) as f64 / (threshold - 2) as f64;
+    let bucket_start = |i: usize| ((i as f64 * bucket_size) as usize + 1).min(n - 1);
+
+    let mut selected = Vec::with_capacity(threshold);
+    selected.push(0);
+    let mut a = 0;
+    for i in 0..threshold - 2 {
+        let start = bucket_start(i);
+        let end = bucket_start(i + 1).max(start + 1);
+        // the next bucket of the last bucket is the last point:
+        let next = &points[end.min(n - 1)..bucket_start(i + 2).max(end + 1).min(n)];
+        let (avg_x, avg_y) = next.iter().fold((0.0, 0.0), |(x, y), p| (x + p.0, y + p.1));
+        let (avg_x, avg_y) = (avg_x / next.len() as f64, avg_y / next.len() as f64);
+
+        let (ax, ay) = points[a];
+        let area = |p: &(f64, f64)| ((ax - avg_x) * (p.1 - ay) - (ax - p.0) * (avg_y - ay)).abs();
+        let chosen = (start..end)
+            .max_by(|&j, &k| compare(area(&points[j]), area(&points[k])))
+            .unwrap_or(start);
+        selected.push(chosen);
+        a = chosen;
+    }
+    selected.push(n - 1);
+    selected
+}
+
+fn compare(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// flat series with a single spike
+    fn spike(len: usize, at: usize) -> Vec<(f64, f64)> {
+        (0..len)
+            .map(|i| (i as f64, if i == at { 100.0 } else { 0.0 }))
+            .collect()
+    }
+
+    #[test]
+    fn short_series_are_kept() {
+        let points = spike(50, 10);
+        assert_eq!(downsample(&points, 50), (0..50).collect::<Vec<usize>>());
+        assert_eq!(downsample(&points, DOWNSAMPLING_MIN_POINTS - 1).len(), 50);
+    }
+
+    #[test]
+    fn lttb_keeps_first_last_and_one_point_per_bucket() {
+        let points = spike(1002, 537);
+        let selected = lttb(&points, 52);
+        assert_eq!(selected.len(), 52);
+        assert_eq!(selected.first(), Some(&0));
+        assert_eq!(selected.last(), Some(&1001));
+        // one point of every bucket of 20 points:
+        for (i, index) in selected[1..51].iter().enumerate() {
+            assert!((20 * i + 1..20 * i + 21).contains(index), "{} {}", i, index);
+        }
+        // the spike spans the largest triangle of its bucket:
+        assert!(selected.contains(&537));
+    }
+
+    #[test]
+    fn minimum_and_maximum_are_kept() {
+        let points: Vec<(f64, f64)> = (0..5000)
+            .map(|i| (i as f64, (i as f64 / 40.0).sin() * 100.0 + (i % 7) as f64))
+            .collect();
+        let selected = downsample(&points, 100);
+        assert!(selected.len() <= 100);
+        assert!(selected.windows(2).all(|w| w[0] < w[1]));
+        let by_y = |&i: &usize, &j: &usize| compare(points[i].1, points[j].1);
+        let min = (0..points.len()).min_by(by_y).unwrap();
+        let max = (0..points.len()).max_by(by_y).unwrap();
+        assert!(selected.contains(&min) && selected.contains(&max));
+    }
+}
@@ -0,0 +1,75 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::NaiveDate;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum SpeedTrackerError {
+    /// config file could not be loaded or contains unusable values
+    Config(String),
+    /// reading or writing a file failed, the string describes what was done
+    Io(String, io::Error),
+    /// a date or other user input could not be parsed
+    Parse(String),
+    /// template file could not be opened or read
+    Template(PathBuf, io::Error),
+    /// there is no data in the requested time range
+    NoData(NaiveDate, NaiveDate),
+}
+
+impl SpeedTrackerError {
+    /// wrap an io error and describe what was done e.g. "Could not open file x"
+    pub fn io<S: Into<String>>(context: S) -> impl FnOnce(io::Error) -> SpeedTrackerError {
+        let context = context.into();
+        move |e| SpeedTrackerError::Io(context, e)
+    }
+}
+
+impl fmt::Display for SpeedTrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpeedTrackerError::Config(msg) => write!(f, "Invalid config: {}", msg),
+            SpeedTrackerError::Io(context, e) => write!(f, "{}: {}", context, e),
+            SpeedTrackerError::Parse(msg) => write!(f, "{}", msg),
+            SpeedTrackerError::Template(path, e) => {
+                write!(f, "Could not read template file {:?}: {}", path, e)
+            }
+            SpeedTrackerError::NoData(from, to) => {
+                write!(f, "No data found from {} to {}", from, to)
+            }
+        }
+    }
+}
+
+impl Error for SpeedTrackerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SpeedTrackerError::Io(_, e) | SpeedTrackerError::Template(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
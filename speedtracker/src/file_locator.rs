@@ -0,0 +1,230 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::lib_constants::*;
+
+/// the files the program needs besides its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocatedFile {
+    Config,
+    Template,
+    CompareTemplate,
+    SpeedTestCmd,
+}
+
+impl LocatedFile {
+    fn file_name(&self) -> &'static str {
+        match self {
+            LocatedFile::Config => CONFIG_FILENAME,
+            LocatedFile::Template => TEMPLATE_FILENAME,
+            LocatedFile::CompareTemplate => COMPARE_TEMPLATE_FILENAME,
+            LocatedFile::SpeedTestCmd => SPEED_TEST_CMD,
+        }
+    }
+    fn env_var(&self) -> &'static str {
+        match self {
+            LocatedFile::Config => ENV_CONFIG_FILE,
+            LocatedFile::Template => ENV_TEMPLATE_FILE,
+            LocatedFile::CompareTemplate => ENV_COMPARE_TEMPLATE_FILE,
+            LocatedFile::SpeedTestCmd => ENV_SPEED_TEST_CMD,
+        }
+    }
+}
+
+impl fmt::Display for LocatedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file_name())
+    }
+}
+
+/// finds config, template and speed test command in this order:
+/// 1. explicit path (only config: '--config' flag)
+/// 2. environment variable e.g. 'SPEEDTRACKER_CONFIG'
+/// 3. '$XDG_CONFIG_HOME/speedtracker/' (default '~/.config/speedtracker/')
+/// 4. '/etc/speedtracker/'
+/// 5. the directory of the (symlink resolved) program
+#[derive(Debug, Clone)]
+pub struct FileLocator {
+    /// path given by '--config'
+    config_flag: Option<PathBuf>,
+    /// directory the program is stored in
+    program_dir: PathBuf,
+}
+
+impl FileLocator {
+    pub fn new(config_flag: Option<PathBuf>) -> FileLocator {
+        FileLocator {
+            config_flag,
+            program_dir: program_dir(),
+        }
+    }
+
+    /// directory the program is stored in
+    pub fn program_dir(&self) -> &Path {
+        &self.program_dir
+    }
+
+    /// all paths that are searched in the order they are searched.
+    /// explicit paths (flag, environment variable) stop the search
+    pub fn candidates(&self, file: LocatedFile) -> Vec<PathBuf> {
+        self.candidates_in(file, &|name| env::var_os(name))
+    }
+
+    /// candidates with the environment variables looked up by var
+    fn candidates_in(
+        &self,
+        file: LocatedFile,
+        var: &dyn Fn(&str) -> Option<OsString>,
+    ) -> Vec<PathBuf> {
+        if file == LocatedFile::Config {
+            if let Some(path) = &self.config_flag {
+                return vec![path.clone()];
+            }
+        }
+        if let Some(path) = var(file.env_var()).filter(|p| !p.is_empty()) {
+            return vec![PathBuf::from(path)];
+        }
+        let mut rs: Vec<PathBuf> = Vec::new();
+        if let Some(dir) = xdg_config_home(var) {
+            rs.push(dir.join(PROGRAM_DIR_NAME).join(file.file_name()));
+        }
+        rs.push(Path::new(SYSTEM_CONFIG_DIR).join(file.file_name()));
+        rs.push(self.program_dir.join(file.file_name()));
+        rs
+    }
+
+    /// first existing candidate or all searched paths if none exists
+    pub fn locate(&self, file: LocatedFile) -> Result<PathBuf, Vec<PathBuf>> {
+        first_existing(self.candidates(file))
+    }
+}
+
+fn first_existing(candidates: Vec<PathBuf>) -> Result<PathBuf, Vec<PathBuf>> {
+    match candidates.iter().find(|p| p.is_file()) {
+        Some(path) => Ok(path.clone()),
+        None => Err(candidates),
+    }
+}
+
+/// human readable list of searched paths
+pub fn format_searched(searched: &[PathBuf]) -> String {
+    let paths: Vec<String> = searched.iter().map(|p| format!("{:?}", p)).collect();
+    format!("searched {}", paths.join(", "))
+}
+
+fn xdg_config_home(var: &dyn Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    match var("XDG_CONFIG_HOME").filter(|p| !p.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => var("HOME")
+            .filter(|p| !p.is_empty())
+            .map(|home| Path::new(&home).join(".config")),
+    }
+}
+
+/// directory of the running program with symlinks resolved,
+/// falls back to the directory of the first argument
+fn program_dir() -> PathBuf {
+    let exe = env::current_exe()
+        .and_then(|p| p.canonicalize())
+        .unwrap_or_else(|_| PathBuf::from(env::args().next().unwrap_or_default()));
+    match exe.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fs;
+
+    /// environment variables with the given values only
+    fn vars(vars: &[(&str, &Path)]) -> impl Fn(&str) -> Option<OsString> {
+        let vars: HashMap<String, OsString> = vars
+            .iter()
+            .map(|(name, value)| (String::from(*name), value.as_os_str().to_os_string()))
+            .collect();
+        move |name: &str| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn files_are_searched_in_order() {
+        let dir = env::temp_dir().join(format!("speedtracker-locator-{}", std::process::id()));
+        let (xdg, program) = (dir.join("xdg"), dir.join("program"));
+        fs::create_dir_all(xdg.join(PROGRAM_DIR_NAME)).unwrap();
+        fs::create_dir_all(&program).unwrap();
+        let flag = dir.join("flag.toml");
+        let from_env = dir.join("env.toml");
+        let locator = |config_flag: Option<&Path>| FileLocator {
+            config_flag: config_flag.map(Path::to_path_buf),
+            program_dir: program.clone(),
+        };
+        let all = vars(&[
+            (ENV_CONFIG_FILE, &from_env),
+            ("XDG_CONFIG_HOME", &xdg),
+            ("HOME", &dir),
+        ]);
+
+        // the flag wins over the environment variable, the variable over the directories:
+        assert_eq!(
+            locator(Some(&flag)).candidates_in(LocatedFile::Config, &all),
+            vec![flag.clone()]
+        );
+        assert_eq!(
+            locator(None).candidates_in(LocatedFile::Config, &all),
+            vec![from_env]
+        );
+        // the flag is only for the config:
+        let searched = locator(Some(&flag)).candidates_in(LocatedFile::Template, &all);
+        let expected = vec![
+            xdg.join(PROGRAM_DIR_NAME).join(TEMPLATE_FILENAME),
+            Path::new(SYSTEM_CONFIG_DIR).join(TEMPLATE_FILENAME),
+            program.join(TEMPLATE_FILENAME),
+        ];
+        assert_eq!(searched, expected);
+        // without XDG_CONFIG_HOME in ~/.config:
+        assert_eq!(
+            locator(None).candidates_in(LocatedFile::Template, &vars(&[("HOME", &dir)]))[0],
+            dir.join(".config")
+                .join(PROGRAM_DIR_NAME)
+                .join(TEMPLATE_FILENAME)
+        );
+
+        // the first existing file is found (unless installed in /etc):
+        fs::write(&expected[2], "").unwrap();
+        if !expected[1].exists() {
+            assert_eq!(first_existing(searched.clone()), Ok(expected[2].clone()));
+            fs::remove_file(&expected[2]).unwrap();
+            assert_eq!(first_existing(searched.clone()), Err(expected.clone()));
+        }
+        fs::write(&expected[0], "").unwrap();
+        assert_eq!(first_existing(searched), Ok(expected[0].clone()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+
+use crate::lib_constants::*;
+
+/// forecast of one hour with its confidence band
+#[derive(Debug)]
+pub struct ForecastPoint {
+    pub timestamp: NaiveDateTime,
+    pub value: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// state of the additive Holt-Winters model after the last hour
+struct Fit {
+    level: f64,
+    trend: f64,
+    season: Vec<f64>,
+    /// root mean square of the one step errors
+    sigma: f64,
+    alpha: f64,
+    beta: f64,
+    gamma: f64,
+}
+
+/// Holt-Winters (triple exponential smoothing) with daily seasonality, fitted on hourly means of
+/// the samples (in any order), hours without samples are interpolated.
+/// the smoothing parameters with the smallest one step errors are searched on a grid.
+/// returns the next days hour by hour, none if the samples do not cover two days
+pub fn forecast(samples: &[(NaiveDateTime, f64)], days: u32) -> Option<Vec<ForecastPoint>> {
+    let season_length = FORECAST_SEASON_HOURS;
+    if samples.len() < FORECAST_MIN_SAMPLES {
+        return None;
+    }
+    let first_hour = truncate_to_hour(samples.iter().map(|s| s.0).min()?)?;
+    let last_hour = truncate_to_hour(samples.iter().map(|s| s.0).max()?)?;
+    let hours = (last_hour - first_hour).num_hours() as usize + 1;
+    if hours < 2 * season_length {
+        return None;
+    }
+    let series = hourly_series(samples, first_hour, hours);
+
+    let mut best: Option<Fit> = None;
+    for &alpha in FORECAST_ALPHAS.iter() {
+        for &beta in FORECAST_BETAS.iter() {
+            for &gamma in FORECAST_GAMMAS.iter() {
+                let fit = fit(&series, season_length, alpha, beta, gamma);
+                let better = match &best {
+                    Some(b) => fit.sigma < b.sigma,
+                    None => true,
+                };
+                if better {
+                    best = Some(fit);
+                }
+            }
+        }
+    }
+    let fit = best?;
+
+    // variance of the h step error grows with the weights of the past errors:
+    let mut error_weights = 0.0;
+    let points = (1..=days as usize * season_length)
+        .map(|h| {
+            let value =
+                fit.level + h as f64 * fit.trend + fit.season[(hours - 1 + h) % season_length];
+            let width = FORECAST_CONFIDENCE_Z * fit.sigma * f64::sqrt(1.0 + error_weights);
+            let seasonal = if h % season_length == 0 {
+                fit.gamma
+            } else {
+                0.0
+            };
+            error_weights += (fit.alpha * (1.0 + h as f64 * fit.beta) + seasonal).powi(2);
+            ForecastPoint {
+                timestamp: last_hour + Duration::hours(h as i64),
+                value: value.max(0.0),
+                lower: (value - width).max(0.0),
+                upper: (value + width).max(0.0),
+            }
+        })
+        .collect();
+    Some(points)
+}
+
+fn truncate_to_hour(timestamp: NaiveDateTime) -> Option<NaiveDateTime> {
+    timestamp.date().and_hms_opt(timestamp.hour(), 0, 0)
+}
+
+/// mean of every hour from first_hour on, hours without samples are interpolated linearly,
+/// samples outside of the hours are skipped
+fn hourly_series(
+    samples: &[(NaiveDateTime, f64)],
+    first_hour: NaiveDateTime,
+    hours: usize,
+) -> Vec<f64> {
+    let mut sums = vec![(0.0, 0); hours];
+    for (t, v) in samples {
+        let i = (*t - first_hour).num_hours();
+        if *t < first_hour || i >= hours as i64 {
+            continue;
+        }
+        let i = i as usize;
+        sums[i] = (sums[i].0 + v, sums[i].1 + 1);
+    }
+    let means: Vec<Option<f64>> = sums
+        .iter()
+        .map(|(sum, n)| if *n > 0 { Some(sum / *n as f64) } else { None })
+        .collect();
+    let known: Vec<usize> = (0..hours).filter(|&i| means[i].is_some()).collect();
+    // first and last hour have samples:
+    let mut series = vec![0.0; hours];
+    for w in known.windows(2) {
+        let (start, end) = (w[0], w[1]);
+        let (a, b) = (means[start].unwrap_or(0.0), means[end].unwrap_or(0.0));
+        for (i, value) in series.iter_mut().enumerate().take(end).skip(start) {
+            *value = a + (b - a) * (i - start) as f64 / (end - start) as f64;
+        }
+    }
+    if let Some(&last) = known.last() {
+        series[last] = means[last].unwrap_or(0.0);
+    }
+    series
+}
+
+/// additive Holt-Winters, initialized with the first two seasons
+fn fit(series: &[f64], season_length: usize, alpha: f64, beta: f64, gamma: f64) -> Fit {
+    let mean = |s: &[f64]| s.iter().sum::<f64>() / s.len() as f64;
+    let first = mean(&series[..season_length]);
+    let second = mean(&series[season_length..2 * season_length]);
+    let mut level = first;
+    let mut trend = (second - first) / season_length as f64;
+    let mut season: Vec<f64> = (0..season_length)
+        .map(|i| ((series[i] - first) + (series[i + season_length] - second)) / 2.0)
+        .collect();
+
+    let mut squared_errors = 0.0;
+    for (t, x) in series.iter().enumerate() {
+        let s = season[t % season_length];
+        let error = x - (level + trend + s);
+        squared_errors += error * error;
+        let new_level = alpha * (x - s) + (1.0 - alpha) * (level + trend);
+        trend = beta * (new_level - level) + (1.0 - beta) * trend;
+        season[t % season_length] = gamma * (x - new_level) + (1.0 - gamma) * s;
+        level = new_level;
+    }
+    Fit {
+        level,
+        trend,
+        season,
+        sigma: f64::sqrt(squared_errors / series.len() as f64),
+        alpha,
+        beta,
+        gamma,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn hourly_samples(days: i64) -> Vec<(NaiveDateTime, f64)> {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 1)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .unwrap();
+        (0..days * 24)
+            .map(|h| {
+                let value = if h % 24 < 12 { 100.0 } else { 200.0 };
+                (start + Duration::hours(h), value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn forecast_of_unsorted_samples_equals_forecast_of_sorted_samples() {
+        let sorted = hourly_samples(4);
+        let mut unsorted = sorted.clone();
+        unsorted.reverse();
+        unsorted.swap(3, 40);
+        let a = forecast(&sorted, 1).unwrap();
+        let b = forecast(&unsorted, 1).unwrap();
+        assert_eq!(a.len(), 24);
+        assert_eq!(a.len(), b.len());
+        for (p, q) in a.iter().zip(b.iter()) {
+            assert_eq!(p.timestamp, q.timestamp);
+            assert!((p.value - q.value).abs() < 1e-9);
+        }
+        assert_eq!(
+            a[0].timestamp,
+            sorted.last().unwrap().0 + Duration::hours(1)
+        );
+    }
+
+    #[test]
+    fn hourly_series_skips_samples_outside_of_the_hours() {
+        let samples = hourly_samples(1);
+        let series = hourly_series(&samples, samples[2].0, 3);
+        assert_eq!(series, vec![100.0, 100.0, 100.0]);
+    }
+}
@@ -21,9 +21,11 @@
 // SOFTWARE.
 //
 
-use log::error;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
 use regex::Regex;
 use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
 use std::f64;
 use std::fmt::Display;
 use std::fs;
@@ -31,80 +33,321 @@ use std::fs::File;
 use std::io::{prelude::*, BufReader, Write};
 use std::path::Path;
 
+use crate::aggregation::{Aggregation, Bucket, MetricAggregation};
+use crate::anomalies::{detect_anomalies, Anomaly};
+use crate::campaign::CampaignReport;
+use crate::changes::{detect_changes, Change};
 use crate::chart_config::*;
+use crate::compare::{Comparison, Period};
+use crate::compliance::{compliance, Compliance, Sample, TierCompliance};
+use crate::downsampling::downsample;
+use crate::error::SpeedTrackerError;
+use crate::forecast::{forecast, ForecastPoint};
 use crate::json_parser::*;
 use crate::lib_constants::*;
+use crate::outages::{format_duration, OutageReport};
+use crate::statistics::{Statistic, Summary};
+use crate::trends::{ewma, rolling_median};
 
 pub struct HtmlGenerator {}
 
+/// results of the analysis of the data shown on the page
+pub struct Reports {
+    pub outages: OutageReport,
+    /// only if a campaign is configured
+    pub campaign: Option<CampaignReport>,
+    pub aggregation: Aggregation,
+}
+
+/// x is a formatted timestamp or the day of a compared time range
 #[derive(Serialize, Debug)]
-struct Point<N> {
-    x: String,
-    y: N,
+struct Point<N, X = String> {
+    x: X,
+    /// null breaks the line
+    y: Option<N>,
 }
 
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
-struct Dataset<N> {
+struct Dataset<N, X = String> {
     label: String,
-    data: Vec<Point<N>>,
+    data: Vec<Point<N, X>>,
     fill: bool,
     border_color: String,
+    /// only set for markers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    show_line: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    point_style: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    point_radius: Option<u32>,
+    /// the area between this and the next dataset is shaded (min/max of rollups)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    band_color: Option<String>,
+    /// every point is drawn as vertical line (changes)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vertical_lines: Option<bool>,
+    /// dashed line (forecast), lengths of dash and gap
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border_dash: Option<Vec<u32>>,
+}
+
+/// value drawn in a chart, rollups calculate medians as f64
+trait ChartValue: Copy + Into<f64> {
+    fn from_f64(value: f64) -> Self;
+}
+
+impl ChartValue for u32 {
+    fn from_f64(value: f64) -> Self {
+        value.round() as u32
+    }
+}
+
+impl ChartValue for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
 }
 
 struct Chart<N> {
     datasets: Vec<Dataset<N>>,
-    median: f64,
-    average: f64,
-    standard_deviation: f64,
+    /// statistics of the measured values in the unit of the chart
+    summary: Summary,
+    compliance: Compliance,
+    findings: Findings,
+}
+
+/// what the analysis of the measured values of a chart found
+struct Findings {
+    /// shifts of the level
+    changes: Vec<Change>,
+    /// single measurements far from their hour of the week
+    anomalies: Vec<Anomaly>,
 }
 
 impl HtmlGenerator {
     pub fn write_html(
         data: &[ParsedEntry],
+        baseline: &[ParsedEntry],
+        reports: &Reports,
         template_file: &Path,
         output_file: &Path,
-        config_latency_chart: &ChartConfig<u32>,
-        config_jitter_chart: &ChartConfig<u32>,
-        config_download_chart: &ChartConfig<f64>,
-        config_upload_chart: &ChartConfig<f64>,
-    ) {
+        charts: &ChartConfigs,
+    ) -> Result<(), SpeedTrackerError> {
         //create chart data;
-        let lat_chart = create_latency_chart(data, config_latency_chart);
-        let jit_chart = create_jitter_chart(data, config_jitter_chart);
-        let dwn_chart = create_download_chart(data, config_download_chart);
-        let upl_chart = create_upload_chart(data, config_upload_chart);
+        let lat_chart = create_latency_chart(data, baseline, &charts.latency, charts);
+        let jit_chart = create_jitter_chart(data, baseline, &charts.jitter, charts);
+        let dwn_chart = create_download_chart(data, baseline, &charts.download, charts);
+        let upl_chart = create_upload_chart(data, baseline, &charts.upload, charts);
+
+        let stat_lat = create_statistic_table(ID_LATENCY, &charts.latency, &lat_chart);
+        let stat_jit = create_statistic_table(ID_JITTER, &charts.jitter, &jit_chart);
+        let stat_dwn = create_statistic_table(ID_DOWNLOAD, &charts.download, &dwn_chart);
+        let stat_upl = create_statistic_table(ID_UPLOAD, &charts.upload, &upl_chart);
 
-        let stat_lat = create_statistic_table(ID_LATENCY, config_latency_chart, &lat_chart);
-        let stat_jit = create_statistic_table(ID_JITTER, config_jitter_chart, &jit_chart);
-        let stat_dwn = create_statistic_table(ID_DOWNLOAD, config_download_chart, &dwn_chart);
-        let stat_upl = create_statistic_table(ID_UPLOAD, config_upload_chart, &upl_chart);
+        let mut statistics_table = create_statistics_table(stat_lat, stat_jit, stat_dwn, stat_upl);
+        if data.is_empty() {
+            statistics_table =
+                format!("<p class=\"notice\">{}</p>", NO_DATA_NOTICE) + &statistics_table;
+        }
 
-        let statistics_table = create_statistics_table(stat_lat, stat_jit, stat_dwn, stat_upl);
+        //failed runs are shown as markers on the x axis:
+        let failed_lat = create_dataset_failed_runs(data);
+        let failed_dwn = create_dataset_failed_runs(data);
 
         //transform chart data to json
         let response_time_dss: Vec<&Dataset<u32>> = lat_chart
             .datasets
             .iter()
             .chain(jit_chart.datasets.iter())
+            .chain(failed_lat.iter())
             .collect();
         let throughput_dss: Vec<&Dataset<f64>> = dwn_chart
             .datasets
             .iter()
             .chain(upl_chart.datasets.iter())
+            .chain(failed_dwn.iter())
             .collect();
 
         let response_time_json = serde_json::to_string(&response_time_dss).unwrap();
         let throughput_json = serde_json::to_string(&throughput_dss).unwrap();
 
-        write_output_file(
-            template_file,
-            output_file,
-            data,
-            &statistics_table,
-            &response_time_json,
-            &throughput_json,
-        );
+        //content of every placeholder except raw data:
+        let sections: HashMap<&str, String> = HashMap::from([
+            (REPLACEMENT_ID_STATISTICS, statistics_table),
+            (REPLACEMENT_ID_RESPONSE_TIMES, response_time_json),
+            (REPLACEMENT_ID_THROUGHPUT, throughput_json),
+            (
+                REPLACEMENT_ID_OUTAGES,
+                create_outages_table(&reports.outages),
+            ),
+            (
+                REPLACEMENT_ID_COMPLIANCE,
+                create_compliance_table(&[
+                    (&charts.latency.label, &lat_chart.compliance),
+                    (&charts.jitter.label, &jit_chart.compliance),
+                    (&charts.download.label, &dwn_chart.compliance),
+                    (&charts.upload.label, &upl_chart.compliance),
+                ]),
+            ),
+            (REPLACEMENT_ID_AVAILABILITY, create_availability_text(data)),
+            (
+                REPLACEMENT_ID_CAMPAIGN,
+                create_campaign_section(reports.campaign.as_ref()),
+            ),
+            (
+                REPLACEMENT_ID_HEATMAP,
+                create_heatmap_section(&reports.aggregation),
+            ),
+            (
+                REPLACEMENT_ID_CHANGES,
+                create_changes_table(&[
+                    &lat_chart.findings.changes,
+                    &jit_chart.findings.changes,
+                    &dwn_chart.findings.changes,
+                    &upl_chart.findings.changes,
+                ]),
+            ),
+        ]);
+
+        write_output_file(template_file, output_file, data, &sections)
+    }
+
+    /// statistics of all charts as plain text, one line per chart
+    pub fn statistics_text(data: &[ParsedEntry], charts: &ChartConfigs) -> String {
+        // anomalies are not shown, so no baseline is needed:
+        let baseline = &[];
+        let lat_chart = create_latency_chart(data, baseline, &charts.latency, charts);
+        let jit_chart = create_jitter_chart(data, baseline, &charts.jitter, charts);
+        let dwn_chart = create_download_chart(data, baseline, &charts.download, charts);
+        let upl_chart = create_upload_chart(data, baseline, &charts.upload, charts);
+        [
+            create_statistic_line(&charts.latency, &lat_chart),
+            create_statistic_line(&charts.jitter, &jit_chart),
+            create_statistic_line(&charts.download, &dwn_chart),
+            create_statistic_line(&charts.upload, &upl_chart),
+        ]
+        .concat()
+    }
+
+    /// comparison of two time ranges: both overlaid on the days of the time ranges
+    /// and the table of the differences
+    pub fn write_comparison(
+        first_data: &[ParsedEntry],
+        second_data: &[ParsedEntry],
+        comparison: &Comparison,
+        template_file: &Path,
+        output_file: &Path,
+        charts: &ChartConfigs,
+    ) -> Result<(), SpeedTrackerError> {
+        let periods = [
+            (comparison.first, first_data, false),
+            (comparison.second, second_data, true),
+        ];
+        let mut response_time_dss: Vec<Dataset<u32, f64>> = Vec::new();
+        let mut throughput_dss: Vec<Dataset<f64, f64>> = Vec::new();
+        for (period, data, dashed) in periods {
+            response_time_dss.push(create_dataset_period(
+                &charts.latency,
+                period,
+                data,
+                dashed,
+                charts.max_points,
+                |p| p.latency,
+            ));
+            response_time_dss.push(create_dataset_period(
+                &charts.jitter,
+                period,
+                data,
+                dashed,
+                charts.max_points,
+                |p| p.jitter,
+            ));
+            throughput_dss.push(create_dataset_period(
+                &charts.download,
+                period,
+                data,
+                dashed,
+                charts.max_points,
+                |p| p.download.map(|v| v / MEGA_BIT_FACTOR),
+            ));
+            throughput_dss.push(create_dataset_period(
+                &charts.upload,
+                period,
+                data,
+                dashed,
+                charts.max_points,
+                |p| p.upload.map(|v| v / MEGA_BIT_FACTOR),
+            ));
+        }
+        let sections: HashMap<&str, String> = HashMap::from([
+            (
+                REPLACEMENT_ID_COMPARISON,
+                create_comparison_table(comparison),
+            ),
+            (
+                REPLACEMENT_ID_RESPONSE_TIMES,
+                serde_json::to_string(&response_time_dss).unwrap(),
+            ),
+            (
+                REPLACEMENT_ID_THROUGHPUT,
+                serde_json::to_string(&throughput_dss).unwrap(),
+            ),
+        ]);
+        write_output_file(template_file, output_file, &[], &sections)
+    }
+
+    /// anomalous measurements of all charts sorted by time,
+    /// the usual values of the hours are taken from the baseline entries
+    pub fn anomalies(
+        data: &[ParsedEntry],
+        baseline: &[ParsedEntry],
+        charts: &ChartConfigs,
+    ) -> Vec<Anomaly> {
+        let mut anomalies: Vec<Anomaly> = [
+            create_latency_chart(data, baseline, &charts.latency, charts)
+                .findings
+                .anomalies,
+            create_jitter_chart(data, baseline, &charts.jitter, charts)
+                .findings
+                .anomalies,
+            create_download_chart(data, baseline, &charts.download, charts)
+                .findings
+                .anomalies,
+            create_upload_chart(data, baseline, &charts.upload, charts)
+                .findings
+                .anomalies,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        anomalies.sort_by_key(|a| a.timestamp);
+        anomalies
+    }
+
+    /// shifts of the level of all charts sorted by time
+    pub fn changes(data: &[ParsedEntry], charts: &ChartConfigs) -> Vec<Change> {
+        // anomalies are not shown, so no baseline is needed:
+        let baseline = &[];
+        let mut changes: Vec<Change> = [
+            create_latency_chart(data, baseline, &charts.latency, charts)
+                .findings
+                .changes,
+            create_jitter_chart(data, baseline, &charts.jitter, charts)
+                .findings
+                .changes,
+            create_download_chart(data, baseline, &charts.download, charts)
+                .findings
+                .changes,
+            create_upload_chart(data, baseline, &charts.upload, charts)
+                .findings
+                .changes,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        changes.sort_by_key(|c| c.first_after);
+        changes
     }
 }
 
@@ -112,161 +355,94 @@ fn write_output_file(
     template_file: &Path,
     output_file: &Path,
     data: &[ParsedEntry],
-    statistics_table: &str,
-    response_time_json: &str,
-    throughput_json: &str,
-) {
-    if data.is_empty() {
-        let msg = "No data found!";
-        log_and_fail(msg);
-    }
+    sections: &HashMap<&str, String>,
+) -> Result<(), SpeedTrackerError> {
+    // open template first, to not destroy an existing output file:
+    let template_reader = fs::File::open(template_file)
+        .map(BufReader::new)
+        .map_err(|e| SpeedTrackerError::Template(template_file.to_path_buf(), e))?;
 
     // write files:
-    let mut out_file: File = match fs::OpenOptions::new()
+    let write_failed = || SpeedTrackerError::io(format!("Could not write file {:?}", output_file));
+    let mut out_file: File = fs::OpenOptions::new()
         .append(false)
         .create(true)
         .write(true)
         .truncate(true) //overwrite
         .open(output_file)
-    {
-        Err(e) => {
-            let msg = format!("Could not create file message= '{}'", e);
-            log_and_fail(&msg);
-        }
-        Ok(f) => f,
-    };
+        .map_err(SpeedTrackerError::io(format!(
+            "Could not create file {:?}",
+            output_file
+        )))?;
 
     let find_replacement = Regex::new(REPLACEMENT_REGEX).unwrap();
-    match fs::File::open(template_file) {
-        Err(e) => {
-            let msg = format!("Could not open template file message= '{}'", e);
-            log_and_fail(&msg);
-        }
-        Ok(f) => {
-            let template_reader = BufReader::new(f);
-            for maybe_line in template_reader.lines() {
-                match maybe_line {
-                    Err(e) => {
-                        let msg = format!("Could not read template file message= '{}'", e);
-                        log_and_fail(&msg);
+    for maybe_line in template_reader.lines() {
+        let line =
+            maybe_line.map_err(|e| SpeedTrackerError::Template(template_file.to_path_buf(), e))?;
+        match find_replacement.find(&line) {
+            Some(mat) => {
+                //matched string without '${' and '}'
+                let matched_string = &line[mat.start() + 2..mat.end() - 1];
+                let prefix = &line[0..mat.start()];
+                let suffix = &line[mat.end()..line.len()];
+                match (matched_string, sections.get(matched_string)) {
+                    (REPLACEMENT_ID_RAW_DATA, _) => {
+                        write_raw_data(data, &mut out_file, prefix, suffix)
                     }
-                    Ok(line) => {
-                        match find_replacement.find(&line) {
-                            Some(mat) => {
-                                //matched string without '${' and '}'
-                                let matched_string = &line[mat.start() + 2..mat.end() - 1];
-                                let prefix = &line[0..mat.start()];
-                                let suffix = &line[mat.end()..line.len()];
-                                match matched_string {
-                                    REPLACEMENT_ID_RAW_DATA => {
-                                        write_raw_data(data, &mut out_file, prefix, suffix);
-                                    }
-                                    REPLACEMENT_ID_STATISTICS => {
-                                        handle_failed_write(writeln!(
-                                            &mut out_file,
-                                            "{}{}{}",
-                                            prefix, statistics_table, suffix
-                                        ));
-                                    }
-                                    REPLACEMENT_ID_RESPONSE_TIMES => {
-                                        handle_failed_write(writeln!(
-                                            &mut out_file,
-                                            "{}{}{}",
-                                            prefix, response_time_json, suffix
-                                        ));
-                                    }
-                                    REPLACEMENT_ID_THROUGHPUT => {
-                                        handle_failed_write(writeln!(
-                                            &mut out_file,
-                                            "{}{}{}",
-                                            prefix, throughput_json, suffix
-                                        ));
-                                    }
-                                    _ => {
-                                        //ignore:
-                                        handle_failed_write(writeln!(&mut out_file, "{}", &line));
-                                    }
-                                };
-                            }
-                            None => {
-                                handle_failed_write(writeln!(&mut out_file, "{}", &line));
-                            }
-                        };
+                    (_, Some(section)) => {
+                        writeln!(&mut out_file, "{}{}{}", prefix, section, suffix)
+                    }
+                    (_, None) => {
+                        //ignore:
+                        writeln!(&mut out_file, "{}", &line)
                     }
                 }
             }
+            None => writeln!(&mut out_file, "{}", &line),
         }
+        .map_err(write_failed())?;
     }
-    match out_file.flush() {
-        Err(e) => {
-            let msg = format!("Could not write file message= '{}'", e);
-            log_and_fail(&msg);
-        }
-        Ok(()) => (),
-    };
-}
-
-fn log_and_fail(msg: &str) -> ! {
-    //return never type!
-    error!("{}", msg);
-    panic!("{}", msg);
-}
-
-fn handle_failed_write(rs: Result<(), std::io::Error>) {
-    if let Err(e) = rs {
-        let msg = format!("Write failed message= '{}'", e);
-        error!("{}", msg);
-        panic!("{}", msg);
-    }
+    out_file.flush().map_err(write_failed())
 }
 
 /// prepare data to show latency:
-fn create_latency_chart(data: &[ParsedEntry], config: &ChartConfig<u32>) -> Chart<u32> {
-    let points: Vec<Point<u32>> = data
-        .iter()
-        .map(|d| {
-            let x: String = d.timestamp.format(DATE_TIME_FORMAT).to_string();
-            let y = *d
-                .performance
-                .as_ref()
-                .map(|p| &p.latency)
-                .unwrap_or(&config.default_value);
-            Point { x, y }
-        })
-        .collect();
-
-    let dss = create_datasets(config, points);
+fn create_latency_chart(
+    data: &[ParsedEntry],
+    baseline: &[ParsedEntry],
+    config: &ChartConfig<u32>,
+    charts: &ChartConfigs,
+) -> Chart<u32> {
+    let (dss, findings) =
+        create_datasets(config, data, baseline, config.default_value, charts, |p| {
+            p.latency
+        });
 
-    let mut values: Vec<f64> = data
+    let values: Vec<f64> = data
         .iter()
         .flat_map(|d| {
-            let y = d.performance.as_ref().map(|p| p.latency);
+            let y = d.performance.as_ref().and_then(|p| p.latency);
             y
         })
         .map(|x| x as f64)
         .collect();
 
-    create_chart(dss, &mut values, MULT_DIV_NEUTRAL)
+    let compliance = create_compliance(data, config, |p| p.latency.map(f64::from), false);
+    create_chart(dss, values, MULT_DIV_NEUTRAL, compliance, findings)
 }
 
 /// prepare data to show jitter:
-fn create_jitter_chart(data: &[ParsedEntry], config: &ChartConfig<u32>) -> Chart<u32> {
-    let points: Vec<Point<u32>> = data
-        .iter()
-        .map(|d| {
-            let x: String = d.timestamp.format(DATE_TIME_FORMAT).to_string();
-            let y: u32 = d
-                .performance
-                .as_ref()
-                .map(|p| p.jitter.unwrap_or(config.default_value))
-                .unwrap();
-            Point { x, y }
-        })
-        .collect();
-
-    let dss = create_datasets(config, points);
+fn create_jitter_chart(
+    data: &[ParsedEntry],
+    baseline: &[ParsedEntry],
+    config: &ChartConfig<u32>,
+    charts: &ChartConfigs,
+) -> Chart<u32> {
+    let (dss, findings) =
+        create_datasets(config, data, baseline, config.default_value, charts, |p| {
+            p.jitter
+        });
 
-    let mut values: Vec<f64> = data
+    let values: Vec<f64> = data
         .iter()
         .flat_map(|d| {
             let y = d.performance.as_ref().map(|p| p.jitter);
@@ -276,79 +452,445 @@ fn create_jitter_chart(data: &[ParsedEntry], config: &ChartConfig<u32>) -> Chart
         .map(|x| x as f64)
         .collect();
 
-    create_chart(dss, &mut values, MULT_DIV_NEUTRAL)
+    let compliance = create_compliance(data, config, |p| p.jitter.map(f64::from), false);
+    create_chart(dss, values, MULT_DIV_NEUTRAL, compliance, findings)
 }
 
 /// prepare data to download speed:
-fn create_download_chart(data: &[ParsedEntry], config: &ChartConfig<f64>) -> Chart<f64> {
-    let points: Vec<Point<f64>> = data
+fn create_download_chart(
+    data: &[ParsedEntry],
+    baseline: &[ParsedEntry],
+    config: &ChartConfig<f64>,
+    charts: &ChartConfigs,
+) -> Chart<f64> {
+    let default_value = config.default_value / MEGA_BIT_FACTOR;
+    let (dss, findings) = create_datasets(config, data, baseline, default_value, charts, |p| {
+        p.download.map(|v| v / MEGA_BIT_FACTOR)
+    });
+
+    let values: Vec<f64> = data
         .iter()
-        .map(|d| {
-            let x: String = d.timestamp.format(DATE_TIME_FORMAT).to_string();
-            let y: f64 = d
-                .performance
-                .as_ref()
-                .map(|p| p.download.unwrap_or(config.default_value))
-                .unwrap()
-                / MEGA_BIT_FACTOR;
-            Point { x, y }
+        .flat_map(|d| {
+            let y = d.performance.as_ref().map(|p| p.download);
+            y
         })
+        .flatten()
         .collect();
 
-    let dss = create_datasets(config, points);
+    let compliance = create_compliance(
+        data,
+        config,
+        |p| p.download.map(|v| v / MEGA_BIT_FACTOR),
+        true,
+    );
+    create_chart(dss, values, MEGA_BIT_FACTOR, compliance, findings)
+}
+
+/// prepare data to upload speed:
+fn create_upload_chart(
+    data: &[ParsedEntry],
+    baseline: &[ParsedEntry],
+    config: &ChartConfig<f64>,
+    charts: &ChartConfigs,
+) -> Chart<f64> {
+    let default_value = config.default_value / MEGA_BIT_FACTOR;
+    let (dss, findings) = create_datasets(config, data, baseline, default_value, charts, |p| {
+        p.upload.map(|v| v / MEGA_BIT_FACTOR)
+    });
 
-    let mut values: Vec<f64> = data
+    let values: Vec<f64> = data
         .iter()
         .flat_map(|d| {
-            let y = d.performance.as_ref().map(|p| p.download);
+            let y = d.performance.as_ref().map(|p| p.upload);
             y
         })
         .flatten()
         .collect();
 
-    create_chart(dss, &mut values, MEGA_BIT_FACTOR)
+    let compliance = create_compliance(
+        data,
+        config,
+        |p| p.upload.map(|v| v / MEGA_BIT_FACTOR),
+        true,
+    );
+    create_chart(dss, values, MEGA_BIT_FACTOR, compliance, findings)
 }
 
-/// prepare data to upload speed:
-fn create_upload_chart(data: &[ParsedEntry], config: &ChartConfig<f64>) -> Chart<f64> {
-    let points: Vec<Point<f64>> = data
+// helper methods:
+
+/// compliance with the expected value, every run counts (failed runs as not available)
+fn create_compliance<T: Copy + Into<f64>>(
+    data: &[ParsedEntry],
+    config: &ChartConfig<T>,
+    value: impl Fn(&Performance) -> Option<f64>,
+    higher_is_better: bool,
+) -> Compliance {
+    let expected = config.expected_value.as_ref();
+    let samples: Vec<Sample> = data
         .iter()
-        .map(|d| {
-            let x: String = d.timestamp.format(DATE_TIME_FORMAT).to_string();
-            let y: f64 = d
-                .performance
-                .as_ref()
-                .map(|p| p.upload.unwrap_or(config.default_value))
-                .unwrap()
-                / MEGA_BIT_FACTOR;
-            Point { x, y }
+        .map(|d| Sample {
+            date: d.timestamp.date(),
+            value: d.performance.as_ref().and_then(&value),
+            expected: expected
+                .and_then(|e| e.reference_value_at(d.timestamp.date()))
+                .map(Into::into),
+            tiers: expected
+                .map(|e| {
+                    e.tiers
+                        .iter()
+                        .map(|t| t.value_at(d.timestamp.date()).into())
+                        .collect()
+                })
+                .unwrap_or_default(),
         })
         .collect();
+    let tiers: Vec<String> = expected
+        .map(|e| e.tiers.iter().map(|t| t.label.clone()).collect())
+        .unwrap_or_default();
+    compliance(&samples, &tiers, higher_is_better)
+}
 
-    let dss = create_datasets(config, points);
+/// points of a chart, missing values and failed runs are drawn as configured by missing_values.
+/// for 'marker' the second dataset marks the missing values of runs that did not fail
+/// (failed runs have their own marker)
+fn create_points<T: Copy>(
+    config: &ChartConfig<T>,
+    data: &[ParsedEntry],
+    default_value: T,
+    value: impl Fn(&Performance) -> Option<T>,
+) -> (Vec<Point<T>>, Vec<Dataset<T>>) {
+    let mut points = Vec::new();
+    let mut missing = Vec::new();
+    let mut last: Option<T> = None;
+    for d in data {
+        let x: String = d.timestamp.format(DATE_TIME_FORMAT).to_string();
+        let measured = d.performance.as_ref().and_then(&value);
+        if measured.is_some() {
+            last = measured;
+            points.push(Point { x, y: measured });
+            continue;
+        }
+        match config.missing_values {
+            MissingValues::Gap => points.push(Point { x, y: None }),
+            MissingValues::Default => points.push(Point {
+                x,
+                y: Some(default_value),
+            }),
+            MissingValues::CarryForward => points.push(Point { x, y: last }),
+            MissingValues::Marker if !d.is_failed_run() => missing.push(Point {
+                x,
+                y: Some(default_value),
+            }),
+            MissingValues::Marker => (),
+        }
+    }
+    let marker = if missing.is_empty() {
+        Vec::new()
+    } else {
+        vec![Dataset {
+            label: format!("{} {}", config.label, MISSING_VALUES_LABEL),
+            data: missing,
+            fill: false,
+            border_color: String::from(&config.border_color),
+            show_line: Some(false),
+            point_style: Some(String::from(MISSING_VALUES_POINT_STYLE)),
+            point_radius: Some(FAILED_RUNS_POINT_RADIUS),
+            band_color: None,
+            vertical_lines: None,
+            border_dash: None,
+        }]
+    };
+    (points, marker)
+}
 
-    let mut values: Vec<f64> = data
+/// at most max_points of the series (0 for all), statistics are calculated with every point.
+/// consecutive gaps are kept as one gap, if there are too many gaps the longest are kept
+/// and they get at most half of the points left after DOWNSAMPLING_MIN_POINTS
+fn downsample_points<T: ChartValue>(mut points: Vec<Point<T>>, max_points: usize) -> Vec<Point<T>> {
+    if max_points == 0 || points.len() <= max_points {
+        return points;
+    }
+    points.dedup_by(|a, b| a.y.is_none() && b.y.is_none());
+    let first = points
+        .first()
+        .and_then(|p| NaiveDateTime::parse_from_str(&p.x, DATE_TIME_FORMAT).ok());
+    let xs: Vec<f64> = points
         .iter()
-        .flat_map(|d| {
-            let y = d.performance.as_ref().map(|p| p.upload);
-            y
+        .enumerate()
+        .map(|(i, p)| {
+            let t = NaiveDateTime::parse_from_str(&p.x, DATE_TIME_FORMAT).ok();
+            match (first, t) {
+                (Some(first), Some(t)) => (t - first).num_seconds() as f64,
+                _ => i as f64,
+            }
         })
-        .flatten()
         .collect();
+    let measured: Vec<(usize, (f64, f64))> = points
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| p.y.map(|y| (i, (xs[i], y.into()))))
+        .collect();
+    let mut gaps: Vec<usize> = (0..points.len())
+        .filter(|i| points[*i].y.is_none())
+        .collect();
+    let measured_budget = max_points
+        - gaps
+            .len()
+            .min(max_points.saturating_sub(DOWNSAMPLING_MIN_POINTS) / 2);
+    let gap_budget = max_points - measured_budget.min(measured.len());
+    if gaps.len() > gap_budget {
+        // the time between the measurements around the gap:
+        let duration = |i: usize| xs[(i + 1).min(xs.len() - 1)] - xs[i.saturating_sub(1)];
+        gaps.sort_by(|a, b| {
+            duration(*b)
+                .partial_cmp(&duration(*a))
+                .unwrap_or(Ordering::Equal)
+        });
+        gaps.truncate(gap_budget);
+    }
+    let xy: Vec<(f64, f64)> = measured.iter().map(|(_, p)| *p).collect();
+    let mut keep: Vec<bool> = vec![false; points.len()];
+    for i in gaps {
+        keep[i] = true;
+    }
+    for i in downsample(&xy, measured_budget) {
+        keep[measured[i].0] = true;
+    }
+    points
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(p, k)| if k { Some(p) } else { None })
+        .collect()
+}
+
+/// one point per day or week with the median of the bucket, the min and max of the buckets
+/// are returned as two datasets with a band drawn between them
+fn create_rollup_points<T: ChartValue>(
+    config: &ChartConfig<T>,
+    data: &[ParsedEntry],
+    default_value: T,
+    period: RollupPeriod,
+    value: impl Fn(&Performance) -> Option<T>,
+) -> (Vec<Point<T>>, Vec<Dataset<T>>) {
+    //every run opens its bucket, so buckets with failed runs only are missing values:
+    let mut buckets: BTreeMap<NaiveDate, Vec<f64>> = BTreeMap::new();
+    for d in data {
+        let date = d.timestamp.date();
+        let start = match period {
+            RollupPeriod::Daily => date,
+            RollupPeriod::Weekly => {
+                date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+            }
+        };
+        let values = buckets.entry(start).or_default();
+        if let Some(v) = d.performance.as_ref().and_then(&value) {
+            values.push(v.into());
+        }
+    }
 
-    create_chart(dss, &mut values, MEGA_BIT_FACTOR)
+    let mut points = Vec::new();
+    let mut mins = Vec::new();
+    let mut maxs = Vec::new();
+    let mut last: Option<T> = None;
+    for (start, values) in buckets {
+        let x: String = start
+            .and_hms_opt(0, 0, 0)
+            .map(|t| t.format(DATE_TIME_FORMAT).to_string())
+            .unwrap_or_default();
+        let summary = Summary::new(values);
+        match (
+            summary.median(),
+            summary.value(Statistic::Min),
+            summary.value(Statistic::Max),
+        ) {
+            (Some(median), Some(min), Some(max)) => {
+                last = Some(T::from_f64(median));
+                mins.push(Point {
+                    x: x.clone(),
+                    y: Some(T::from_f64(min)),
+                });
+                maxs.push(Point {
+                    x: x.clone(),
+                    y: Some(T::from_f64(max)),
+                });
+                points.push(Point { x, y: last });
+            }
+            _ => match config.missing_values {
+                MissingValues::Gap => points.push(Point { x, y: None }),
+                MissingValues::Default => points.push(Point {
+                    x,
+                    y: Some(default_value),
+                }),
+                MissingValues::CarryForward => points.push(Point { x, y: last }),
+                // buckets without any value are only failed runs, they have their own marker:
+                MissingValues::Marker => (),
+            },
+        }
+    }
+    if points.is_empty() {
+        return (points, Vec::new());
+    }
+    let band_dss = create_band_datasets(
+        &config.label,
+        &config.border_color,
+        (ROLLUP_MAX_LABEL, maxs),
+        (ROLLUP_MIN_LABEL, mins),
+    );
+    (points, band_dss)
 }
 
-// helper methods:
+/// two datasets without line, the band between them is shaded
+fn create_band_datasets<T>(
+    label: &str,
+    color: &str,
+    (upper_label, upper): (&str, Vec<Point<T>>),
+    (lower_label, lower): (&str, Vec<Point<T>>),
+) -> Vec<Dataset<T>> {
+    let band = |suffix: &str, data: Vec<Point<T>>, band_color: Option<String>| Dataset {
+        label: format!("{} {}", label, suffix),
+        data,
+        fill: false,
+        border_color: String::from(color),
+        show_line: Some(false),
+        point_style: None,
+        point_radius: Some(0),
+        band_color,
+        vertical_lines: None,
+        border_dash: None,
+    };
+    // the band is drawn from upper to the dataset that follows it:
+    vec![
+        band(upper_label, upper, Some(String::from(color))),
+        band(lower_label, lower, None),
+    ]
+}
 
-fn create_datasets<T: Copy>(config: &ChartConfig<T>, points: Vec<Point<T>>) -> Vec<Dataset<T>> {
-    let expected_ds = config
-        .expected_value
-        .as_ref()
-        .map(|c| create_dataset_expected(c, &points));
+/// dashed projection of the next days with its confidence band, none if there are too few samples
+fn create_forecast_datasets<T: ChartValue>(
+    config: &ForecastConfig,
+    samples: &[(NaiveDateTime, f64)],
+) -> Vec<Dataset<T>> {
+    let points = match forecast(samples, config.days) {
+        Some(points) => points,
+        None => return Vec::new(),
+    };
+    let point = |p: &ForecastPoint, y: f64| Point {
+        x: p.timestamp.format(DATE_TIME_FORMAT).to_string(),
+        y: Some(T::from_f64(y)),
+    };
+    let line = Dataset {
+        label: String::from(&config.label),
+        data: points.iter().map(|p| point(p, p.value)).collect(),
+        fill: false,
+        border_color: String::from(&config.border_color),
+        show_line: None,
+        point_style: None,
+        point_radius: Some(0),
+        band_color: None,
+        vertical_lines: None,
+        border_dash: Some(FORECAST_BORDER_DASH.to_vec()),
+    };
+    let band_dss = create_band_datasets(
+        &config.label,
+        &config.border_color,
+        (
+            FORECAST_UPPER_LABEL,
+            points.iter().map(|p| point(p, p.upper)).collect(),
+        ),
+        (
+            FORECAST_LOWER_LABEL,
+            points.iter().map(|p| point(p, p.lower)).collect(),
+        ),
+    );
+    std::iter::once(line).chain(band_dss).collect()
+}
+
+fn create_datasets<T: ChartValue>(
+    config: &ChartConfig<T>,
+    data: &[ParsedEntry],
+    baseline: &[ParsedEntry],
+    default_value: T,
+    charts: &ChartConfigs,
+    value: impl Fn(&Performance) -> Option<T>,
+) -> (Vec<Dataset<T>>, Findings) {
+    //every run with a value, trends, changes and anomalies do not depend on rollup or downsampling:
+    let to_samples = |entries: &[ParsedEntry]| -> Vec<(NaiveDateTime, f64)> {
+        entries
+            .iter()
+            .filter_map(|d| {
+                let y = d.performance.as_ref().and_then(&value);
+                y.map(|v| (d.timestamp, v.into()))
+            })
+            .collect()
+    };
+    let samples = to_samples(data);
+    let trend_dss: Vec<Dataset<T>> = [
+        (&config.rolling_median, rolling_median as TrendFn),
+        (&config.ewma, |s, w, u| {
+            ewma(s, w, u).into_iter().map(Some).collect()
+        }),
+    ]
+    .iter()
+    .filter_map(|(c, trend)| {
+        let c = c.as_ref()?;
+        Some(create_dataset_trend(c, *trend, &samples, charts.max_points))
+    })
+    .collect();
+    let forecast_dss: Vec<Dataset<T>> = match &config.forecast {
+        Some(c) => create_forecast_datasets(c, &samples),
+        None => Vec::new(),
+    };
+    let findings = Findings {
+        changes: detect_changes(&config.label, &samples, &charts.changes),
+        anomalies: detect_anomalies(
+            &config.label,
+            &samples,
+            &to_samples(baseline),
+            config.anomaly_threshold,
+        ),
+    };
+    let (points, additional) = match charts.rollup {
+        Some(period) => create_rollup_points(config, data, default_value, period, value),
+        None => {
+            let (points, marker) = create_points(config, data, default_value, value);
+            (downsample_points(points, charts.max_points), marker)
+        }
+    };
+    let expected_dss: Vec<Dataset<T>> = match &config.expected_value {
+        Some(c) => {
+            let value_ds = create_dataset_expected(
+                &c.label,
+                c.fill,
+                &c.border_color,
+                c.value,
+                &c.history,
+                &points,
+            );
+            let tier_dss = c.tiers.iter().filter_map(|t| {
+                create_dataset_expected(
+                    &t.label,
+                    t.fill,
+                    &t.border_color,
+                    Some(t.value),
+                    &t.history,
+                    &points,
+                )
+            });
+            value_ds.into_iter().chain(tier_dss).collect()
+        }
+        None => Vec::new(),
+    };
 
     let ds = create_dataset(config, points);
-    vec![Some(ds), expected_ds].into_iter().flatten().collect()
+    let dss = std::iter::once(ds)
+        .chain(trend_dss)
+        .chain(forecast_dss)
+        .chain(expected_dss)
+        .chain(additional)
+        .chain(create_dataset_anomalies(config, &findings.anomalies))
+        .chain(create_dataset_changes(config, &findings.changes))
+        .collect();
+    (dss, findings)
 }
 
 /// dataset containing the real data:
@@ -358,70 +900,296 @@ fn create_dataset<T: Copy>(config: &ChartConfig<T>, points: Vec<Point<T>>) -> Da
         data: points,
         fill: config.fill,
         border_color: String::from(&config.border_color),
+        show_line: None,
+        point_style: None,
+        point_radius: None,
+        band_color: None,
+        vertical_lines: None,
+        border_dash: None,
     }
 }
 
-/// dataset for expected line:
-fn create_dataset_expected<T: Copy>(config: &ExpectedConfig<T>, points: &[Point<T>]) -> Dataset<T> {
-    let default_x = &String::from("");
-    let first_x: &str = points.first().map(|p| &p.x).unwrap_or(default_x);
-    let last_x: &str = points.last().map(|p| &p.x).unwrap_or(default_x);
+/// one value per sample
+type TrendFn = fn(&[(NaiveDateTime, f64)], u32, WindowUnit) -> Vec<Option<f64>>;
+
+/// dataset of a trend line, calculated from every run with a value
+fn create_dataset_trend<T: ChartValue>(
+    config: &TrendConfig,
+    trend: TrendFn,
+    samples: &[(NaiveDateTime, f64)],
+    max_points: usize,
+) -> Dataset<T> {
+    let points: Vec<Point<T>> = samples
+        .iter()
+        .zip(trend(samples, config.window, config.window_unit))
+        .map(|((timestamp, _), y)| Point {
+            x: timestamp.format(DATE_TIME_FORMAT).to_string(),
+            y: y.map(T::from_f64),
+        })
+        .collect();
     Dataset {
         label: String::from(&config.label),
-        data: vec![
-            Point {
-                x: String::from(first_x),
-                y: config.value,
-            },
-            Point {
-                x: String::from(last_x),
-                y: config.value,
-            },
-        ],
-        fill: config.fill,
+        data: downsample_points(points, max_points),
+        fill: false,
         border_color: String::from(&config.border_color),
+        show_line: None,
+        point_style: None,
+        point_radius: Some(0),
+        band_color: None,
+        vertical_lines: None,
+        border_dash: None,
     }
 }
 
-/// create chart an do some statistics:
-fn create_chart<T: Copy>(dss: Vec<Dataset<T>>, values: &mut Vec<f64>, divisor: f64) -> Chart<T> {
-    let med: f64 = median(values); //is also sorting!
-    let avg: f64 = average(values);
-    let std: f64 = standard_deviation(values, &avg);
+/// measurements of a compared time range, x is the day of the time range (1.0 is the first midnight),
+/// the second time range is drawn dashed
+fn create_dataset_period<T: ChartValue>(
+    config: &ChartConfig<T>,
+    period: Period,
+    data: &[ParsedEntry],
+    dashed: bool,
+    max_points: usize,
+    value: impl Fn(&Performance) -> Option<T>,
+) -> Dataset<T, f64> {
+    let start = period.from.and_hms_opt(0, 0, 0).unwrap_or_default();
+    let points: Vec<Point<T, f64>> = data
+        .iter()
+        .filter_map(|d| {
+            let y = d.performance.as_ref().and_then(&value)?;
+            let day = 1.0 + (d.timestamp - start).num_seconds() as f64 / SECONDS_PER_DAY;
+            Some(Point { x: day, y: Some(y) })
+        })
+        .collect();
+    let xy: Vec<(f64, f64)> = points
+        .iter()
+        .map(|p| (p.x, p.y.map(Into::into).unwrap_or_default()))
+        .collect();
+    let mut keep = vec![false; points.len()];
+    for i in downsample(&xy, max_points) {
+        keep[i] = true;
+    }
+    Dataset {
+        label: format!("{} {}", config.label, period),
+        data: points
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(p, k)| if k { Some(p) } else { None })
+            .collect(),
+        fill: false,
+        border_color: String::from(&config.border_color),
+        show_line: None,
+        point_style: None,
+        point_radius: Some(0),
+        band_color: None,
+        vertical_lines: None,
+        border_dash: if dashed {
+            Some(COMPARE_BORDER_DASH.to_vec())
+        } else {
+            None
+        },
+    }
+}
 
-    Chart {
-        datasets: dss,
-        median: med / divisor,
-        average: avg / divisor,
-        standard_deviation: std / divisor,
+/// dataset highlighting the anomalous measurements
+fn create_dataset_anomalies<T: ChartValue>(
+    config: &ChartConfig<T>,
+    anomalies: &[Anomaly],
+) -> Option<Dataset<T>> {
+    if anomalies.is_empty() {
+        return None;
     }
+    Some(Dataset {
+        label: format!("{} {}", config.label, ANOMALIES_LABEL),
+        data: anomalies
+            .iter()
+            .map(|a| Point {
+                x: a.timestamp.format(DATE_TIME_FORMAT).to_string(),
+                y: Some(T::from_f64(a.value)),
+            })
+            .collect(),
+        fill: false,
+        border_color: String::from(ANOMALIES_COLOR),
+        show_line: Some(false),
+        point_style: Some(String::from(ANOMALIES_POINT_STYLE)),
+        point_radius: Some(ANOMALIES_POINT_RADIUS),
+        band_color: None,
+        vertical_lines: None,
+        border_dash: None,
+    })
 }
 
-fn median(numbers: &mut Vec<f64>) -> f64 {
-    numbers.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    let mid: usize = numbers.len() / 2;
-    numbers[mid]
+/// dataset with a vertical line at every change, the point is the median after the change
+fn create_dataset_changes<T: ChartValue>(
+    config: &ChartConfig<T>,
+    changes: &[Change],
+) -> Option<Dataset<T>> {
+    if changes.is_empty() {
+        return None;
+    }
+    Some(Dataset {
+        label: format!("{} {}", config.label, CHANGES_LABEL),
+        data: changes
+            .iter()
+            .map(|c| Point {
+                x: c.first_after.format(DATE_TIME_FORMAT).to_string(),
+                y: Some(T::from_f64(c.median_after)),
+            })
+            .collect(),
+        fill: false,
+        border_color: String::from(&config.border_color),
+        show_line: Some(false),
+        point_style: None,
+        point_radius: Some(CHANGES_POINT_RADIUS),
+        band_color: None,
+        vertical_lines: Some(true),
+        border_dash: None,
+    })
 }
 
-fn average(numbers: &[f64]) -> f64 {
-    let len: f64 = numbers.len() as f64;
-    numbers.iter().sum::<f64>() / len
+/// dataset for an expected line (the single expected value or a tier),
+/// changes of the value are drawn as steps:
+fn create_dataset_expected<T: Copy>(
+    label: &str,
+    fill: bool,
+    border_color: &str,
+    value: Option<T>,
+    history: &[ExpectedChange<T>],
+    points: &[Point<T>],
+) -> Option<Dataset<T>> {
+    if points.is_empty() {
+        return None;
+    }
+    // from the first to the last run, whatever is drawn for missing values:
+    let first_x = points.first().map(|p| p.x.clone()).unwrap_or_default();
+    let last_x = points.last().map(|p| p.x.clone()).unwrap_or_default();
+    // formatted timestamps sort like the timestamps:
+    let mut changes: Vec<(String, T)> = history
+        .iter()
+        .filter_map(|c| {
+            c.valid_from
+                .and_hms_opt(0, 0, 0)
+                .map(|t| (t.format(DATE_TIME_FORMAT).to_string(), c.value))
+        })
+        .collect();
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut current = changes
+        .iter()
+        .filter(|(x, _)| *x <= first_x)
+        .last()
+        .map(|(_, v)| *v)
+        .or(value);
+    let mut data = Vec::new();
+    if let Some(y) = current {
+        data.push(Point {
+            x: first_x.clone(),
+            y: Some(y),
+        });
+    }
+    for (x, y) in changes
+        .into_iter()
+        .filter(|(x, _)| *x > first_x && *x <= last_x)
+    {
+        if current.is_some() {
+            data.push(Point {
+                x: x.clone(),
+                y: current,
+            });
+        }
+        data.push(Point { x, y: Some(y) });
+        current = Some(y);
+    }
+    if current.is_some() {
+        data.push(Point {
+            x: last_x,
+            y: current,
+        });
+    }
+    if data.is_empty() {
+        return None;
+    }
+    Some(Dataset {
+        label: String::from(label),
+        data,
+        fill,
+        border_color: String::from(border_color),
+        show_line: None,
+        point_style: None,
+        point_radius: None,
+        band_color: None,
+        vertical_lines: None,
+        border_dash: None,
+    })
 }
 
-fn standard_deviation(numbers: &[f64], average: &f64) -> f64 {
-    let variance: f64 = numbers
+/// dataset with a marker for every failed run, none if all runs succeeded:
+fn create_dataset_failed_runs<T: Default>(data: &[ParsedEntry]) -> Option<Dataset<T>> {
+    let points: Vec<Point<T>> = data
         .iter()
-        .map(|x| {
-            let y = x - average;
-            y * y
+        .filter(|d| d.is_failed_run())
+        .map(|d| Point {
+            x: d.timestamp.format(DATE_TIME_FORMAT).to_string(),
+            y: Some(T::default()),
         })
-        .sum::<f64>();
-    let d = numbers.len() as f64;
-    f64::sqrt(variance / d)
+        .collect();
+    if points.is_empty() {
+        return None;
+    }
+    Some(Dataset {
+        label: String::from(FAILED_RUNS_LABEL),
+        data: points,
+        fill: false,
+        border_color: String::from(FAILED_RUNS_COLOR),
+        show_line: Some(false),
+        point_style: Some(String::from(FAILED_RUNS_POINT_STYLE)),
+        point_radius: Some(FAILED_RUNS_POINT_RADIUS),
+        band_color: None,
+        vertical_lines: None,
+        border_dash: None,
+    })
+}
+
+/// create chart an do some statistics:
+fn create_chart<T: Copy>(
+    dss: Vec<Dataset<T>>,
+    values: Vec<f64>,
+    divisor: f64,
+    compliance: Compliance,
+    findings: Findings,
+) -> Chart<T> {
+    let summary = Summary::new(values.into_iter().map(|v| v / divisor).collect());
+    Chart {
+        datasets: dss,
+        summary,
+        compliance,
+        findings,
+    }
+}
+
+/// share in percent, n/a if it is a share of nothing
+fn format_share(share: f64, of: usize) -> String {
+    if of == 0 {
+        String::from(STATISTIC_NOT_AVAILABLE)
+    } else {
+        format!("{:.1}%", share)
+    }
 }
 
-fn write_raw_data(data: &[ParsedEntry], out_file: &mut File, prefix: &str, suffix: &str) {
-    handle_failed_write(writeln!(
+fn format_statistic(summary: &Summary, statistic: Statistic) -> String {
+    match (statistic, summary.value(statistic)) {
+        (Statistic::Count, _) => summary.count().to_string(),
+        (_, Some(v)) => format!("{:.3}", v),
+        (_, None) => String::from(STATISTIC_NOT_AVAILABLE),
+    }
+}
+
+fn write_raw_data(
+    data: &[ParsedEntry],
+    out_file: &mut File,
+    prefix: &str,
+    suffix: &str,
+) -> std::io::Result<()> {
+    writeln!(
         out_file,
         "{}<table id=\"rawdata\">\
               <tr>\
@@ -441,18 +1209,20 @@ fn write_raw_data(data: &[ParsedEntry], out_file: &mut File, prefix: &str, suffi
                   <th class=\"performance\">upload_config</th>\
                   <th class=\"performance\">download (bits per second)</th>\
                   <th class=\"performance\">upload (bits per second)</th>\
+                  <th class=\"run\">run</th>\
+                  <th class=\"run\">backend</th>\
               </tr>",
         prefix
-    ));
+    )?;
     for entry in data {
-        handle_failed_write(writeln!(
+        writeln!(
             out_file,
             "<tr>
                  <td class=\"ts\">{}</td>",
             entry.timestamp.format(DATE_TIME_FORMAT)
-        ));
+        )?;
         if let Some(client) = &entry.client {
-            handle_failed_write(writeln!(
+            writeln!(
                 out_file,
                 "<td class=\"client\">{}</td>
                  <td class=\"client\">{}</td>
@@ -464,30 +1234,24 @@ fn write_raw_data(data: &[ParsedEntry], out_file: &mut File, prefix: &str, suffi
                 client.lat,
                 client.lon,
                 client.isp
-            ));
+            )?;
         } else {
-            handle_failed_write(writeln!(
-                out_file,
-                "<td colspan=\"5\" class=\"client\"></td>"
-            ));
+            writeln!(out_file, "<td colspan=\"5\" class=\"client\"></td>")?;
         }
         if let Some(server) = &entry.server {
-            handle_failed_write(writeln!(
+            writeln!(
                 out_file,
                 "<td class=\"server\">{}</td>
                  <td class=\"server\">{}</td>
                  <td class=\"server\">{}</td>
                  <td class=\"server\">{}</td>",
                 server.name, server.sponsor, server.distance, server.host
-            ));
+            )?;
         } else {
-            handle_failed_write(writeln!(
-                out_file,
-                "<td colspan=\"4\" class=\"server\"></td>"
-            ));
+            writeln!(out_file, "<td colspan=\"4\" class=\"server\"></td>")?;
         }
         if let Some(performance) = &entry.performance {
-            handle_failed_write(writeln!(
+            writeln!(
                 out_file,
                 "<td class=\"performance\">{}</td>
                  <td class=\"performance\">{}</td>
@@ -495,22 +1259,39 @@ fn write_raw_data(data: &[ParsedEntry], out_file: &mut File, prefix: &str, suffi
                  <td class=\"performance\">{}</td>
                  <td class=\"performance\">{}</td>
                  <td class=\"performance\">{}</td>",
-                performance.latency,
+                to_string(&performance.latency),
                 to_string(&performance.jitter),
                 to_string(&performance.download_config),
                 to_string(&performance.upload_config),
                 to_string(&performance.download),
                 to_string(&performance.upload)
-            ));
+            )?;
         } else {
-            handle_failed_write(writeln!(
+            writeln!(out_file, "<td colspan=\"6\" class=\"performance\"></td>")?;
+        }
+        if let Some(run) = &entry.run {
+            writeln!(
                 out_file,
-                "<td colspan=\"6\" class=\"performance\"></td>"
-            ));
+                "<td class=\"run\" title=\"{}\">{}</td>
+                 <td class=\"run\">{}</td>",
+                escape_html(run.stderr.as_deref().unwrap_or_default()),
+                run.status,
+                to_string(&run.backend)
+            )?;
+        } else {
+            writeln!(out_file, "<td colspan=\"2\" class=\"run\"></td>")?;
         }
-        handle_failed_write(writeln!(out_file, "</tr>"));
+        writeln!(out_file, "</tr>")?;
     }
-    handle_failed_write(writeln!(out_file, "</table>{}", suffix));
+    writeln!(out_file, "</table>{}", suffix)
+}
+
+/// error output may contain anything
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
 fn to_string<T: Display>(op: &Option<T>) -> String {
@@ -547,30 +1328,747 @@ fn create_statistic_table<N: Copy>(
     chart_config: &ChartConfig<N>,
     chart: &Chart<N>,
 ) -> String {
+    let statistics = &chart_config.statistics;
+    // the compliance rows have a column for each level:
+    let columns = statistics.len().max(COMPLIANCE_LEVELS.len());
     format!(
         "<table class=\"statistic_{}\">\
               <tr>\
-                <th colspan=\"3\">{}</th>\
+                <th colspan=\"{}\">{}</th>\
               </tr>
+              <tr>{}</tr>
+              <tr>{}</tr>\
+              {}\
+         </table>\
+        ",
+        id,
+        columns,
+        chart_config.label,
+        statistics
+            .iter()
+            .map(|s| format!("<th>{}</th>", s))
+            .collect::<String>(),
+        statistics
+            .iter()
+            .map(|s| format!("<td>{}</td>", format_statistic(&chart.summary, *s)))
+            .collect::<String>(),
+        create_compliance_rows(&chart.compliance, columns)
+    )
+}
+
+/// availability and, if an expected value is configured, how often it was reached
+fn create_compliance_rows(compliance: &Compliance, columns: usize) -> String {
+    let availability = format!(
+        "<tr>\
+           <td colspan=\"{}\">{} {} ({} of {} runs)</td>\
+         </tr>",
+        columns,
+        STATISTIC_AVAILABILITY,
+        format_share(compliance.availability(), compliance.runs),
+        compliance.available,
+        compliance.runs
+    );
+    let expected = match &compliance.expected {
+        Some(e) => {
+            format!(
+                "<tr>{}</tr>\
+                 <tr>{}</tr>\
+                 <tr>\
+                   <td colspan=\"{}\">{} {} of {} days ({})</td>\
+                 </tr>",
+                e.reached
+                    .iter()
+                    .map(|(level, _)| format!("<th>{} {}%</th>", STATISTIC_REACHED, level))
+                    .collect::<String>(),
+                e.reached
+                    .iter()
+                    .map(|(_, share)| format!("<td>{}</td>", format_share(*share, e.compared)))
+                    .collect::<String>(),
+                columns,
+                STATISTIC_DAYS_MET,
+                e.days_met,
+                e.days,
+                format_share(e.days_met_share(), e.days)
+            ) + &create_tier_rows(&e.tiers, e.compared)
+        }
+        None => String::new(),
+    };
+    availability + &expected
+}
+
+/// share of the measurements meeting each tier, e.g. "normal (200 / 250): 85.0%"
+fn create_tier_rows(tiers: &[TierCompliance], compared: usize) -> String {
+    if tiers.is_empty() {
+        return String::new();
+    }
+    format!(
+        "<tr>{}</tr>\
+         <tr>{}</tr>",
+        tiers
+            .iter()
+            .map(|t| format!("<th>{} ({})</th>", t.label, format_values(&t.values)))
+            .collect::<String>(),
+        tiers
+            .iter()
+            .map(|t| format!("<td>{}</td>", format_share(t.met, compared)))
+            .collect::<String>()
+    )
+}
+
+fn format_tiers(tiers: &[TierCompliance], compared: usize) -> String {
+    tiers
+        .iter()
+        .map(|t| {
+            format!(
+                "{} ({}) {}",
+                t.label,
+                format_values(&t.values),
+                format_share(t.met, compared)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// values in force during the time range, e.g. "250 / 500" after a contract change
+fn format_values(values: &[f64]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(" / ")
+}
+
+/// compliance of all charts in one table, e.g. for a complaint to the provider
+fn create_compliance_table(charts: &[(&str, &Compliance)]) -> String {
+    let level_headers: String = COMPLIANCE_LEVELS
+        .iter()
+        .map(|level| format!("<th>{} {}%</th>", STATISTIC_REACHED, level))
+        .collect();
+    let rows: String = charts
+        .iter()
+        .map(|(label, compliance)| {
+            let expected_cells = match &compliance.expected {
+                Some(e) => format!(
+                    "<td>{}</td>{}<td>{} of {} ({})</td><td>{}</td>",
+                    format_values(&e.values),
+                    e.reached
+                        .iter()
+                        .map(|(_, share)| format!("<td>{}</td>", format_share(*share, e.compared)))
+                        .collect::<String>(),
+                    e.days_met,
+                    e.days,
+                    format_share(e.days_met_share(), e.days),
+                    format_tiers(&e.tiers, e.compared)
+                ),
+                None => format!("<td colspan=\"{}\"></td>", COMPLIANCE_LEVELS.len() + 3),
+            };
+            format!(
+                "<tr>\
+                   <th>{}</th>\
+                   <td>{} ({} of {})</td>\
+                   {}\
+                 </tr>",
+                label,
+                format_share(compliance.availability(), compliance.runs),
+                compliance.available,
+                compliance.runs,
+                expected_cells
+            )
+        })
+        .collect();
+    format!(
+        "<table id=\"compliance\">\
               <tr>\
+                <th></th>\
                 <th>{}</th>\
+                <th>expected</th>\
+                {}\
                 <th>{}</th>\
                 <th>{}</th>\
-              </tr>
+              </tr>\
+              {}\
+         </table>",
+        STATISTIC_AVAILABILITY, level_headers, STATISTIC_DAYS_MET, STATISTIC_TIERS, rows
+    )
+}
+
+/// share of the runs that measured something
+fn create_availability_text(data: &[ParsedEntry]) -> String {
+    let runs = data.len();
+    let failed = data.iter().filter(|d| d.performance.is_none()).count();
+    let share = if runs == 0 {
+        0.0
+    } else {
+        (runs - failed) as f64 * 100.0 / runs as f64
+    };
+    format!(
+        "{} of {} runs successful ({}), {} failed",
+        runs - failed,
+        runs,
+        format_share(share, runs),
+        failed
+    )
+}
+
+/// one svg per metric: the median of every weekday x hour and of every hour of all days,
+/// colored from red (worst) to green (best)
+fn create_heatmap_section(aggregation: &Aggregation) -> String {
+    aggregation.metrics.iter().map(create_heatmap).collect()
+}
+
+fn create_heatmap(metric: &MetricAggregation) -> String {
+    let medians: Vec<f64> = metric
+        .by_weekday_hour
+        .iter()
+        .chain(metric.by_hour.iter())
+        .filter_map(|b| b.median)
+        .collect();
+    let min = medians.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = medians.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let color = |median: Option<f64>| match median {
+        Some(m) => {
+            let share = if max > min {
+                (m - min) / (max - min)
+            } else {
+                1.0
+            };
+            let good = if metric.higher_is_better {
+                share
+            } else {
+                1.0 - share
+            };
+            format!("hsl({:.0},70%,50%)", good * 120.0)
+        }
+        None => String::from(HEATMAP_EMPTY_COLOR),
+    };
+    let cell = |row: usize, label: &str, bucket: &Bucket| {
+        format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\">\
+               <title>{} {}:00 median {} P10 {} {} (n = {})</title>\
+             </rect>",
+            HEATMAP_LABEL_WIDTH + bucket.hour as usize * HEATMAP_CELL_WIDTH,
+            HEATMAP_LABEL_HEIGHT + row * HEATMAP_CELL_HEIGHT,
+            HEATMAP_CELL_WIDTH - 1,
+            HEATMAP_CELL_HEIGHT - 1,
+            color(bucket.median),
+            label,
+            bucket.hour,
+            format_bucket_value(bucket.median),
+            format_bucket_value(bucket.p10),
+            metric.unit,
+            bucket.count
+        )
+    };
+    let cells: String = metric
+        .by_weekday_hour
+        .iter()
+        .map(|b| {
+            let weekday = b.weekday.map(|w| w.to_string()).unwrap_or_default();
+            cell(
+                b.weekday
+                    .map(|w| w.num_days_from_monday() as usize)
+                    .unwrap_or_default(),
+                &weekday,
+                b,
+            )
+        })
+        .chain(
+            metric
+                .by_hour
+                .iter()
+                .map(|b| cell(DAYS_PER_WEEK, HEATMAP_ALL_DAYS_LABEL, b)),
+        )
+        .collect();
+    let row_labels: String = (0..DAYS_PER_WEEK)
+        .map(|row| {
+            metric.by_weekday_hour[row * HOURS_PER_DAY]
+                .weekday
+                .map(|w| w.to_string())
+                .unwrap_or_default()
+        })
+        .chain(std::iter::once(String::from(HEATMAP_ALL_DAYS_LABEL)))
+        .enumerate()
+        .map(|(row, label)| {
+            format!(
+                "<text x=\"0\" y=\"{}\">{}</text>",
+                HEATMAP_LABEL_HEIGHT + (row + 1) * HEATMAP_CELL_HEIGHT - 4,
+                label
+            )
+        })
+        .collect();
+    let hour_labels: String = (0..HOURS_PER_DAY)
+        .step_by(3)
+        .map(|hour| {
+            format!(
+                "<text x=\"{}\" y=\"{}\">{}</text>",
+                HEATMAP_LABEL_WIDTH + hour * HEATMAP_CELL_WIDTH,
+                HEATMAP_LABEL_HEIGHT - 4,
+                hour
+            )
+        })
+        .collect();
+    format!(
+        "<figure class=\"heatmap\">\
+           <figcaption>{} median ({})</figcaption>\
+           <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-size=\"12\">\
+             {}{}{}\
+           </svg>\
+         </figure>",
+        metric.metric,
+        metric.unit,
+        HEATMAP_LABEL_WIDTH + HOURS_PER_DAY * HEATMAP_CELL_WIDTH,
+        HEATMAP_LABEL_HEIGHT + (DAYS_PER_WEEK + 1) * HEATMAP_CELL_HEIGHT,
+        hour_labels,
+        row_labels,
+        cells
+    )
+}
+
+fn format_bucket_value(value: Option<f64>) -> String {
+    match value {
+        Some(v) => format!("{:.1}", v),
+        None => String::from(STATISTIC_NOT_AVAILABLE),
+    }
+}
+
+/// medians of both time ranges, their difference and the result of the Mann-Whitney U test
+fn create_comparison_table(comparison: &Comparison) -> String {
+    let format_value = |v: Option<f64>| match v {
+        Some(v) => format!("{:.2}", v),
+        None => String::from(STATISTIC_NOT_AVAILABLE),
+    };
+    let rows: String = comparison
+        .metrics
+        .iter()
+        .map(|m| {
+            format!(
+                "<tr class=\"statistic_{}\">\
+                   <td>{} ({})</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                 </tr>",
+                m.metric,
+                m.metric,
+                m.unit,
+                m.first_count,
+                format_value(m.first_median),
+                m.second_count,
+                format_value(m.second_median),
+                m.delta
+                    .map(|d| format!("{:+.2}", d))
+                    .unwrap_or_else(|| String::from(STATISTIC_NOT_AVAILABLE)),
+                m.change_percent
+                    .map(|c| format!("{:+.1}%", c))
+                    .unwrap_or_else(|| String::from(STATISTIC_NOT_AVAILABLE)),
+                m.test
+                    .map(|t| format!("{:.4}", t.p_value))
+                    .unwrap_or_else(|| String::from(STATISTIC_NOT_AVAILABLE)),
+                m.verdict
+            )
+        })
+        .collect();
+    format!(
+        "<table id=\"comparison\">\
+              <tr>\
+                <th></th>\
+                <th colspan=\"2\">{}</th>\
+                <th colspan=\"2\">{} (dashed)</th>\
+                <th colspan=\"5\"></th>\
+              </tr>\
               <tr>\
-                <td>{:.3}</td>\
-                <td>{:.3}</td>\
-                <td>{:.3}</td>\
+                <th>chart</th>\
+                <th>n</th>\
+                <th>median</th>\
+                <th>n</th>\
+                <th>median</th>\
+                <th>delta</th>\
+                <th>change</th>\
+                <th>p-value</th>\
+                <th>verdict</th>\
               </tr>\
+              {}\
          </table>\
-        ",
-        id,
+         <p>Mann-Whitney U test, a p-value below {} is a significant difference</p>",
+        comparison.first, comparison.second, rows, COMPARE_SIGNIFICANCE_LEVEL
+    )
+}
+
+/// changes of all charts sorted by time, with the medians before and after
+fn create_changes_table(changes: &[&Vec<Change>]) -> String {
+    let mut changes: Vec<&Change> = changes.iter().flat_map(|c| c.iter()).collect();
+    changes.sort_by_key(|c| c.first_after);
+    let rows: String = if changes.is_empty() {
+        String::from("<tr><td colspan=\"5\">no changes detected</td></tr>")
+    } else {
+        changes
+            .iter()
+            .map(|c| {
+                format!(
+                    "<tr>\
+                       <td>{}</td>\
+                       <td class=\"ts\">{}</td>\
+                       <td>{:.1}</td>\
+                       <td>{:.1}</td>\
+                       <td>{:+.1}%</td>\
+                     </tr>",
+                    c.metric,
+                    c.first_after.format(DATE_TIME_FORMAT),
+                    c.median_before,
+                    c.median_after,
+                    c.change_percent
+                )
+            })
+            .collect()
+    };
+    format!(
+        "<table id=\"changes\">\
+              <tr>\
+                <th>chart</th>\
+                <th>since</th>\
+                <th>median before</th>\
+                <th>median after</th>\
+                <th>change</th>\
+              </tr>\
+              {}\
+         </table>",
+        rows
+    )
+}
+
+fn create_outages_table(report: &OutageReport) -> String {
+    let rows: String = report
+        .outages
+        .iter()
+        .map(|outage| {
+            format!(
+                "<tr>\
+                   <td class=\"ts\">{}</td>\
+                   <td class=\"ts\">{}{}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                 </tr>",
+                outage.start.format(DATE_TIME_FORMAT),
+                outage.end.format(DATE_TIME_FORMAT),
+                if outage.ongoing { " (ongoing)" } else { "" },
+                format_duration(outage.duration_minutes),
+                outage
+                    .causes
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        })
+        .collect();
+    format!(
+        "<table id=\"outages\">\
+              <tr>\
+                <th>start</th>\
+                <th>end</th>\
+                <th>duration</th>\
+                <th>cause</th>\
+              </tr>\
+              {}\
+              <tr>\
+                <th colspan=\"4\">{} outages, downtime {}, longest {}</th>\
+              </tr>\
+         </table>",
+        rows,
+        report.count,
+        format_duration(report.total_minutes),
+        format_duration(report.longest_minutes)
+    )
+}
+
+/// verdict, criteria and the used and excluded measurements of the campaign
+fn create_campaign_section(report: Option<&CampaignReport>) -> String {
+    let report = match report {
+        Some(r) => r,
+        None => return String::from("<p>no campaign configured</p>"),
+    };
+    let days: Vec<String> = report.days.iter().map(|d| d.to_string()).collect();
+    let criteria: String = report
+        .criteria
+        .iter()
+        .map(|c| {
+            format!(
+                "<tr>\
+                   <td>{} {}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                   <td>{}</td>\
+                 </tr>",
+                c.metric,
+                c.speed,
+                c.contract_value,
+                c.rule,
+                c.result,
+                if c.met { "met" } else { "not met" }
+            )
+        })
+        .collect();
+    let used: String = report
+        .used
+        .iter()
+        .map(|m| {
+            format!(
+                "<tr>\
+                   <td class=\"ts\">{}</td>\
+                   <td>{:.1}</td>\
+                   <td>{:.1}</td>\
+                 </tr>",
+                m.timestamp.format(DATE_TIME_FORMAT),
+                m.download,
+                m.upload
+            )
+        })
+        .collect();
+    let excluded: String = report
+        .excluded
+        .iter()
+        .map(|e| {
+            format!(
+                "<tr>\
+                   <td class=\"ts\">{}</td>\
+                   <td>{}</td>\
+                 </tr>",
+                e.timestamp.format(DATE_TIME_FORMAT),
+                e.reason
+            )
+        })
+        .collect();
+    format!(
+        "<p class=\"campaign_{}\">{}: {}</p>\
+         <p>measurement days: {}</p>\
+         <table id=\"campaign\">\
+              <tr>\
+                <th>speed</th>\
+                <th>contract (Mbit/s)</th>\
+                <th>rule</th>\
+                <th>result</th>\
+                <th></th>\
+              </tr>\
+              {}\
+         </table>\
+         <table id=\"campaign_used\">\
+              <tr>\
+                <th>used measurement</th>\
+                <th>download (Mbit/s)</th>\
+                <th>upload (Mbit/s)</th>\
+              </tr>\
+              {}\
+         </table>\
+         <table id=\"campaign_excluded\">\
+              <tr>\
+                <th>excluded entry</th>\
+                <th>reason</th>\
+              </tr>\
+              {}\
+         </table>",
+        report.status,
+        report.status,
+        report.message,
+        days.join(", "),
+        criteria,
+        used,
+        excluded
+    )
+}
+
+fn create_statistic_line<N: Copy>(chart_config: &ChartConfig<N>, chart: &Chart<N>) -> String {
+    let compliance = &chart.compliance;
+    let expected = match &compliance.expected {
+        Some(e) => format!(
+            ", {}, {} {} of {} days{}",
+            e.reached
+                .iter()
+                .map(|(level, share)| {
+                    format!(
+                        "{} {}% = {}",
+                        STATISTIC_REACHED,
+                        level,
+                        format_share(*share, e.compared)
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
+            STATISTIC_DAYS_MET,
+            e.days_met,
+            e.days,
+            if e.tiers.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    ", {} {}",
+                    STATISTIC_TIERS,
+                    format_tiers(&e.tiers, e.compared)
+                )
+            }
+        ),
+        None => String::new(),
+    };
+    let statistics: Vec<String> = chart_config
+        .statistics
+        .iter()
+        .map(|s| format!("{} = {}", s, format_statistic(&chart.summary, *s)))
+        .collect();
+    format!(
+        "{}: {}, {} = {}{}\n",
         chart_config.label,
-        STATISTIC_MEDIAN,
-        STATISTIC_AVG,
-        STATISTIC_STD,
-        chart.median,
-        chart.average,
-        chart.standard_deviation
+        statistics.join(", "),
+        STATISTIC_AVAILABILITY,
+        format_share(compliance.availability(), compliance.runs),
+        expected
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a point every 10 minutes, the failed runs have no value
+    fn series(len: usize, failed: impl Fn(usize) -> bool) -> Vec<Point<f64>> {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 1)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .unwrap();
+        (0..len)
+            .map(|i| Point {
+                x: (start + Duration::minutes(10 * i as i64))
+                    .format(DATE_TIME_FORMAT)
+                    .to_string(),
+                y: if failed(i) { None } else { Some(i as f64) },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn downsampling_a_gap_heavy_series_keeps_the_maximum_points() {
+        let points = downsample_points(series(3000, |i| i % 3 == 0), 100);
+        let gaps = points.iter().filter(|p| p.y.is_none()).count();
+        assert!(points.len() <= 100, "{} points", points.len());
+        assert!(gaps > 0 && gaps <= (100 - DOWNSAMPLING_MIN_POINTS) / 2);
+        assert!(points.len() - gaps >= 50);
+    }
+
+    #[test]
+    fn downsampling_keeps_the_longest_gaps() {
+        // one failed run every 10 points, and 20 failed runs in a row from 500:
+        let points =
+            downsample_points(series(1000, |i| i % 10 == 0 || (500..520).contains(&i)), 20);
+        assert!(points.len() <= 20, "{} points", points.len());
+        let long_gap = points
+            .windows(3)
+            .any(|w| w[1].y.is_none() && w[0].y < Some(500.0) && w[2].y >= Some(520.0));
+        assert!(long_gap);
+    }
+
+    /// a run on the day of october 2026 at the time, without download if it failed
+    fn entry(day: u32, time: &str, download: Option<f64>) -> ParsedEntry {
+        let performance = match download {
+            Some(d) => format!(
+                r#","performance":{{"latency":10,"download":{:?}}}"#,
+                d * MEGA_BIT_FACTOR
+            ),
+            None => String::new(),
+        };
+        crate::json_parser::JsonParser::parse(&format!(
+            r#"{{"timestamp":"2026-10-{:02} {}"{}}}"#,
+            day, time, performance
+        ))
+        .unwrap()
+    }
+
+    /// (x, y) of the points of a dataset
+    type Xy = Vec<(String, Option<f64>)>;
+
+    /// the points of the rollup and of its max and min band
+    fn rollup(data: &[ParsedEntry], period: RollupPeriod) -> (Xy, Xy, Xy) {
+        let config: ChartConfig<f64> = toml::from_str(
+            "label = 'download'\nfill = false\nborder_color = 'green'\ndefault_value = 0.0\n",
+        )
+        .unwrap();
+        let (points, band) = create_rollup_points(&config, data, 0.0, period, |p| {
+            p.download.map(|v| v / MEGA_BIT_FACTOR)
+        });
+        let xy =
+            |points: &[Point<f64>]| -> Xy { points.iter().map(|p| (p.x.clone(), p.y)).collect() };
+        let band_points = |suffix: &str| {
+            band.iter()
+                .find(|ds| ds.label == format!("download {}", suffix))
+                .map(|ds| xy(&ds.data))
+                .unwrap_or_default()
+        };
+        (
+            xy(&points),
+            band_points(ROLLUP_MAX_LABEL),
+            band_points(ROLLUP_MIN_LABEL),
+        )
+    }
+
+    fn at_midnight(day: u32, y: f64) -> (String, Option<f64>) {
+        (format!("2026-10-{:02} 00:00:00", day), Some(y))
+    }
+
+    #[test]
+    fn rollups_are_the_median_of_a_day_or_week_with_a_min_max_band() {
+        // 2026-10-12 and 2026-10-19 are mondays:
+        let data = [
+            entry(11, "23:50:00", Some(100.0)),
+            entry(12, "00:10:00", Some(200.0)),
+            entry(12, "12:00:00", Some(300.0)),
+            entry(12, "23:59:00", Some(100.0)),
+            entry(13, "08:00:00", None),
+            entry(18, "23:00:00", Some(400.0)),
+            entry(19, "00:00:00", Some(50.0)),
+        ];
+        let (points, max, min) = rollup(&data, RollupPeriod::Daily);
+        assert_eq!(
+            points,
+            vec![
+                at_midnight(11, 100.0),
+                at_midnight(12, 200.0),
+                // a day with failed runs only is a gap:
+                (String::from("2026-10-13 00:00:00"), None),
+                at_midnight(18, 400.0),
+                at_midnight(19, 50.0),
+            ]
+        );
+        assert_eq!(max[1], at_midnight(12, 300.0));
+        assert_eq!(min[1], at_midnight(12, 100.0));
+        assert_eq!(max.len(), 4);
+
+        let (points, max, min) = rollup(&data, RollupPeriod::Weekly);
+        assert_eq!(
+            points,
+            vec![
+                at_midnight(5, 100.0),
+                at_midnight(12, 250.0),
+                at_midnight(19, 50.0)
+            ]
+        );
+        assert_eq!(
+            max,
+            vec![
+                at_midnight(5, 100.0),
+                at_midnight(12, 400.0),
+                at_midnight(19, 50.0)
+            ]
+        );
+        assert_eq!(min[1], at_midnight(12, 100.0));
+    }
+
+    #[test]
+    fn downsampling_keeps_all_gaps_within_the_budget() {
+        let points = downsample_points(series(1000, |i| i % 100 == 50), 100);
+        assert!(points.len() <= 100);
+        assert_eq!(points.iter().filter(|p| p.y.is_none()).count(), 10);
+    }
+}
@@ -0,0 +1,263 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use faccess::PathExt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::SpeedTrackerError;
+use crate::file_access_problems;
+use crate::file_locator::*;
+use crate::lib_constants::*;
+use crate::path_access_problems;
+
+/// default template, the same as in 'pi_files'
+const DEFAULT_TEMPLATE: &str = include_str!("../../pi_files/template.html");
+/// default template of 'compare', the same as in 'pi_files'
+const DEFAULT_COMPARE_TEMPLATE: &str = include_str!("../../pi_files/compare_template.html");
+/// commented config, written with the values chosen in 'init'
+const DEFAULT_CONFIG: &str = include_str!("../../pi_files/speedtracker.toml");
+
+/// everything 'init' needs to know to write a working installation
+#[derive(Debug)]
+pub struct InitOptions {
+    /// directory to write speedtracker.toml and the templates to
+    pub config_dir: PathBuf,
+    /// directory where data is collected
+    pub data_dir: PathBuf,
+    /// should be on a path served by a webserver (apache e.g.)
+    pub output_file: PathBuf,
+    /// in megabits per second
+    pub expected_download: f64,
+    /// in megabits per second
+    pub expected_upload: f64,
+    /// interval of the cron job in minutes
+    pub run_interval_minutes: u32,
+    /// overwrite existing files
+    pub force: bool,
+}
+
+impl InitOptions {
+    /// proposals for a new installation with config and template in config_dir
+    pub fn defaults(config_dir: PathBuf) -> InitOptions {
+        InitOptions {
+            data_dir: config_dir.join(DEFAULT_INIT_DATA_DIR),
+            output_file: PathBuf::from(DEFAULT_INIT_OUTPUT_FILE),
+            expected_download: DEFAULT_EXPECTED_DOWNLOAD_VALUE,
+            expected_upload: DEFAULT_EXPECTED_UPLOAD_VALUE,
+            run_interval_minutes: DEFAULT_RUN_INTERVAL_MINUTES,
+            force: false,
+            config_dir,
+        }
+    }
+}
+
+/// write config and template, check the installation.
+/// returns the problems found, the files are written anyway
+pub fn init_installation(
+    options: &InitOptions,
+    locator: &FileLocator,
+) -> Result<Vec<String>, SpeedTrackerError> {
+    if cron_line(options.run_interval_minutes, Path::new("")).is_none() {
+        return Err(SpeedTrackerError::Config(format!(
+            "a run interval of {} minutes can not be scheduled by cron",
+            options.run_interval_minutes
+        )));
+    }
+    create_dir(&options.config_dir)?;
+    let config_file = options.config_dir.join(CONFIG_FILENAME);
+    let template_file = options.config_dir.join(TEMPLATE_FILENAME);
+    let compare_template_file = options.config_dir.join(COMPARE_TEMPLATE_FILENAME);
+    for file in [&config_file, &template_file, &compare_template_file] {
+        if file.exists() && !options.force {
+            return Err(SpeedTrackerError::Config(format!(
+                "{:?} exists already, use --force to overwrite it",
+                file
+            )));
+        }
+    }
+    create_dir(&options.data_dir)?;
+
+    write_file(&config_file, &commented_config(options))?;
+    write_file(&template_file, DEFAULT_TEMPLATE)?;
+    write_file(&compare_template_file, DEFAULT_COMPARE_TEMPLATE)?;
+
+    let mut problems = path_access_problems(&options.data_dir);
+    problems.extend(file_access_problems(&options.output_file));
+    match locator.locate(LocatedFile::SpeedTestCmd) {
+        Ok(cmd) if !cmd.executable() => {
+            problems.push(format!("{:?} is not executable (chmod +x)", cmd))
+        }
+        Ok(_) => (),
+        Err(searched) => problems.push(format!(
+            "{} not found (compile SpeedTest and copy it next to speedtracker), {}",
+            SPEED_TEST_CMD,
+            format_searched(&searched)
+        )),
+    }
+    Ok(problems)
+}
+
+fn create_dir(dir: &Path) -> Result<(), SpeedTrackerError> {
+    fs::create_dir_all(dir).map_err(SpeedTrackerError::io(format!("Could not create {:?}", dir)))
+}
+
+fn write_file(file: &Path, content: &str) -> Result<(), SpeedTrackerError> {
+    fs::write(file, content)
+        .map_err(SpeedTrackerError::io(format!("Could not write {:?}", file)))?;
+    println!("written {}", file.display());
+    Ok(())
+}
+
+/// crontab line for the new installation, '--config' is added if the config
+/// is not stored next to the program
+pub fn init_cron_line(options: &InitOptions, locator: &FileLocator) -> String {
+    let mut command = format!(
+        "{} run",
+        locator.program_dir().join(PROGRAM_DIR_NAME).display()
+    );
+    if options.config_dir != locator.program_dir() {
+        let config_file = options.config_dir.join(CONFIG_FILENAME);
+        command = format!("{} --config {}", command, config_file.display());
+    }
+    // the interval is checked by init_installation:
+    cron_line(options.run_interval_minutes, Path::new(&command)).unwrap_or_default()
+}
+
+/// crontab line to run mode 1 every run_interval_minutes,
+/// none if cron can not express the interval
+pub fn cron_line(run_interval_minutes: u32, command: &Path) -> Option<String> {
+    let schedule = match run_interval_minutes {
+        m if m > 0 && m < 60 && 60 % m == 0 => format!("*/{} * * * *", m),
+        60 => String::from("0 * * * *"),
+        m if m > 60 && m % 60 == 0 && 24 % (m / 60) == 0 => format!("0 */{} * * *", m / 60),
+        _ => return None,
+    };
+    Some(format!("{} {}", schedule, command.display()))
+}
+
+/// the config of 'pi_files' with the paths and values chosen for this installation
+fn commented_config(options: &InitOptions) -> String {
+    // (table, key, value) of the lines to replace:
+    let values: [(&str, &str, String); 6] = [
+        ("", "data_dir", toml_string(&options.data_dir)),
+        ("", "output_file", toml_string(&options.output_file)),
+        (
+            "",
+            "log_file",
+            toml_string(&options.config_dir.join(LOG_FILENAME)),
+        ),
+        (
+            "",
+            "run_interval_minutes",
+            options.run_interval_minutes.to_string(),
+        ),
+        (
+            "download_chart.expected_value",
+            "value",
+            format!("{:?}", options.expected_download),
+        ),
+        (
+            "upload_chart.expected_value",
+            "value",
+            format!("{:?}", options.expected_upload),
+        ),
+    ];
+    let mut table = "";
+    let mut rs = String::from("# speedtracker config, written by 'speedtracker init'\n");
+    for line in DEFAULT_CONFIG.lines() {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            table = name;
+        }
+        let replaced = line.split_once(" = ").and_then(|(key, _)| {
+            values
+                .iter()
+                .find(|(t, k, _)| *t == table && *k == key)
+                .map(|(_, k, value)| format!("{} = {}", k, value))
+        });
+        rs.push_str(replaced.as_deref().unwrap_or(line));
+        rs.push('\n');
+    }
+    rs
+}
+
+/// path as toml literal string like the rest of the config, or as escaped basic string
+/// if a literal string can not hold it (a ' or a control character)
+fn toml_string(path: &Path) -> String {
+    let path = path.display().to_string();
+    if path.contains('\'') || path.chars().any(char::is_control) {
+        toml::Value::String(path).to_string()
+    } else {
+        format!("'{}'", path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_is_the_sample_config_with_the_chosen_values() {
+        let mut options = InitOptions::defaults(PathBuf::from("/home/pi/speedtracker"));
+        options.expected_download = 100.0;
+        options.run_interval_minutes = 30;
+        let config = commented_config(&options);
+        assert_eq!(config.lines().count(), DEFAULT_CONFIG.lines().count() + 1);
+        let value: toml::Value = toml::from_str(&config).unwrap();
+        assert_eq!(
+            value["data_dir"].as_str(),
+            Some("/home/pi/speedtracker/data")
+        );
+        assert_eq!(
+            value["log_file"].as_str(),
+            Some("/home/pi/speedtracker/speedtracker.log")
+        );
+        assert_eq!(value["run_interval_minutes"].as_integer(), Some(30));
+        assert_eq!(
+            value["download_chart"]["expected_value"]["value"].as_float(),
+            Some(100.0)
+        );
+        assert_eq!(
+            value["upload_chart"]["expected_value"]["value"].as_float(),
+            Some(DEFAULT_EXPECTED_UPLOAD_VALUE)
+        );
+        // everything else is unchanged:
+        assert_eq!(value["output_xdays"].as_integer(), Some(14));
+        assert_eq!(
+            value["download_chart"]["default_value"].as_float(),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn paths_with_quotes_are_escaped() {
+        let mut options = InitOptions::defaults(PathBuf::from("/home/o'brien"));
+        options.output_file = PathBuf::from(r#"/var/www/"speed" \ o'brien.html"#);
+        let value: toml::Value = toml::from_str(&commented_config(&options)).unwrap();
+        assert_eq!(value["data_dir"].as_str(), Some("/home/o'brien/data"));
+        assert_eq!(
+            value["output_file"].as_str(),
+            Some(r#"/var/www/"speed" \ o'brien.html"#)
+        );
+    }
+}
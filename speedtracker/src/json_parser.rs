@@ -22,15 +22,18 @@
 //
 
 use crate::lib_constants::*;
+use crate::runner::Backend;
 
 use chrono::naive::NaiveDateTime;
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Error;
+use std::fmt;
 
 //format as produced by cmd speedtestJson
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Client {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub wlan: Option<String>,
     pub ip: String,
     pub lat: String,
@@ -38,7 +41,7 @@ pub struct Client {
     pub isp: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Server {
     pub name: String,
     pub sponsor: String,
@@ -46,17 +49,77 @@ pub struct Server {
     pub host: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Performance {
-    pub latency: u32,
+    /// missing if the backend measured no round trip time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub jitter: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub download_config: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub upload_config: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub download: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub upload: Option<f64>,
 }
 
+//format of the run record added by speedtracker
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunStatus {
+    /// speed test run and wrote a result
+    Ok,
+    /// speed test run but reported an error, e.g. no server found
+    NoServer,
+    /// speed test returned with an exit code other than 0 or wrote no result
+    Crashed,
+    /// speed test could not be started
+    SpawnFailed,
+    /// result could not be written to the data file
+    WriteFailed,
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RunStatus::Ok => "ok",
+            RunStatus::NoServer => "no-server",
+            RunStatus::Crashed => "crashed",
+            RunStatus::SpawnFailed => "spawn-failed",
+            RunStatus::WriteFailed => "write-failed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunInfo {
+    pub status: RunStatus,
+    /// missing in data written before other speed tests than speedtestJson could be run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<Backend>,
+    #[serde(
+        deserialize_with = "naive_date_time_from_str",
+        serialize_with = "naive_date_time_to_str"
+    )]
+    pub start: NaiveDateTime,
+    #[serde(
+        deserialize_with = "naive_date_time_from_str",
+        serialize_with = "naive_date_time_to_str"
+    )]
+    pub stop: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// first characters of the error output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ParsedEntry {
     #[serde(deserialize_with = "naive_date_time_from_str")]
@@ -65,6 +128,15 @@ pub struct ParsedEntry {
     pub client: Option<Client>,
     pub server: Option<Server>,
     pub performance: Option<Performance>,
+    /// missing in data written before failed runs were recorded
+    pub run: Option<RunInfo>,
+}
+
+impl ParsedEntry {
+    /// a record of a run that did not measure anything
+    pub fn is_failed_run(&self) -> bool {
+        self.performance.is_none() && matches!(&self.run, Some(r) if r.status != RunStatus::Ok)
+    }
 }
 
 pub struct JsonParser {}
@@ -81,3 +153,13 @@ where
     let s: String = Deserialize::deserialize(deserializer)?;
     NaiveDateTime::parse_from_str(&s, DATE_TIME_FORMAT).map_err(de::Error::custom)
 }
+
+pub(crate) fn naive_date_time_to_str<S>(
+    date_time: &NaiveDateTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&date_time.format(DATE_TIME_FORMAT).to_string())
+}
@@ -22,32 +22,65 @@
 //
 
 use chrono::NaiveDate;
-use chrono::{Duration, Local};
-use confy::ConfyError;
+use chrono::{Duration, Local, NaiveDateTime};
 use faccess::PathExt;
 use flexi_logger::*;
 use log::*;
 use serde::{Deserialize, Serialize};
 
-use std::error::Error;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
+use std::io::ErrorKind;
 use std::io::{prelude::*, BufReader, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use std::process::Command;
 
+use crate::aggregation::aggregate;
+pub use crate::campaign::CampaignStatus;
+use crate::campaign::{evaluate_campaign, CampaignConfig, Contract};
+use crate::changes::ChangeSettings;
 use crate::chart_config::*;
-use crate::html_generator::HtmlGenerator;
+use crate::compare::{compare, Comparison, Period};
+use crate::config_layers::*;
+pub use crate::config_validator::{validate_config_file, ConfigProblem, Severity};
+pub use crate::doctor::{run_doctor, CheckStatus, DoctorCheck};
+pub use crate::error::SpeedTrackerError;
+pub use crate::file_locator::{format_searched, FileLocator, LocatedFile};
+use crate::html_generator::{HtmlGenerator, Reports};
+pub use crate::init::{init_cron_line, init_installation, InitOptions};
 use crate::json_parser::JsonParser;
-use crate::json_parser::ParsedEntry;
+use crate::json_parser::{ParsedEntry, RunInfo, RunStatus};
 use crate::lib_constants::*;
+use crate::outages::{detect_outages, format_duration, OutageReport};
+use crate::runner::{speed_test_runner, Backend, RunnerConfig, SpeedTestRunner};
 
+mod aggregation;
+mod anomalies;
+mod campaign;
+mod changes;
 mod chart_config;
+mod compare;
+mod compliance;
+mod config_layers;
+mod config_validator;
+mod doctor;
+mod downsampling;
+mod error;
+mod file_locator;
+mod forecast;
 mod html_generator;
+mod init;
 mod json_parser;
 mod lib_constants;
+mod outages;
+mod runner;
+mod statistics;
+mod trends;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     /// directory where data is collected
     data_dir: String,
@@ -59,6 +92,22 @@ pub struct Config {
     log_file: String,
     /// maximal size of log file in kb before the file is rotated
     log_file_max_length_in_kb: u64,
+    /// interval of the cron job running mode 1 in minutes
+    run_interval_minutes: u32,
+    /// no entry for more than this number of run intervals is an outage
+    outage_missed_intervals: u32,
+    /// draw daily or weekly min / median / max instead of every run
+    rollup: Rollup,
+    /// with rollup 'auto' time ranges longer than this are drawn as daily rollups
+    rollup_daily_after_days: u32,
+    /// with rollup 'auto' time ranges longer than this are drawn as weekly rollups
+    rollup_weekly_after_days: u32,
+    /// maximal number of points of a dataset, longer series are downsampled, 0 draws every point
+    max_points: usize,
+    /// samples needed before and after a change of the level of a chart
+    change_min_samples: u32,
+    /// medians before and after a change differ by at least this percentage
+    change_min_shift_percent: f64,
     /// latency_chart configuration
     latency_chart: ChartConfig<u32>,
     /// jitter_chart configuration
@@ -67,6 +116,10 @@ pub struct Config {
     download_chart: ChartConfig<f64>,
     /// upload_chart configuration
     upload_chart: ChartConfig<f64>,
+    /// tiers with the contract speeds for a measurement campaign
+    campaign: Option<CampaignConfig>,
+    /// speed test run in mode 1
+    runner: RunnerConfig,
 }
 
 #[derive(Debug)]
@@ -85,19 +138,19 @@ pub struct Setup {
     to_date: NaiveDate,
     /// should be on a path served by a webserver (apache e.g.)
     output_file: String,
-    /// different to the standard working dir, it is the same
-    /// dir the program is stored. so the program can be called
-    /// from anywhere and read the config an template file always
-    /// from the same place
-    working_dir: String,
-    /// latency_chart configuration
-    latency_chart: ChartConfig<u32>,
-    /// jitter_chart configuration
-    jitter_chart: ChartConfig<u32>,
-    /// download_chart configuration
-    download_chart: ChartConfig<f64>,
-    /// upload_chart configuration
-    upload_chart: ChartConfig<f64>,
+    /// finds template file and speed test command,
+    /// so the program can be called from anywhere
+    locator: FileLocator,
+    /// interval of the cron job running mode 1 in minutes
+    run_interval_minutes: u32,
+    /// no entry for more than this number of run intervals is an outage
+    outage_missed_intervals: u32,
+    /// configuration of all charts
+    charts: ChartConfigs,
+    /// contract speeds for a measurement campaign, or why they are not found in the tiers
+    campaign: Option<Result<Contract, String>>,
+    /// speed test run in mode 1
+    runner: RunnerConfig,
 }
 
 impl Setup {
@@ -105,40 +158,386 @@ impl Setup {
     /// or do nothing  in mode 2
     pub fn maybe_speed_test(&self) {
         if let Some(f) = &self.new_data_file {
-            run_speed_test(Path::new(&self.working_dir), Path::new(&f));
+            let runner = speed_test_runner(&self.runner);
+            match self.runner.program(&self.locator) {
+                Ok(program) => run_speed_test(runner.as_ref(), &program, Path::new(&f)),
+                Err(msg) => {
+                    let now = Local::now().naive_local();
+                    let run = run_info(
+                        self.runner.backend,
+                        RunStatus::SpawnFailed,
+                        now,
+                        now,
+                        None,
+                        &msg,
+                    );
+                    record_run(Path::new(&f), run, None);
+                }
+            }
         }
     }
-    /// parse data in specific time range
-    pub fn read_data(&self) -> Vec<ParsedEntry> {
-        let maybe_file_list = read_data_file_paths(
+    /// parse data in specific time range, sorted by timestamp
+    /// (imports append older entries to the end of a data file)
+    pub fn read_data(&self) -> Result<Vec<ParsedEntry>, SpeedTrackerError> {
+        self.read_data_from(self.from_date, &self.first_filter_file_name)
+    }
+    /// parse the data the usual values of the hours of the week are taken from:
+    /// the ANOMALY_BASELINE_WEEKS weeks up to the end of the time range or the whole time range if longer
+    pub fn read_baseline_data(&self) -> Result<Vec<ParsedEntry>, SpeedTrackerError> {
+        let from = self
+            .from_date
+            .min(self.to_date - Duration::weeks(ANOMALY_BASELINE_WEEKS));
+        self.read_data_from(from, &get_data_file_name(&from))
+    }
+    /// parse the data from the date to the end of the time range, sorted by timestamp
+    fn read_data_from(
+        &self,
+        from_date: NaiveDate,
+        first_filter_file_name: &str,
+    ) -> Result<Vec<ParsedEntry>, SpeedTrackerError> {
+        let mut rs: Vec<ParsedEntry> = Vec::new();
+        let paths = read_data_file_paths(
             Path::new(&self.data_dir),
-            &self.first_filter_file_name,
+            first_filter_file_name,
             &self.last_filter_file_name,
+        )?;
+        for file in paths {
+            rs.extend(parse_output_file(
+                Path::new(&file),
+                &from_date,
+                &self.to_date,
+            )?);
+        }
+        rs.sort_by_key(|e| e.timestamp);
+        Ok(rs)
+    }
+    /// write the stored json lines of the specific time range unchanged to the writer,
+    /// returns the number of exported lines
+    pub fn export_data(&self, writer: &mut dyn Write) -> Result<usize, SpeedTrackerError> {
+        let mut count: usize = 0;
+        for file in self.read_data_file_paths()? {
+            let lines = read_output_file(Path::new(&file), &self.from_date, &self.to_date)?;
+            for (line, _) in lines {
+                writeln!(writer, "{}", line).map_err(SpeedTrackerError::io("Export failed"))?;
+                count += 1;
+            }
+        }
+        writer
+            .flush()
+            .map_err(SpeedTrackerError::io("Export failed"))?;
+        Ok(count)
+    }
+    /// write the aggregation by hour of day and weekday x hour as json, returns the number of entries
+    pub fn export_aggregation(&self, writer: &mut dyn Write) -> Result<usize, SpeedTrackerError> {
+        let data = self.read_data()?;
+        serde_json::to_writer_pretty(&mut *writer, &aggregate(&data))
+            .map_err(|e| SpeedTrackerError::Io(String::from("Export failed"), e.into()))?;
+        writeln!(writer)
+            .and_then(|_| writer.flush())
+            .map_err(SpeedTrackerError::io("Export failed"))?;
+        Ok(data.len())
+    }
+    /// print the configured statistics of all charts to the console
+    pub fn print_statistics(&self, data: &[ParsedEntry]) {
+        println!(
+            "from {} to {}: {} entries, {} failed runs",
+            self.from_date,
+            self.to_date,
+            data.len(),
+            data.iter().filter(|d| d.is_failed_run()).count()
         );
-        if let Some(file_list) = maybe_file_list {
-            file_list
-                .iter()
-                .flat_map(|file| {
-                    parse_output_file(Path::new(&file), &self.from_date, &self.to_date)
-                })
-                .flatten()
-                .collect()
-        } else {
-            Vec::new()
+        print!("{}", HtmlGenerator::statistics_text(data, &self.charts));
+    }
+    /// find the outages in the data
+    pub fn outage_report(&self, data: &[ParsedEntry]) -> OutageReport {
+        detect_outages(
+            data,
+            self.range_end(),
+            self.run_interval_minutes,
+            self.outage_missed_intervals,
+        )
+    }
+    /// print the outages as table or as json to the console
+    pub fn print_outages(&self, data: &[ParsedEntry], as_json: bool) {
+        let report = self.outage_report(data);
+        if as_json {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            return;
+        }
+        println!("from {} to {}:", self.from_date, self.to_date);
+        for outage in &report.outages {
+            println!(
+                "{} - {}  {:>12}  {}{}",
+                outage.start.format(DATE_TIME_FORMAT),
+                outage.end.format(DATE_TIME_FORMAT),
+                format_duration(outage.duration_minutes),
+                outage
+                    .causes
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                if outage.ongoing { " (ongoing)" } else { "" }
+            );
+        }
+        println!(
+            "{} outages, downtime {}, longest {}",
+            report.count,
+            format_duration(report.total_minutes),
+            format_duration(report.longest_minutes)
+        );
+    }
+    /// write the anomalous measurements of all charts as json, returns the number of anomalies
+    pub fn export_anomalies(&self, writer: &mut dyn Write) -> Result<usize, SpeedTrackerError> {
+        let data = self.read_data()?;
+        let baseline = self.read_baseline_data()?;
+        let anomalies = HtmlGenerator::anomalies(&data, &baseline, &self.charts);
+        serde_json::to_writer_pretty(&mut *writer, &anomalies)
+            .map_err(|e| SpeedTrackerError::Io(String::from("Export failed"), e.into()))?;
+        writeln!(writer)
+            .and_then(|_| writer.flush())
+            .map_err(SpeedTrackerError::io("Export failed"))?;
+        Ok(anomalies.len())
+    }
+    /// print the anomalous measurements of all charts as table or as json to the console,
+    /// returns the number of anomalies
+    pub fn print_anomalies(
+        &self,
+        data: &[ParsedEntry],
+        as_json: bool,
+    ) -> Result<usize, SpeedTrackerError> {
+        let baseline = self.read_baseline_data()?;
+        let anomalies = HtmlGenerator::anomalies(data, &baseline, &self.charts);
+        if as_json {
+            println!("{}", serde_json::to_string_pretty(&anomalies).unwrap());
+            return Ok(anomalies.len());
+        }
+        println!("from {} to {}:", self.from_date, self.to_date);
+        for anomaly in &anomalies {
+            println!(
+                "{}  {:<12} {:>10.1} (usual {:.1}, score {:+.1})",
+                anomaly.timestamp.format(DATE_TIME_FORMAT),
+                anomaly.metric,
+                anomaly.value,
+                anomaly.baseline,
+                anomaly.score
+            );
+        }
+        println!("{} anomalies", anomalies.len());
+        Ok(anomalies.len())
+    }
+    /// print the changes of the level of all charts as table or as json to the console
+    pub fn print_changes(&self, data: &[ParsedEntry], as_json: bool) {
+        let changes = HtmlGenerator::changes(data, &self.charts);
+        if as_json {
+            println!("{}", serde_json::to_string_pretty(&changes).unwrap());
+            return;
+        }
+        println!("from {} to {}:", self.from_date, self.to_date);
+        for change in &changes {
+            println!(
+                "{} - {}  {:<12} {:>10.1} -> {:>10.1}  {:+.1}%",
+                change.last_before.format(DATE_TIME_FORMAT),
+                change.first_after.format(DATE_TIME_FORMAT),
+                change.metric,
+                change.median_before,
+                change.median_after,
+                change.change_percent
+            );
+        }
+        println!("{} changes", changes.len());
+    }
+    /// evaluate the measurement campaign and print the report as text or json
+    pub fn print_campaign(
+        &self,
+        data: &[ParsedEntry],
+        as_json: bool,
+    ) -> Result<CampaignStatus, SpeedTrackerError> {
+        let contract = match &self.campaign {
+            Some(Ok(contract)) => contract,
+            Some(Err(msg)) => return Err(SpeedTrackerError::Config(msg.clone())),
+            None => {
+                return Err(SpeedTrackerError::Config(String::from(
+                    "no [campaign] configured",
+                )))
+            }
+        };
+        let report = evaluate_campaign(data, contract);
+        if as_json {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            return Ok(report.status);
+        }
+        println!("{}: {}", report.status, report.message);
+        let days: Vec<String> = report.days.iter().map(|d| d.to_string()).collect();
+        println!("measurement days: {}", days.join(", "));
+        for c in &report.criteria {
+            println!(
+                "{} {} {} {} Mbit/s: {} -> {}",
+                if c.met { "met    " } else { "NOT MET" },
+                c.metric,
+                c.speed,
+                c.contract_value,
+                c.rule,
+                c.result
+            );
+        }
+        println!("used measurements:");
+        for m in &report.used {
+            println!(
+                "  {}  download {:.1} Mbit/s  upload {:.1} Mbit/s",
+                m.timestamp.format(DATE_TIME_FORMAT),
+                m.download,
+                m.upload
+            );
         }
+        println!("excluded entries:");
+        for e in &report.excluded {
+            println!("  {}  {}", e.timestamp.format(DATE_TIME_FORMAT), e.reason);
+        }
+        Ok(report.status)
     }
-    /// generate html file by transforming data and template:
-    pub fn generate_html(&self, data: &[ParsedEntry]) {
+    /// generate html file by transforming data and template,
+    /// an empty time range gives a page with a notice instead of the charts
+    pub fn generate_html(&self, data: &[ParsedEntry]) -> Result<(), SpeedTrackerError> {
+        let template_file = self
+            .locator
+            .locate(LocatedFile::Template)
+            .map_err(|searched| {
+                SpeedTrackerError::Template(
+                    PathBuf::from(TEMPLATE_FILENAME),
+                    std::io::Error::new(ErrorKind::NotFound, format_searched(&searched)),
+                )
+            })?;
+        let reports = Reports {
+            outages: self.outage_report(data),
+            campaign: match &self.campaign {
+                Some(Ok(contract)) => Some(evaluate_campaign(data, contract)),
+                Some(Err(msg)) => {
+                    warn!("campaign not evaluated: {}", msg);
+                    None
+                }
+                None => None,
+            },
+            aggregation: aggregate(data),
+        };
         HtmlGenerator::write_html(
             data,
-            &Path::new(&self.working_dir).join(TEMPLATE_FILENAME),
+            &self.read_baseline_data()?,
+            &reports,
+            &template_file,
             Path::new(&self.output_file),
-            &self.latency_chart,
-            &self.jitter_chart,
-            &self.download_chart,
-            &self.upload_chart,
-        );
+            &self.charts,
+        )
+    }
+    /// compare the data of this time range with the data of the time range of other
+    fn comparison(
+        &self,
+        data: &[ParsedEntry],
+        other: &Setup,
+        other_data: &[ParsedEntry],
+    ) -> Comparison {
+        compare(self.period(), data, other.period(), other_data)
+    }
+    /// print the comparison with the time range of other as table or as json to the console
+    pub fn print_comparison(
+        &self,
+        data: &[ParsedEntry],
+        other: &Setup,
+        other_data: &[ParsedEntry],
+        as_json: bool,
+    ) {
+        let comparison = self.comparison(data, other, other_data);
+        if as_json {
+            println!("{}", serde_json::to_string_pretty(&comparison).unwrap());
+            return;
+        }
+        println!("{} compared with {}:", comparison.second, comparison.first);
+        for m in &comparison.metrics {
+            let format_value = |v: Option<f64>| match v {
+                Some(v) => format!("{:.1}", v),
+                None => String::from(STATISTIC_NOT_AVAILABLE),
+            };
+            println!(
+                "{:<12} {:>10} -> {:>10} {:<7} {:>8}  p={:<8} {} (n={}/{})",
+                m.metric,
+                format_value(m.first_median),
+                format_value(m.second_median),
+                m.unit,
+                m.change_percent
+                    .map(|c| format!("{:+.1}%", c))
+                    .unwrap_or_else(|| String::from(STATISTIC_NOT_AVAILABLE)),
+                m.test
+                    .map(|t| format!("{:.4}", t.p_value))
+                    .unwrap_or_else(|| String::from(STATISTIC_NOT_AVAILABLE)),
+                m.verdict,
+                m.first_count,
+                m.second_count
+            );
+        }
+    }
+    /// generate html file comparing this time range with the time range of other
+    pub fn generate_comparison(
+        &self,
+        data: &[ParsedEntry],
+        other: &Setup,
+        other_data: &[ParsedEntry],
+        output_file: &Path,
+    ) -> Result<(), SpeedTrackerError> {
+        let template_file =
+            self.locator
+                .locate(LocatedFile::CompareTemplate)
+                .map_err(|searched| {
+                    SpeedTrackerError::Template(
+                        PathBuf::from(COMPARE_TEMPLATE_FILENAME),
+                        std::io::Error::new(ErrorKind::NotFound, format_searched(&searched)),
+                    )
+                })?;
+        HtmlGenerator::write_comparison(
+            data,
+            other_data,
+            &self.comparison(data, other, other_data),
+            &template_file,
+            output_file,
+            &self.charts,
+        )
+    }
+    /// error for an empty time range
+    pub fn no_data(&self) -> SpeedTrackerError {
+        SpeedTrackerError::NoData(self.from_date, self.to_date)
+    }
+    /// end of the time range: the end of to_date or now for the current period
+    fn range_end(&self) -> NaiveDateTime {
+        let end_of_day = (self.to_date + Duration::days(1))
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default();
+        end_of_day.min(Local::now().naive_local())
     }
+    /// time range of the setup
+    fn period(&self) -> Period {
+        Period {
+            from: self.from_date,
+            to: self.to_date,
+        }
+    }
+    /// paths of the data files that may contain entries of the time range
+    fn read_data_file_paths(&self) -> Result<Vec<String>, SpeedTrackerError> {
+        read_data_file_paths(
+            Path::new(&self.data_dir),
+            &self.first_filter_file_name,
+            &self.last_filter_file_name,
+        )
+    }
+}
+
+/// result of an import of json lines into the data files
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// lines appended to a data file
+    pub imported: usize,
+    /// lines skipped since an entry with the same timestamp exists already
+    pub duplicates: usize,
+    /// lines skipped since they could not be parsed
+    pub invalid: usize,
 }
 
 impl ::std::default::Default for Config {
@@ -149,56 +548,141 @@ impl ::std::default::Default for Config {
             output_xdays: DEFAULT_OUTPUT_XDAYS,
             log_file: String::from(DEFAULT_LOG_FILE),
             log_file_max_length_in_kb: DEFAULT_LOG_FILE_MAX_LENGTH_IN_KB,
+            run_interval_minutes: DEFAULT_RUN_INTERVAL_MINUTES,
+            outage_missed_intervals: DEFAULT_OUTAGE_MISSED_INTERVALS,
+            rollup: DEFAULT_ROLLUP,
+            rollup_daily_after_days: DEFAULT_ROLLUP_DAILY_AFTER_DAYS,
+            rollup_weekly_after_days: DEFAULT_ROLLUP_WEEKLY_AFTER_DAYS,
+            max_points: DEFAULT_MAX_POINTS,
+            change_min_samples: DEFAULT_CHANGE_MIN_SAMPLES,
+            change_min_shift_percent: DEFAULT_CHANGE_MIN_SHIFT_PERCENT,
             latency_chart: ChartConfig {
                 label: String::from(DEFAULT_LATENCY_LABEL),
                 fill: DEFAULT_FILL,
                 border_color: String::from(DEFAULT_LATENCY_COLOR),
                 default_value: DEFAULT_LATENCY_VALUE,
+                missing_values: DEFAULT_MISSING_VALUES,
+                statistics: DEFAULT_STATISTICS.to_vec(),
+                anomaly_threshold: DEFAULT_ANOMALY_THRESHOLD,
                 expected_value: None,
+                rolling_median: None,
+                ewma: None,
+                forecast: None,
             },
             jitter_chart: ChartConfig {
                 label: String::from(DEFAULT_JITTER_LABEL),
                 fill: DEFAULT_FILL,
                 border_color: String::from(DEFAULT_JITTER_COLOR),
                 default_value: DEFAULT_JITTER_VALUE,
+                missing_values: DEFAULT_MISSING_VALUES,
+                statistics: DEFAULT_STATISTICS.to_vec(),
+                anomaly_threshold: DEFAULT_ANOMALY_THRESHOLD,
                 expected_value: None,
+                rolling_median: None,
+                ewma: None,
+                forecast: None,
             },
             download_chart: ChartConfig {
                 label: String::from(DEFAULT_DOWNLOAD_LABEL),
                 fill: DEFAULT_FILL,
                 border_color: String::from(DEFAULT_DOWNLOAD_COLOR),
                 default_value: DEFAULT_DOWNLOAD_VALUE,
+                missing_values: DEFAULT_MISSING_VALUES,
+                statistics: DEFAULT_STATISTICS.to_vec(),
+                anomaly_threshold: DEFAULT_ANOMALY_THRESHOLD,
                 expected_value: Some(ExpectedConfig {
                     label: String::from(DEFAULT_EXPECTED_DOWNLOAD_LABEL),
                     fill: DEFAULT_FILL,
                     border_color: String::from(DEFAULT_EXPECTED_DOWNLOAD_COLOR),
-                    value: DEFAULT_EXPECTED_DOWNLOAD_VALUE,
+                    value: Some(DEFAULT_EXPECTED_DOWNLOAD_VALUE),
+                    tiers: Vec::new(),
+                    reference: None,
+                    history: Vec::new(),
                 }),
+                rolling_median: None,
+                ewma: None,
+                forecast: None,
             },
             upload_chart: ChartConfig {
                 label: String::from(DEFAULT_UPLOAD_LABEL),
                 fill: DEFAULT_FILL,
                 border_color: String::from(DEFAULT_UPLOAD_COLOR),
                 default_value: DEFAULT_UPLOAD_VALUE,
+                missing_values: DEFAULT_MISSING_VALUES,
+                statistics: DEFAULT_STATISTICS.to_vec(),
+                anomaly_threshold: DEFAULT_ANOMALY_THRESHOLD,
                 expected_value: Some(ExpectedConfig {
                     label: String::from(DEFAULT_EXPECTED_UPLOAD_LABEL),
                     fill: DEFAULT_FILL,
                     border_color: String::from(DEFAULT_EXPECTED_UPLOAD_COLOR),
-                    value: DEFAULT_EXPECTED_UPLOAD_VALUE,
+                    value: Some(DEFAULT_EXPECTED_UPLOAD_VALUE),
+                    tiers: Vec::new(),
+                    reference: None,
+                    history: Vec::new(),
                 }),
+                rolling_median: None,
+                ewma: None,
+                forecast: None,
+            },
+            campaign: None,
+            runner: RunnerConfig {
+                backend: DEFAULT_BACKEND,
+                command: String::new(),
+                server: String::new(),
+                args: Vec::new(),
             },
         }
     }
 }
 
-/// read config file (create if not exists).
-/// write example to console (if not exists).
-pub fn read_config(working_dir: &Path) -> Config {
-    let path = working_dir.join(CONFIG_FILENAME);
-    let maybe_config: Result<Config, ConfyError> = confy::load_path(&path);
-    match maybe_config {
-        Err(ex) => panic!("Abort {:?}", ex),
-        Ok(cfg) => cfg,
+/// find the config file, see FileLocator for the search order
+pub fn locate_config(locator: &FileLocator) -> Result<PathBuf, SpeedTrackerError> {
+    locator.locate(LocatedFile::Config).map_err(|searched| {
+        SpeedTrackerError::Config(format!(
+            "{} not found, {}",
+            CONFIG_FILENAME,
+            format_searched(&searched)
+        ))
+    })
+}
+
+/// read an existing config file, values are layered:
+/// defaults < config file < 'SPEEDTRACKER_*' environment variables < command line overrides.
+/// returns the config and the warnings of the ignored environment variables
+pub fn read_config_file(
+    path: &Path,
+    cli_overrides: &[String],
+) -> Result<(Config, Vec<String>), SpeedTrackerError> {
+    let config_error =
+        |e: &dyn std::fmt::Display| SpeedTrackerError::Config(format!("{:?}: {}", path, e));
+    let content = fs::read_to_string(path).map_err(|e| config_error(&e))?;
+    let file_value: toml::Value = toml::from_str(&content).map_err(|e| config_error(&e))?;
+    layer_config(file_value, cli_overrides).map_err(|e| match e {
+        SpeedTrackerError::Config(msg) => SpeedTrackerError::Config(format!("{:?}: {}", path, msg)),
+        other => other,
+    })
+}
+
+/// put the parsed config file between defaults and overrides,
+/// returns the config and the warnings of the ignored environment variables
+pub(crate) fn layer_config(
+    file_value: toml::Value,
+    cli_overrides: &[String],
+) -> Result<(Config, Vec<String>), SpeedTrackerError> {
+    let config_error = |e: &dyn std::fmt::Display| SpeedTrackerError::Config(e.to_string());
+    let mut value = toml::Value::try_from(Config::default()).map_err(|e| config_error(&e))?;
+    merge_file(&mut value, file_value);
+    let warnings = apply_env_overrides(&mut value, env::vars())?;
+    apply_cli_overrides(&mut value, cli_overrides)?;
+    let config = value.try_into().map_err(|e| config_error(&e))?;
+    Ok((config, warnings))
+}
+
+/// effective config in one line, values of sensitive keys are hidden
+pub fn config_for_log(config: &Config) -> String {
+    match toml::Value::try_from(config) {
+        Ok(value) => serde_json::to_string(&masked(&value)).unwrap_or_default(),
+        Err(e) => format!("config not printable: {}", e),
     }
 }
 
@@ -221,7 +705,10 @@ pub fn init_logger(config: &Config) {
 }
 
 /// transform config to setup to run it in mode 1
-pub fn config_to_setup_for_mode_1(working_dir: &Path, config: Config) -> Setup {
+pub fn config_to_setup_for_mode_1(
+    locator: &FileLocator,
+    config: Config,
+) -> Result<Setup, SpeedTrackerError> {
     //last data file name is:
     let now = chrono::Local::now();
     let today: NaiveDate = now.naive_local().date();
@@ -238,16 +725,20 @@ pub fn config_to_setup_for_mode_1(working_dir: &Path, config: Config) -> Setup {
     let past = now - Duration::days(config.output_xdays as i64);
     let pastday: NaiveDate = past.naive_local().date();
     let first_filter_file_name: String = get_data_file_name(&pastday);
+    let rollup = rollup_period(&config, pastday, today);
+    let campaign = campaign_contract(&config, today);
 
     //do some checks first:
     if !check_path_full_access(Path::new(&config.data_dir))
         | !check_file_full_access(Path::new(&new_data_file))
         | !check_file_full_access(Path::new(&config.output_file))
     {
-        panic!("PLEASE CORRECT CONFIG!");
+        return Err(SpeedTrackerError::Config(String::from(
+            "paths are not accessible, please correct config!",
+        )));
     }
 
-    Setup {
+    Ok(Setup {
         data_dir: config.data_dir,
         new_data_file: Some(new_data_file),
         first_filter_file_name,
@@ -255,52 +746,186 @@ pub fn config_to_setup_for_mode_1(working_dir: &Path, config: Config) -> Setup {
         from_date: pastday,
         to_date: today,
         output_file: config.output_file,
-        working_dir: working_dir.to_str().unwrap().to_string(),
-        latency_chart: config.latency_chart,
-        jitter_chart: config.jitter_chart,
-        download_chart: config.download_chart,
-        upload_chart: config.upload_chart,
-    }
+        locator: locator.clone(),
+        run_interval_minutes: config.run_interval_minutes,
+        outage_missed_intervals: config.outage_missed_intervals,
+        charts: ChartConfigs {
+            latency: config.latency_chart,
+            jitter: config.jitter_chart,
+            download: config.download_chart,
+            upload: config.upload_chart,
+            rollup,
+            max_points: config.max_points,
+            changes: ChangeSettings {
+                min_samples: config.change_min_samples as usize,
+                min_shift_percent: config.change_min_shift_percent,
+            },
+        },
+        campaign,
+        runner: config.runner,
+    })
 }
 
 /// transform config to setup to run it in mode 2
 pub fn config_to_setup_for_mode_2(
-    working_dir: &Path,
+    locator: &FileLocator,
     config: Config,
     from_date: &str,
     to_date: &str,
     output_file: &str,
-) -> Setup {
-    //note: console errors are ok here since it is used as console command:
-    let from_date_as_nd = NaiveDate::parse_from_str(from_date, DATE_FORMAT)
-        .unwrap_or_else(|_| panic!("Invalid date format: {} (e.g. 2022-01-15)", from_date));
-    let to_date_as_nd = NaiveDate::parse_from_str(to_date, DATE_FORMAT)
-        .unwrap_or_else(|_| panic!("Invalid date format: {} (e.g. 2022-01-15)", to_date));
+) -> Result<Setup, SpeedTrackerError> {
+    let mut setup = config_to_setup_for_range(locator, config, Some(from_date), Some(to_date))?;
+    setup.output_file = output_file.to_string();
+    Ok(setup)
+}
+
+/// transform config to setup to only read data in a time range.
+/// a missing from_date defaults to 'output_xdays' in the past, a missing to_date to today
+pub fn config_to_setup_for_range(
+    locator: &FileLocator,
+    config: Config,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+) -> Result<Setup, SpeedTrackerError> {
+    let today: NaiveDate = Local::now().naive_local().date();
+    let from_date_as_nd = match from_date {
+        Some(d) => parse_date(d)?,
+        None => today - Duration::days(config.output_xdays as i64),
+    };
+    let to_date_as_nd = match to_date {
+        Some(d) => parse_date(d)?,
+        None => today,
+    };
 
     if from_date_as_nd > to_date_as_nd {
-        panic!(
+        return Err(SpeedTrackerError::Parse(format!(
             "from_date > to_date,  {} > {} ",
             from_date_as_nd, to_date_as_nd
-        );
+        )));
     }
 
     let first_filter_file_name = get_data_file_name(&from_date_as_nd);
     let last_filter_file_name = get_data_file_name(&to_date_as_nd);
+    let rollup = rollup_period(&config, from_date_as_nd, to_date_as_nd);
+    let campaign = campaign_contract(&config, to_date_as_nd);
 
-    Setup {
+    Ok(Setup {
         data_dir: config.data_dir,
         new_data_file: None,
         first_filter_file_name,
         last_filter_file_name,
         from_date: from_date_as_nd,
         to_date: to_date_as_nd,
-        output_file: output_file.to_string(),
-        working_dir: working_dir.to_str().unwrap().to_string(),
-        latency_chart: config.latency_chart,
-        jitter_chart: config.jitter_chart,
-        download_chart: config.download_chart,
-        upload_chart: config.upload_chart,
+        output_file: config.output_file,
+        locator: locator.clone(),
+        run_interval_minutes: config.run_interval_minutes,
+        outage_missed_intervals: config.outage_missed_intervals,
+        charts: ChartConfigs {
+            latency: config.latency_chart,
+            jitter: config.jitter_chart,
+            download: config.download_chart,
+            upload: config.upload_chart,
+            rollup,
+            max_points: config.max_points,
+            changes: ChangeSettings {
+                min_samples: config.change_min_samples as usize,
+                min_shift_percent: config.change_min_shift_percent,
+            },
+        },
+        campaign,
+        runner: config.runner,
+    })
+}
+
+/// contract speeds of the configured campaign in force at the end of the time range,
+/// taken from the tiers of the charts
+fn campaign_contract(config: &Config, to: NaiveDate) -> Option<Result<Contract, String>> {
+    config
+        .campaign
+        .as_ref()
+        .map(|c| c.contract(&config.download_chart, &config.upload_chart, to))
+}
+
+/// rollup period for the time range from .. to (both included)
+fn rollup_period(config: &Config, from: NaiveDate, to: NaiveDate) -> Option<RollupPeriod> {
+    config.rollup.period(
+        (to - from).num_days() + 1,
+        config.rollup_daily_after_days,
+        config.rollup_weekly_after_days,
+    )
+}
+
+/// import json lines (e.g. from an export) into the data files of the matching month.
+/// entries with a timestamp already stored are skipped. use '-' to read from stdin
+pub fn import_data(config: &Config, input: &Path) -> Result<ImportSummary, SpeedTrackerError> {
+    let reader: Box<dyn BufRead> = if input == Path::new("-") {
+        Box::new(BufReader::new(std::io::stdin()))
+    } else {
+        let file = fs::File::open(input)
+            .map_err(SpeedTrackerError::io(format!("Could not open {:?}", input)))?;
+        Box::new(BufReader::new(file))
+    };
+    let data_dir = Path::new(&config.data_dir);
+    let mut known_timestamps: HashMap<String, HashSet<NaiveDateTime>> = HashMap::new();
+    let mut summary = ImportSummary::default();
+    for read_line in reader.lines() {
+        let line =
+            read_line.map_err(SpeedTrackerError::io(format!("Could not read {:?}", input)))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let entry = match JsonParser::parse(line) {
+            Ok(e) => e,
+            Err(e) => {
+                print_and_log_error(format!(
+                    "Could not parse json: '{}', message = '{}'",
+                    line, e
+                ));
+                summary.invalid += 1;
+                continue;
+            }
+        };
+        let file_name = get_data_file_name(&entry.timestamp.date());
+        let data_file = data_dir.join(&file_name);
+        let timestamps = match known_timestamps.entry(file_name) {
+            Entry::Occupied(o) => o.into_mut(),
+            Entry::Vacant(v) => {
+                let stored = if data_file.exists() {
+                    parse_output_file(&data_file, &NaiveDate::MIN, &NaiveDate::MAX)?
+                } else {
+                    Vec::new()
+                };
+                v.insert(stored.iter().map(|e| e.timestamp).collect())
+            }
+        };
+        if timestamps.insert(entry.timestamp) {
+            append_json_to_file(&data_file, &format!("{}\n", line)).map_err(
+                SpeedTrackerError::io(format!("Could not append to {:?}", data_file)),
+            )?;
+            summary.imported += 1;
+        } else {
+            summary.duplicates += 1;
+        }
     }
+    Ok(summary)
+}
+
+/// check that all paths of the config are accessible,
+/// problems are written to the console and the log
+pub fn check_config(config: &Config) -> bool {
+    let today: NaiveDate = Local::now().naive_local().date();
+    let new_data_file = Path::new(&config.data_dir).join(get_data_file_name(&today));
+    // no short circuit, report all problems:
+    check_path_full_access(Path::new(&config.data_dir))
+        & check_file_full_access(&new_data_file)
+        & check_file_full_access(Path::new(&config.output_file))
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, SpeedTrackerError> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT).map_err(|_| {
+        SpeedTrackerError::Parse(format!("Invalid date format: {} (e.g. 2022-01-15)", date))
+    })
 }
 
 //create a file name from a date
@@ -311,33 +936,45 @@ fn get_data_file_name(date: &NaiveDate) -> String {
 }
 
 fn check_path_full_access(path: &Path) -> bool {
+    report_problems(path_access_problems(path))
+}
+
+fn check_file_full_access(file: &Path) -> bool {
+    report_problems(file_access_problems(file))
+}
+
+/// reasons why a directory cannot be read or written
+pub(crate) fn path_access_problems(path: &Path) -> Vec<String> {
     if !path.exists() {
-        print_and_log_error(format!("Path does not exists: {:?}", path));
-        return false;
+        return vec![format!("Path does not exists: {:?}", path)];
     }
-    let mut rs = true;
+    let mut rs = Vec::new();
     if !path.readable() {
-        print_and_log_error(format!("No read permission on path: {:?}", path));
-        rs = false;
+        rs.push(format!("No read permission on path: {:?}", path));
     }
     if !path.writable() {
-        print_and_log_error(format!("No write permission on path: {:?}", path));
-        rs = false;
+        rs.push(format!("No write permission on path: {:?}", path));
     }
     rs
 }
 
-fn check_file_full_access(file: &Path) -> bool {
+/// reasons why a file cannot be created or written
+pub(crate) fn file_access_problems(file: &Path) -> Vec<String> {
     let dir = match file.parent() {
         Some(d) => d,
-        None => {
-            print_and_log_error(format!("File has an invalid path: {:?}", file));
-            return false;
-        }
+        None => return vec![format!("File has an invalid path: {:?}", file)],
     };
-    let rs = check_path_full_access(dir);
-    if rs && file.exists() && !file.writable() {
-        print_and_log_error(format!("No write permission for file: {:?}", file));
+    let mut rs = path_access_problems(dir);
+    if rs.is_empty() && file.exists() && !file.writable() {
+        rs.push(format!("No write permission for file: {:?}", file));
+    }
+    rs
+}
+
+fn report_problems(problems: Vec<String>) -> bool {
+    let rs = problems.is_empty();
+    for problem in problems {
+        print_and_log_error(problem);
     }
     rs
 }
@@ -352,62 +989,144 @@ fn print_and_log_info(msg: String) {
     info!("{}", msg);
 }
 
-/// run the speed_test and append the json output to a data_file
-fn run_speed_test(working_dir: &Path, output_file: &Path) {
-    let start: String = Local::now().format(DATE_TIME_FORMAT).to_string();
-    let output_rs = Command::new(working_dir.join(SPEED_TEST_CMD).to_str().unwrap()).output();
-    let stop: String = Local::now().format(DATE_TIME_FORMAT).to_string();
-    match output_rs {
-        Ok(output) => {
-            let err_msg = String::from_utf8_lossy(&output.stderr);
-            if output.status.success() {
-                let json = String::from_utf8_lossy(&output.stdout);
-                match append_json_to_file(output_file, &json) {
-                    Ok(()) => {
-                        if !err_msg.trim().eq("") {
-                            // speed_test run normally but could not find a server e.g:
-                            print_and_log_error(format!(
-                                "run_speed_test ERROR from {} to {} message = '{}'",
-                                start, stop, &err_msg
-                            ));
-                        } else {
-                            // everything is fine:
-                            print_and_log_info(format!(
-                                "run_speed_test OK from {} to {}",
-                                start, stop
-                            ));
-                        }
-                    }
-                    Err(err) => print_and_log_error(format!(
-                        // could not write speed_test result:
-                        "run_speed_test ERROR from {} to {} message = '{}'",
-                        start, stop, &err
-                    )),
-                };
-            } else {
-                // speed_test crashed:
-                print_and_log_error(format!(
-                    "run_speed_test ERROR from {} to {} message = '{}'",
-                    start, stop, &err_msg
-                ));
+/// run the speed_test and append its result as record to a data_file,
+/// a failed run is appended as record without performance
+fn run_speed_test(runner: &dyn SpeedTestRunner, program: &Path, output_file: &Path) {
+    let start = Local::now().naive_local();
+    let mut outputs: Vec<String> = Vec::new();
+    let (mut status, mut exit_code, mut err_msg) = (RunStatus::Ok, None, String::new());
+    for args in runner.calls() {
+        match Command::new(program).args(&args).output() {
+            Ok(output) => {
+                let (call_status, call_err_msg) = runner.status(&output);
+                outputs.push(String::from_utf8_lossy(&output.stdout).into_owned());
+                status = call_status;
+                exit_code = output.status.code();
+                err_msg = call_err_msg;
+            }
+            // could not get any output speed_test
+            Err(e) => {
+                status = RunStatus::SpawnFailed;
+                exit_code = None;
+                err_msg = e.to_string();
             }
         }
-        // could not get any output speed_test
-        Err(e) => print_and_log_error(format!(
-            "run_speed_test ERROR from {} to {} message = '{}'",
+        if status != RunStatus::Ok {
+            break;
+        }
+    }
+    let stop = Local::now().naive_local();
+    let record = match status {
+        RunStatus::SpawnFailed => None,
+        _ => Some(runner.record(&outputs, start)),
+    };
+    let run = match &record {
+        // exit code 0 but no result:
+        Some(Err(msg)) if status == RunStatus::Ok => run_info(
+            runner.backend(),
+            RunStatus::Crashed,
             start,
             stop,
-            &e.to_string()
+            exit_code,
+            msg,
+        ),
+        _ => run_info(runner.backend(), status, start, stop, exit_code, &err_msg),
+    };
+    record_run(output_file, run, record.and_then(Result::ok));
+}
+
+fn run_info(
+    backend: Backend,
+    status: RunStatus,
+    start: NaiveDateTime,
+    stop: NaiveDateTime,
+    exit_code: Option<i32>,
+    err_msg: &str,
+) -> RunInfo {
+    let err_msg = err_msg.trim();
+    RunInfo {
+        status,
+        backend: Some(backend),
+        start,
+        stop,
+        exit_code,
+        stderr: if err_msg.is_empty() {
+            None
+        } else {
+            Some(err_msg.chars().take(RUN_STDERR_EXCERPT_LENGTH).collect())
+        },
+    }
+}
+
+/// append the result of the speed test together with the run info,
+/// if there is no result a record with only timestamp and run info is appended
+fn record_run(
+    output_file: &Path,
+    run: RunInfo,
+    record: Option<serde_json::Map<String, serde_json::Value>>,
+) {
+    let record = match record {
+        Some(mut result) => {
+            result.insert(String::from("run"), serde_json::json!(run));
+            serde_json::Value::Object(result)
+        }
+        None => failed_run_record(&run),
+    };
+    let (start, stop) = (
+        run.start.format(DATE_TIME_FORMAT),
+        run.stop.format(DATE_TIME_FORMAT),
+    );
+    match append_json_to_file(output_file, &format!("{}\n", record)) {
+        Ok(()) if run.status == RunStatus::Ok => {
+            // everything is fine:
+            print_and_log_info(format!("run_speed_test OK from {} to {}", start, stop))
+        }
+        Ok(()) => print_and_log_error(format!(
+            "run_speed_test ERROR from {} to {} status = {} message = '{}'",
+            start,
+            stop,
+            run.status,
+            run.stderr.as_deref().unwrap_or_default()
         )),
+        Err(err) => {
+            // could not write speed_test result:
+            print_and_log_error(format!(
+                "run_speed_test ERROR from {} to {} status = {} message = '{}'",
+                start,
+                stop,
+                RunStatus::WriteFailed,
+                &err
+            ));
+            // try to record at least the failure, e.g. if the result was too large:
+            let failed = run_info(
+                run.backend.unwrap_or(DEFAULT_BACKEND),
+                RunStatus::WriteFailed,
+                run.start,
+                run.stop,
+                None,
+                &err.to_string(),
+            );
+            let record = format!("{}\n", failed_run_record(&failed));
+            if let Err(e) = append_json_to_file(output_file, &record) {
+                error!("could not record failed run, message = '{}'", e);
+            }
+        }
     }
 }
 
+/// record without any measurement, the timestamp is the start of the run
+fn failed_run_record(run: &RunInfo) -> serde_json::Value {
+    serde_json::json!({
+        "timestamp": run.start.format(DATE_TIME_FORMAT).to_string(),
+        "run": run,
+    })
+}
+
 /// append to a file, create the output_file if it does not exist
-fn append_json_to_file(output_file: &Path, json: &str) -> Result<(), Box<dyn Error>> {
+fn append_json_to_file(output_file: &Path, json: &str) -> std::io::Result<()> {
     let mut file = fs::OpenOptions::new()
         .append(true)
         .create(true)
-        .write(true)
         .open(output_file)?;
     file.write_all(json.as_bytes())?;
     file.flush()?;
@@ -420,9 +1139,12 @@ fn read_data_file_paths(
     data_dir: &Path,
     first_filter_file_name: &str,
     last_filter_file_name: &str,
-) -> Option<Vec<String>> {
+) -> Result<Vec<String>, SpeedTrackerError> {
     let mut rs: Vec<String> = fs::read_dir(data_dir)
-        .ok()?
+        .map_err(SpeedTrackerError::io(format!(
+            "Could not read data dir {:?}",
+            data_dir
+        )))?
         .filter_map(|entry| {
             Some(
                 entry
@@ -436,7 +1158,8 @@ fn read_data_file_paths(
             )
         })
         .filter(|file_name| {
-            (first_filter_file_name <= file_name) && (last_filter_file_name >= file_name)
+            (first_filter_file_name <= file_name.as_str())
+                && (last_filter_file_name >= file_name.as_str())
         })
         .map(|file_name| {
             Path::new(data_dir)
@@ -447,17 +1170,33 @@ fn read_data_file_paths(
         })
         .collect();
     rs.sort();
-    Some(rs)
+    Ok(rs)
 }
 
 fn parse_output_file(
     data_dir: &Path,
     from_date: &NaiveDate,
     to_date: &NaiveDate,
-) -> Option<Vec<ParsedEntry>> {
-    let file = fs::File::open(data_dir).ok()?;
+) -> Result<Vec<ParsedEntry>, SpeedTrackerError> {
+    let rs = read_output_file(data_dir, from_date, to_date)?;
+    Ok(rs
+        .into_iter()
+        .map(|(_, parsed_entry)| parsed_entry)
+        .collect())
+}
+
+/// read all entries in the time range together with their original json line
+fn read_output_file(
+    data_dir: &Path,
+    from_date: &NaiveDate,
+    to_date: &NaiveDate,
+) -> Result<Vec<(String, ParsedEntry)>, SpeedTrackerError> {
+    let file = fs::File::open(data_dir).map_err(SpeedTrackerError::io(format!(
+        "Could not open {:?}",
+        data_dir
+    )))?;
     let reader = BufReader::new(file);
-    let rs: Vec<ParsedEntry> = reader
+    let rs: Vec<(String, ParsedEntry)> = reader
         .lines()
         .flat_map(|read_line| {
             match read_line {
@@ -467,7 +1206,7 @@ fn parse_output_file(
                         Ok(parsed_entry) => {
                             let entry_date = &parsed_entry.timestamp.date();
                             if from_date <= entry_date && entry_date <= to_date {
-                                Some(parsed_entry)
+                                Some((line, parsed_entry))
                             } else {
                                 None
                             }
@@ -485,5 +1224,5 @@ fn parse_output_file(
             }
         })
         .collect();
-    Some(rs)
+    Ok(rs)
 }
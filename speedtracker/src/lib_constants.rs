@@ -21,11 +21,55 @@
 // SOFTWARE.
 //
 
+use crate::chart_config::{MissingValues, Rollup, WindowUnit};
+use crate::runner::Backend;
+use crate::statistics::Statistic;
+
 pub const SPEED_TEST_CMD: &str = "speedtestJson";
 
+// other speed test backends, called by name from PATH if no 'command' is configured:
+pub const DEFAULT_BACKEND: Backend = Backend::SpeedtestJson;
+pub const OOKLA_CMD: &str = "speedtest";
+pub const SPEEDTEST_CLI_CMD: &str = "speedtest-cli";
+pub const LIBRESPEED_CMD: &str = "librespeed-cli";
+pub const IPERF3_CMD: &str = "iperf3";
+pub const BITS_PER_BYTE: f64 = 8.0;
+pub const MICROSECONDS_PER_MILLISECOND: f64 = 1000.0;
+
 // file names:
 pub const CONFIG_FILENAME: &str = "speedtracker.toml";
 pub const TEMPLATE_FILENAME: &str = "template.html";
+pub const COMPARE_TEMPLATE_FILENAME: &str = "compare_template.html";
+pub const LOG_FILENAME: &str = "speedtracker.log";
+
+// file locations:
+pub const PROGRAM_DIR_NAME: &str = "speedtracker";
+pub const SYSTEM_CONFIG_DIR: &str = "/etc/speedtracker";
+pub const ENV_CONFIG_FILE: &str = "SPEEDTRACKER_CONFIG";
+pub const ENV_TEMPLATE_FILE: &str = "SPEEDTRACKER_TEMPLATE";
+pub const ENV_COMPARE_TEMPLATE_FILE: &str = "SPEEDTRACKER_COMPARE_TEMPLATE";
+pub const ENV_SPEED_TEST_CMD: &str = "SPEEDTRACKER_SPEEDTEST_CMD";
+
+// config overrides:
+pub const ENV_OVERRIDE_PREFIX: &str = "SPEEDTRACKER_";
+/// environment variables with the prefix that are no config keys
+pub const ENV_RESERVED: [&str; 4] = [
+    ENV_CONFIG_FILE,
+    ENV_TEMPLATE_FILE,
+    ENV_COMPARE_TEMPLATE_FILE,
+    ENV_SPEED_TEST_CMD,
+];
+/// values of keys containing one of these are not logged
+pub const SENSITIVE_KEY_PARTS: [&str; 4] = ["password", "secret", "token", "api_key"];
+pub const MASKED_VALUE: &str = "******";
+
+// log messages of a speed test run:
+pub const RUN_LOG_OK: &str = "run_speed_test OK";
+pub const RUN_LOG_ERROR: &str = "run_speed_test ERROR";
+/// maximal number of characters of the error output stored in a run record
+pub const RUN_STDERR_EXCERPT_LENGTH: usize = 300;
+/// number of runs 'doctor' looks at
+pub const DOCTOR_RECENT_RUNS: usize = 10;
 
 // date formats:
 pub const DATE_FILE_NAME_FORMAT: &str = "%Y-%m-DATA.json";
@@ -38,12 +82,75 @@ pub const DEFAULT_OUTPUT_FILE: &str = "./index.html";
 pub const DEFAULT_OUTPUT_XDAYS: u32 = 14;
 pub const DEFAULT_LOG_FILE: &str = "./speedtracker.log";
 pub const DEFAULT_LOG_FILE_MAX_LENGTH_IN_KB: u64 = 8096;
+pub const DEFAULT_RUN_INTERVAL_MINUTES: u32 = 120;
+pub const DEFAULT_OUTAGE_MISSED_INTERVALS: u32 = 3;
+pub const DEFAULT_ROLLUP: Rollup = Rollup::Auto;
+pub const DEFAULT_ROLLUP_DAILY_AFTER_DAYS: u32 = 31;
+pub const DEFAULT_ROLLUP_WEEKLY_AFTER_DAYS: u32 = 180;
+pub const DEFAULT_MAX_POINTS: usize = 1000;
+pub const DEFAULT_CHANGE_MIN_SAMPLES: u32 = 10;
+pub const DEFAULT_CHANGE_MIN_SHIFT_PERCENT: f64 = 10.0;
+/// difference of the medians in standard errors (estimated by the MAD) that counts as change
+pub const CHANGE_THRESHOLD: f64 = 5.0;
+/// MAD * 1.4826 estimates the standard deviation of normal distributed values
+pub const MAD_TO_STANDARD_DEVIATION: f64 = 1.4826;
+/// downsampling keeps first, last, minimum, maximum and one point per bucket
+pub const DOWNSAMPLING_MIN_POINTS: usize = 5;
+
+// init defaults:
+pub const DEFAULT_INIT_DATA_DIR: &str = "data";
+pub const DEFAULT_INIT_OUTPUT_FILE: &str = "/var/www/html/index.html";
+
+// measurement campaign (rules of the Bundesnetzagentur):
+pub const CAMPAIGN_DAYS: usize = 3;
+pub const CAMPAIGN_MEASUREMENTS_PER_DAY: usize = 10;
+pub const CAMPAIGN_MIN_SPACING_MINUTES: i64 = 5;
+/// break between the first and second half of the measurements of a day
+pub const CAMPAIGN_BREAK_AFTER: usize = 5;
+pub const CAMPAIGN_BREAK_HOURS: i64 = 3;
+/// calendar days between two measurement days
+pub const CAMPAIGN_MIN_DAYS_BETWEEN: i64 = 1;
+/// all measurement days are within this number of calendar days
+pub const CAMPAIGN_MAX_PERIOD_DAYS: i64 = 14;
+/// share of the maximum speed that has to be reached at least once on CAMPAIGN_MAXIMUM_DAYS days
+pub const CAMPAIGN_MAXIMUM_SHARE: f64 = 0.9;
+pub const CAMPAIGN_MAXIMUM_DAYS: usize = 2;
+/// share of the measurements that have to reach the normal speed
+pub const CAMPAIGN_NORMAL_SHARE: f64 = 0.9;
+/// undercutting the minimum speed on this number of days is a deviation
+pub const CAMPAIGN_MINIMUM_DAYS: usize = 2;
+/// labels of the tiers with the contract speeds
+pub const DEFAULT_CAMPAIGN_MAXIMUM_TIER: &str = "maximum";
+pub const DEFAULT_CAMPAIGN_NORMAL_TIER: &str = "normal";
+pub const DEFAULT_CAMPAIGN_MINIMUM_TIER: &str = "minimum";
 
 // charts:
 pub const DEFAULT_FILL: bool = false;
+/// how charts draw runs without a value
+pub const DEFAULT_MISSING_VALUES: MissingValues = MissingValues::Gap;
+pub const DEFAULT_WINDOW_UNIT: WindowUnit = WindowUnit::Samples;
+/// modified z-score of Iglewicz and Hoaglin
+pub const DEFAULT_ANOMALY_THRESHOLD: f64 = 3.5;
+/// measurements of an hour of the week (or of the day) needed as baseline
+pub const ANOMALY_MIN_BASELINE: usize = 5;
+/// weeks of measurements the usual values of the hours are taken from
+pub const ANOMALY_BASELINE_WEEKS: i64 = 8;
+/// forecasts need samples of two days
+pub const FORECAST_MIN_SAMPLES: usize = 24;
+pub const FORECAST_SEASON_HOURS: usize = 24;
+/// grid of the smoothing parameters of level, trend and season
+pub const FORECAST_ALPHAS: [f64; 6] = [0.05, 0.1, 0.2, 0.3, 0.5, 0.8];
+pub const FORECAST_BETAS: [f64; 4] = [0.0, 0.01, 0.05, 0.1];
+pub const FORECAST_GAMMAS: [f64; 5] = [0.05, 0.1, 0.2, 0.3, 0.5];
+/// 95% confidence band
+pub const FORECAST_CONFIDENCE_Z: f64 = 1.96;
 
 pub const MEGA_BIT_FACTOR: f64 = 1000000.0;
 pub const MULT_DIV_NEUTRAL: f64 = 1.0;
+pub const UNIT_MS: &str = "ms";
+pub const UNIT_MBIT: &str = "Mbit/s";
+pub const HOURS_PER_DAY: usize = 24;
+pub const DAYS_PER_WEEK: usize = 7;
 
 // latency:
 pub const DEFAULT_LATENCY_LABEL: &str = "latency";
@@ -82,12 +189,285 @@ pub const REPLACEMENT_ID_STATISTICS: &str = "STATISTICS";
 pub const REPLACEMENT_ID_RESPONSE_TIMES: &str = "RESPONSE_TIMES";
 pub const REPLACEMENT_ID_THROUGHPUT: &str = "THROUGHPUT";
 pub const REPLACEMENT_ID_RAW_DATA: &str = "RAW_DATA";
+pub const REPLACEMENT_ID_OUTAGES: &str = "OUTAGES";
+pub const REPLACEMENT_ID_COMPLIANCE: &str = "COMPLIANCE";
+pub const REPLACEMENT_ID_AVAILABILITY: &str = "AVAILABILITY";
+pub const REPLACEMENT_ID_CAMPAIGN: &str = "CAMPAIGN";
+pub const REPLACEMENT_ID_HEATMAP: &str = "HEATMAP";
+pub const REPLACEMENT_ID_CHANGES: &str = "CHANGES";
+/// placeholders a template should contain
+pub const TEMPLATE_PLACEHOLDERS: [&str; 10] = [
+    REPLACEMENT_ID_STATISTICS,
+    REPLACEMENT_ID_RESPONSE_TIMES,
+    REPLACEMENT_ID_THROUGHPUT,
+    REPLACEMENT_ID_RAW_DATA,
+    REPLACEMENT_ID_OUTAGES,
+    REPLACEMENT_ID_COMPLIANCE,
+    REPLACEMENT_ID_AVAILABILITY,
+    REPLACEMENT_ID_CAMPAIGN,
+    REPLACEMENT_ID_HEATMAP,
+    REPLACEMENT_ID_CHANGES,
+];
+/// table of the compare template
+pub const REPLACEMENT_ID_COMPARISON: &str = "COMPARISON";
+
+// heatmap of the weekday x hour medians (svg, sizes in pixel):
+pub const HEATMAP_CELL_WIDTH: usize = 22;
+pub const HEATMAP_CELL_HEIGHT: usize = 18;
+pub const HEATMAP_LABEL_WIDTH: usize = 36;
+pub const HEATMAP_LABEL_HEIGHT: usize = 16;
+pub const HEATMAP_EMPTY_COLOR: &str = "#E8E8E8";
+pub const HEATMAP_ALL_DAYS_LABEL: &str = "all";
+
+// markers for failed runs:
+pub const FAILED_RUNS_LABEL: &str = "failed runs";
+pub const FAILED_RUNS_COLOR: &str = "black";
+/// point styles of Chart.js, e.g. 'cross', 'crossRot', 'triangle'
+pub const FAILED_RUNS_POINT_STYLE: &str = "crossRot";
+pub const FAILED_RUNS_POINT_RADIUS: u32 = 6;
+/// marker for missing values of a chart with missing_values = 'marker'
+pub const MISSING_VALUES_LABEL: &str = "missing";
+pub const MISSING_VALUES_POINT_STYLE: &str = "triangle";
+/// band of the min and max values of daily or weekly rollups
+pub const ROLLUP_MIN_LABEL: &str = "min";
+pub const ROLLUP_MAX_LABEL: &str = "max";
+/// vertical line at a change of the level
+pub const CHANGES_LABEL: &str = "change";
+pub const CHANGES_POINT_RADIUS: u32 = 3;
+/// highlighted anomalous measurements
+pub const ANOMALIES_LABEL: &str = "anomalies";
+pub const ANOMALIES_COLOR: &str = "magenta";
+pub const ANOMALIES_POINT_STYLE: &str = "rectRot";
+pub const ANOMALIES_POINT_RADIUS: u32 = 5;
+/// forecast line and band
+pub const FORECAST_BORDER_DASH: [u32; 2] = [6, 4];
+pub const FORECAST_UPPER_LABEL: &str = "upper";
+pub const FORECAST_LOWER_LABEL: &str = "lower";
+pub const KEY_FORECAST: &str = "forecast";
+/// the second of two compared time ranges
+pub const COMPARE_BORDER_DASH: [u32; 2] = [6, 4];
+/// p-value below which the difference of two time ranges is significant
+pub const COMPARE_SIGNIFICANCE_LEVEL: f64 = 0.05;
+pub const SECONDS_PER_DAY: f64 = 86400.0;
 
 pub const ID_LATENCY: &str = "latency";
 pub const ID_JITTER: &str = "jitter";
 pub const ID_DOWNLOAD: &str = "download";
 pub const ID_UPLOAD: &str = "upload";
 
+// column headers of the statistics:
 pub const STATISTIC_MEDIAN: &str = "median";
 pub const STATISTIC_AVG: &str = "average";
-pub const STATISTIC_STD: &str = "standard-deviation";
+pub const STATISTIC_STD: &str = "standard-deviation (population)";
+pub const STATISTIC_SAMPLE_STD: &str = "standard-deviation (sample)";
+pub const STATISTIC_SAMPLES: &str = "n";
+pub const STATISTIC_MIN: &str = "min";
+pub const STATISTIC_MAX: &str = "max";
+pub const STATISTIC_IQR: &str = "IQR";
+// keys of the statistics in the config, percentiles are 'p0' to 'p100':
+pub const STATISTIC_KEY_COUNT: &str = "n";
+pub const STATISTIC_KEY_MIN: &str = "min";
+pub const STATISTIC_KEY_MAX: &str = "max";
+pub const STATISTIC_KEY_AVERAGE: &str = "average";
+pub const STATISTIC_KEY_MEDIAN: &str = "median";
+pub const STATISTIC_KEY_IQR: &str = "iqr";
+pub const STATISTIC_KEY_STD: &str = "standard-deviation";
+pub const STATISTIC_KEY_SAMPLE_STD: &str = "sample-standard-deviation";
+/// columns of the statistic table if a chart does not configure them
+pub const DEFAULT_STATISTICS: [Statistic; 4] = [
+    Statistic::Median,
+    Statistic::Average,
+    Statistic::StandardDeviation,
+    Statistic::Count,
+];
+/// shown instead of a statistic if there are too few samples
+pub const STATISTIC_NOT_AVAILABLE: &str = "n/a";
+pub const NO_DATA_NOTICE: &str = "no measurements in this period";
+pub const STATISTIC_AVAILABILITY: &str = "availability";
+pub const STATISTIC_REACHED: &str = "reached";
+pub const STATISTIC_DAYS_MET: &str = "daily median met";
+pub const STATISTIC_TIERS: &str = "tiers";
+
+/// levels in percent of the expected value the compliance is computed for
+pub const COMPLIANCE_LEVELS: [u32; 3] = [100, 90, 50];
+
+// config keys:
+pub const KEY_LATENCY_CHART: &str = "latency_chart";
+pub const KEY_JITTER_CHART: &str = "jitter_chart";
+pub const KEY_DOWNLOAD_CHART: &str = "download_chart";
+pub const KEY_UPLOAD_CHART: &str = "upload_chart";
+pub const KEY_CAMPAIGN: &str = "campaign";
+pub const KEY_RUNNER: &str = "runner";
+pub const KEY_EXPECTED_VALUE: &str = "expected_value";
+pub const KEY_ROLLING_MEDIAN: &str = "rolling_median";
+pub const KEY_EWMA: &str = "ewma";
+/// optional keys of an expected_value, 'value', 'tiers' or 'history' has to be set
+pub const EXPECTED_OPTIONAL_KEYS: [&str; 4] = ["value", "tiers", "history", "reference"];
+pub const KEY_TIERS: &str = "tiers";
+/// optional keys of a tier
+pub const TIER_OPTIONAL_KEYS: [&str; 1] = ["history"];
+/// tables on top level that may be left out
+pub const OPTIONAL_TABLES: [&str; 2] = [KEY_CAMPAIGN, KEY_RUNNER];
+/// tables of a chart that may be left out
+pub const CHART_OPTIONAL_TABLES: [&str; 4] = [
+    KEY_EXPECTED_VALUE,
+    KEY_ROLLING_MEDIAN,
+    KEY_EWMA,
+    KEY_FORECAST,
+];
+
+// colors:
+pub const COLOR_REGEX: &str = r"^(#[0-9a-f]{3}|#[0-9a-f]{6}|(rgb|rgba|hsl|hsla|hwb)\(\s*-?[0-9.]+%?\s*(,\s*-?[0-9.]+%?\s*){2,3}\))$";
+
+/// named colors of css (as used by chartjs-color)
+pub const CSS_COLOR_NAMES: [&str; 149] = [
+    "transparent",
+    "aliceblue",
+    "antiquewhite",
+    "aqua",
+    "aquamarine",
+    "azure",
+    "beige",
+    "bisque",
+    "black",
+    "blanchedalmond",
+    "blue",
+    "blueviolet",
+    "brown",
+    "burlywood",
+    "cadetblue",
+    "chartreuse",
+    "chocolate",
+    "coral",
+    "cornflowerblue",
+    "cornsilk",
+    "crimson",
+    "cyan",
+    "darkblue",
+    "darkcyan",
+    "darkgoldenrod",
+    "darkgray",
+    "darkgreen",
+    "darkgrey",
+    "darkkhaki",
+    "darkmagenta",
+    "darkolivegreen",
+    "darkorange",
+    "darkorchid",
+    "darkred",
+    "darksalmon",
+    "darkseagreen",
+    "darkslateblue",
+    "darkslategray",
+    "darkslategrey",
+    "darkturquoise",
+    "darkviolet",
+    "deeppink",
+    "deepskyblue",
+    "dimgray",
+    "dimgrey",
+    "dodgerblue",
+    "firebrick",
+    "floralwhite",
+    "forestgreen",
+    "fuchsia",
+    "gainsboro",
+    "ghostwhite",
+    "gold",
+    "goldenrod",
+    "gray",
+    "green",
+    "greenyellow",
+    "grey",
+    "honeydew",
+    "hotpink",
+    "indianred",
+    "indigo",
+    "ivory",
+    "khaki",
+    "lavender",
+    "lavenderblush",
+    "lawngreen",
+    "lemonchiffon",
+    "lightblue",
+    "lightcoral",
+    "lightcyan",
+    "lightgoldenrodyellow",
+    "lightgray",
+    "lightgreen",
+    "lightgrey",
+    "lightpink",
+    "lightsalmon",
+    "lightseagreen",
+    "lightskyblue",
+    "lightslategray",
+    "lightslategrey",
+    "lightsteelblue",
+    "lightyellow",
+    "lime",
+    "limegreen",
+    "linen",
+    "magenta",
+    "maroon",
+    "mediumaquamarine",
+    "mediumblue",
+    "mediumorchid",
+    "mediumpurple",
+    "mediumseagreen",
+    "mediumslateblue",
+    "mediumspringgreen",
+    "mediumturquoise",
+    "mediumvioletred",
+    "midnightblue",
+    "mintcream",
+    "mistyrose",
+    "moccasin",
+    "navajowhite",
+    "navy",
+    "oldlace",
+    "olive",
+    "olivedrab",
+    "orange",
+    "orangered",
+    "orchid",
+    "palegoldenrod",
+    "palegreen",
+    "paleturquoise",
+    "palevioletred",
+    "papayawhip",
+    "peachpuff",
+    "peru",
+    "pink",
+    "plum",
+    "powderblue",
+    "purple",
+    "rebeccapurple",
+    "red",
+    "rosybrown",
+    "royalblue",
+    "saddlebrown",
+    "salmon",
+    "sandybrown",
+    "seagreen",
+    "seashell",
+    "sienna",
+    "silver",
+    "skyblue",
+    "slateblue",
+    "slategray",
+    "slategrey",
+    "snow",
+    "springgreen",
+    "steelblue",
+    "tan",
+    "teal",
+    "thistle",
+    "tomato",
+    "turquoise",
+    "violet",
+    "wheat",
+    "white",
+    "whitesmoke",
+    "yellow",
+    "yellowgreen",
+];
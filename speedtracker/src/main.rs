@@ -21,52 +21,552 @@
 // SOFTWARE.
 //
 
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use log::{error, info, warn};
 use mylib::*;
 use std::env;
-use std::path::Path;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::str::FromStr;
 
 pub const PROGRAM_NAME: &str = "speedtracker";
 pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+// note: exit code 2 is used by clap for wrong usage
+pub const EXIT_CONFIG_ERROR: i32 = 3;
+pub const EXIT_IO_ERROR: i32 = 4;
+pub const EXIT_PARSE_ERROR: i32 = 5;
+pub const EXIT_TEMPLATE_ERROR: i32 = 6;
+pub const EXIT_NO_DATA: i32 = 7;
+
+#[derive(Parser)]
+#[clap(name = PROGRAM_NAME, version, about = "track and visualize DSL speed over a longer period")]
+#[clap(arg_required_else_help = true)]
+struct Cli {
+    /// read the config from this file instead of searching it
+    /// (SPEEDTRACKER_CONFIG, $XDG_CONFIG_HOME/speedtracker, /etc/speedtracker, program directory)
+    #[clap(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// override a config value, e.g. --set download_chart.expected_value.value=100
+    /// (overrides config file and SPEEDTRACKER_* environment variables)
+    #[clap(
+        long,
+        global = true,
+        value_name = "KEY=VALUE",
+        multiple_occurrences = true
+    )]
+    set: Vec<String>,
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// mode 1: run a speed test and generate the configured output file
+    Run,
+    /// mode 2: only generate an output file for a time range (no speed test)
+    Render {
+        /// first day to visualize, e.g. 2022-01-01
+        #[clap(long, value_name = "DATE")]
+        from: String,
+        /// last day to visualize, e.g. 2022-01-31
+        #[clap(long, value_name = "DATE")]
+        to: String,
+        /// html file to write
+        #[clap(long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// print the statistics of a time range to the console
+    Stats {
+        #[clap(flatten)]
+        range: RangeArgs,
+    },
+    /// export the stored json lines of a time range
+    Export {
+        #[clap(flatten)]
+        range: RangeArgs,
+        /// file to write, default is the console
+        #[clap(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// write the medians by hour of day and by weekday x hour as json instead of the entries
+        #[clap(long)]
+        aggregation: bool,
+        /// write the anomalous measurements as json instead of the entries
+        #[clap(long, conflicts_with = "aggregation")]
+        anomalies: bool,
+    },
+    /// import json lines (e.g. an export) into the data directory
+    Import {
+        /// file to read, use '-' for the console
+        #[clap(long, value_name = "FILE")]
+        input: PathBuf,
+    },
+    /// print the outages of a time range (failed runs, no download, missing entries)
+    Outages {
+        #[clap(flatten)]
+        range: RangeArgs,
+        /// print json instead of a table
+        #[clap(long)]
+        json: bool,
+    },
+    /// print the anomalous measurements of a time range, exits with 1 if there are any (e.g. for alerts by cron)
+    Anomalies {
+        #[clap(flatten)]
+        range: RangeArgs,
+        /// print json instead of a table
+        #[clap(long)]
+        json: bool,
+    },
+    /// print the changes of the level of every chart in a time range with the medians before and after
+    Changes {
+        #[clap(flatten)]
+        range: RangeArgs,
+        /// print json instead of a table
+        #[clap(long)]
+        json: bool,
+    },
+    /// compare two time ranges, e.g. before and after a router swap, as table or as html file
+    Compare {
+        /// first day of the first time range, e.g. 2022-01-01
+        #[clap(long, value_name = "DATE")]
+        from: String,
+        /// last day of the first time range, e.g. 2022-01-31
+        #[clap(long, value_name = "DATE")]
+        to: String,
+        /// first day of the second time range, e.g. 2022-02-01
+        #[clap(long, value_name = "DATE")]
+        with_from: String,
+        /// last day of the second time range, e.g. 2022-02-28
+        #[clap(long, value_name = "DATE")]
+        with_to: String,
+        /// html file to write instead of printing a table
+        #[clap(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// print json instead of a table
+        #[clap(long, conflicts_with = "output")]
+        json: bool,
+    },
+    /// evaluate a measurement campaign against the contract speeds in [campaign]
+    Campaign {
+        #[clap(flatten)]
+        range: RangeArgs,
+        /// print json instead of text
+        #[clap(long)]
+        json: bool,
+    },
+    /// check that all configured paths are accessible
+    Check,
+    /// print which config file, template files and speed test command are used
+    Paths,
+    /// validate the config file and print all problems found
+    ValidateConfig,
+    /// write a commented speedtracker.toml and the templates and check the installation
+    Init(InitArgs),
+    /// check the installation and the recent runs, print a suggested fix for every problem
+    Doctor,
+}
+
+#[derive(Args)]
+struct InitArgs {
+    /// directory for speedtracker.toml and the templates, default is the program directory
+    #[clap(long, value_name = "DIR")]
+    dir: Option<PathBuf>,
+    /// directory where data is collected
+    #[clap(long, value_name = "DIR")]
+    data_dir: Option<PathBuf>,
+    /// html file, should be on a path served by a webserver
+    #[clap(long, value_name = "FILE")]
+    output_file: Option<PathBuf>,
+    /// expected download speed in Mbit/s
+    #[clap(long, value_name = "MBIT/S")]
+    expected_download: Option<f64>,
+    /// expected upload speed in Mbit/s
+    #[clap(long, value_name = "MBIT/S")]
+    expected_upload: Option<f64>,
+    /// run the speed test every x minutes, e.g. 30, 60 or 120
+    #[clap(long, value_name = "MINUTES")]
+    interval: Option<u32>,
+    /// do not ask, use the default for every value not given as flag
+    #[clap(long)]
+    yes: bool,
+    /// overwrite existing files
+    #[clap(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct RangeArgs {
+    /// first day, default is 'output_xdays' in the past
+    #[clap(long, value_name = "DATE")]
+    from: Option<String>,
+    /// last day, default is today
+    #[clap(long, value_name = "DATE")]
+    to: Option<String>,
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let args_len = args.len();
+    let cli = Cli::parse_from(legacy_args(env::args().collect()));
 
-    if args_len != 2 && args_len != 4 {
-        print_usage();
-        process::exit(EXIT_SUCCESS);
-    } else {
-        let working_dir: &Path = Path::new(&args[0]).parent().unwrap();
-        let config = read_config(working_dir);
-
-        init_logger(&config);
-
-        // decide setup by command line arguments:
-        let setup = match args_len {
-            2 if &args[1] == "run" => config_to_setup_for_mode_1(working_dir, config),
-            4 => config_to_setup_for_mode_2(working_dir, config, &args[1], &args[2], &args[3]),
-            _ => {
-                print_usage();
-                process::exit(EXIT_SUCCESS);
+    let exit_code = match execute(cli) {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            eprintln!("{}", e);
+            error!("{}", e);
+            exit_code_of(&e)
+        }
+    };
+    let _ = std::io::stdout().flush();
+    process::exit(exit_code);
+}
+
+fn execute(cli: Cli) -> Result<i32, SpeedTrackerError> {
+    let locator = FileLocator::new(cli.config);
+    // commands that do not need a valid config:
+    match &cli.command {
+        Commands::Paths => {
+            print_paths(&locator);
+            return Ok(EXIT_SUCCESS);
+        }
+        Commands::Init(args) => return init(args, &locator),
+        Commands::Doctor => return Ok(doctor(&locator, &cli.set)),
+        _ => (),
+    }
+
+    let config_file = locate_config(&locator)?;
+    if let Commands::ValidateConfig = cli.command {
+        return Ok(validate_config(&config_file, &cli.set));
+    }
+
+    let (config, warnings) = read_config_file(&config_file, &cli.set)?;
+
+    init_logger(&config);
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+    info!("using config file {:?}", config_file);
+    info!("effective config: {}", config_for_log(&config));
+
+    match cli.command {
+        Commands::Run => {
+            let setup = config_to_setup_for_mode_1(&locator, config)?;
+            generate(&setup)
+        }
+        Commands::Render { from, to, output } => {
+            let output = output.to_string_lossy();
+            let setup = config_to_setup_for_mode_2(&locator, config, &from, &to, &output)?;
+            generate(&setup)
+        }
+        Commands::Stats { range } => {
+            let setup = range_setup(&locator, config, &range)?;
+            let parsed_data = setup.read_data()?;
+            if parsed_data.is_empty() {
+                return Err(setup.no_data());
+            }
+            setup.print_statistics(&parsed_data);
+            Ok(EXIT_SUCCESS)
+        }
+        Commands::Outages { range, json } => {
+            let setup = range_setup(&locator, config, &range)?;
+            let parsed_data = setup.read_data()?;
+            setup.print_outages(&parsed_data, json);
+            Ok(EXIT_SUCCESS)
+        }
+        Commands::Anomalies { range, json } => {
+            let setup = range_setup(&locator, config, &range)?;
+            let parsed_data = setup.read_data()?;
+            match setup.print_anomalies(&parsed_data, json)? {
+                0 => Ok(EXIT_SUCCESS),
+                _ => Ok(EXIT_FAILURE),
+            }
+        }
+        Commands::Changes { range, json } => {
+            let setup = range_setup(&locator, config, &range)?;
+            let parsed_data = setup.read_data()?;
+            setup.print_changes(&parsed_data, json);
+            Ok(EXIT_SUCCESS)
+        }
+        Commands::Compare {
+            from,
+            to,
+            with_from,
+            with_to,
+            output,
+            json,
+        } => {
+            // both periods are shown with the same config:
+            let setup =
+                config_to_setup_for_range(&locator, config.clone(), Some(&from), Some(&to))?;
+            let other =
+                config_to_setup_for_range(&locator, config, Some(&with_from), Some(&with_to))?;
+            let parsed_data = setup.read_data()?;
+            if parsed_data.is_empty() {
+                return Err(setup.no_data());
+            }
+            let other_data = other.read_data()?;
+            if other_data.is_empty() {
+                return Err(other.no_data());
+            }
+            match output {
+                Some(file) => {
+                    setup.generate_comparison(&parsed_data, &other, &other_data, &file)?;
+                    eprintln!("comparison written to {:?}", file);
+                }
+                None => setup.print_comparison(&parsed_data, &other, &other_data, json),
             }
-        };
+            Ok(EXIT_SUCCESS)
+        }
+        Commands::Campaign { range, json } => {
+            let setup = range_setup(&locator, config, &range)?;
+            let parsed_data = setup.read_data()?;
+            match setup.print_campaign(&parsed_data, json)? {
+                CampaignStatus::Pass => Ok(EXIT_SUCCESS),
+                CampaignStatus::Fail | CampaignStatus::Incomplete => Ok(EXIT_FAILURE),
+            }
+        }
+        Commands::Export {
+            range,
+            output,
+            aggregation,
+            anomalies,
+        } => {
+            let setup = range_setup(&locator, config, &range)?;
+            let mut writer: Box<dyn Write> = match output {
+                Some(file) => Box::new(File::create(&file).map_err(SpeedTrackerError::io(
+                    format!("Could not create file {:?}", file),
+                ))?),
+                None => Box::new(std::io::stdout()),
+            };
+            if aggregation {
+                let count = setup.export_aggregation(&mut writer)?;
+                eprintln!("{} entries aggregated", count);
+            } else if anomalies {
+                let count = setup.export_anomalies(&mut writer)?;
+                eprintln!("{} anomalies exported", count);
+            } else {
+                let count = setup.export_data(&mut writer)?;
+                eprintln!("{} entries exported", count);
+            }
+            Ok(EXIT_SUCCESS)
+        }
+        Commands::Import { input } => {
+            let summary = import_data(&config, &input)?;
+            println!(
+                "{} entries imported, {} duplicates skipped, {} invalid lines skipped",
+                summary.imported, summary.duplicates, summary.invalid
+            );
+            Ok(EXIT_SUCCESS)
+        }
+        Commands::Check => {
+            if check_config(&config) {
+                println!("OK");
+                Ok(EXIT_SUCCESS)
+            } else {
+                Ok(EXIT_FAILURE)
+            }
+        }
+        Commands::Paths | Commands::ValidateConfig | Commands::Init(_) | Commands::Doctor => {
+            unreachable!("handled before the config is read")
+        }
+    }
+}
 
-        // run speed test:
-        setup.maybe_speed_test();
+/// ask for every value not given as flag, write config and template and check the installation
+fn init(args: &InitArgs, locator: &FileLocator) -> Result<i32, SpeedTrackerError> {
+    let config_dir = ask(
+        "directory for speedtracker.toml and the templates",
+        args.dir.as_ref().map(|p| p.display().to_string()),
+        locator.program_dir().display().to_string(),
+        args.yes,
+    );
+    let defaults = InitOptions::defaults(PathBuf::from(config_dir));
+    let options = InitOptions {
+        data_dir: PathBuf::from(ask(
+            "directory for the collected data",
+            args.data_dir.as_ref().map(|p| p.display().to_string()),
+            defaults.data_dir.display().to_string(),
+            args.yes,
+        )),
+        output_file: PathBuf::from(ask(
+            "html output file (served by a webserver)",
+            args.output_file.as_ref().map(|p| p.display().to_string()),
+            defaults.output_file.display().to_string(),
+            args.yes,
+        )),
+        expected_download: ask(
+            "expected download speed in Mbit/s",
+            args.expected_download,
+            defaults.expected_download,
+            args.yes,
+        ),
+        expected_upload: ask(
+            "expected upload speed in Mbit/s",
+            args.expected_upload,
+            defaults.expected_upload,
+            args.yes,
+        ),
+        run_interval_minutes: ask(
+            "run the speed test every x minutes",
+            args.interval,
+            defaults.run_interval_minutes,
+            args.yes,
+        ),
+        force: args.force,
+        ..defaults
+    };
 
-        // parse and filter data:
-        let parsed_data = setup.read_data();
+    let problems = init_installation(&options, locator)?;
+    for problem in &problems {
+        println!("WARNING: {}", problem);
+    }
+    println!(
+        "\ninstall the cron job via 'crontab -e':\n{}",
+        init_cron_line(&options, locator)
+    );
 
-        //write output
-        setup.generate_html(&parsed_data)
+    Ok(if problems.is_empty() {
+        EXIT_SUCCESS
+    } else {
+        EXIT_FAILURE
+    })
+}
+
+/// value given as flag, or ask on the console (empty answer is the default)
+fn ask<T: FromStr + Display>(question: &str, given: Option<T>, default: T, use_default: bool) -> T {
+    if let Some(value) = given {
+        return value;
+    }
+    if use_default {
+        return default;
+    }
+    loop {
+        print!("{} [{}]: ", question, default);
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        match std::io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => return default, // no console
+            Ok(_) => (),
+        }
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return default;
+        }
+        match answer.parse::<T>() {
+            Ok(value) => return value,
+            Err(_) => println!("'{}' is not valid, please try again", answer),
+        }
+    }
+}
+
+/// print all problems of the config file, fails if there is at least one error
+fn validate_config(config_file: &Path, cli_overrides: &[String]) -> i32 {
+    let problems = validate_config_file(config_file, cli_overrides);
+    for problem in &problems {
+        match problem.position {
+            Some(_) => println!("{}:{}", config_file.display(), problem),
+            None => println!("{}: {}", config_file.display(), problem),
+        }
+    }
+    let errors = problems
+        .iter()
+        .filter(|p| p.severity == Severity::Error)
+        .count();
+    println!(
+        "{} error(s), {} warning(s)",
+        errors,
+        problems.len() - errors
+    );
+    if errors == 0 {
+        EXIT_SUCCESS
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+/// print one line per check, fails if at least one check failed
+fn doctor(locator: &FileLocator, cli_overrides: &[String]) -> i32 {
+    let checks = run_doctor(locator, cli_overrides);
+    for check in &checks {
+        println!("{}  {}: {}", check.status, check.name, check.message);
+        if let Some(fix) = &check.fix {
+            println!("      fix: {}", fix);
+        }
+    }
+    if checks.iter().any(|c| c.status == CheckStatus::Fail) {
+        EXIT_FAILURE
+    } else {
+        EXIT_SUCCESS
     }
 }
 
-fn print_usage() {
-    println!("FOR MODE 1 'speedtest + standard.html generation' run:");
-    println!("{} run\n", PROGRAM_NAME);
-    println!("FOR MODE 2: 'only' output.html generation run:");
-    println!("{} from_date to_date output_file\n", PROGRAM_NAME);
-    println!("e.g. {} 2022-01-01 2021-12-31 ./index.html\n", PROGRAM_NAME);
+/// print the location of every file the program needs
+fn print_paths(locator: &FileLocator) {
+    for file in [
+        LocatedFile::Config,
+        LocatedFile::Template,
+        LocatedFile::CompareTemplate,
+        LocatedFile::SpeedTestCmd,
+    ] {
+        match locator.locate(file) {
+            Ok(path) => println!("{}: {}", file, path.display()),
+            Err(searched) => println!("{}: not found, {}", file, format_searched(&searched)),
+        }
+    }
+}
+
+/// mode 1 and mode 2: maybe run speed test, read the data and write the output
+fn generate(setup: &Setup) -> Result<i32, SpeedTrackerError> {
+    // run speed test:
+    setup.maybe_speed_test();
+
+    // parse and filter data:
+    let parsed_data = setup.read_data()?;
+
+    //write output
+    setup.generate_html(&parsed_data)?;
+    Ok(EXIT_SUCCESS)
+}
+
+fn range_setup(
+    locator: &FileLocator,
+    config: Config,
+    range: &RangeArgs,
+) -> Result<Setup, SpeedTrackerError> {
+    config_to_setup_for_range(locator, config, range.from.as_deref(), range.to.as_deref())
+}
+
+/// every kind of error gets its own exit code so wrapper scripts can react on it
+fn exit_code_of(error: &SpeedTrackerError) -> i32 {
+    match error {
+        SpeedTrackerError::Config(_) => EXIT_CONFIG_ERROR,
+        SpeedTrackerError::Io(_, _) => EXIT_IO_ERROR,
+        SpeedTrackerError::Parse(_) => EXIT_PARSE_ERROR,
+        SpeedTrackerError::Template(_, _) => EXIT_TEMPLATE_ERROR,
+        SpeedTrackerError::NoData(_, _) => EXIT_NO_DATA,
+    }
+}
+
+/// keep the old positional form of mode 2 working for existing cron jobs:
+/// 'speedtracker from_date to_date output_file' becomes
+/// 'speedtracker render --from from_date --to to_date --output output_file'
+fn legacy_args(args: Vec<String>) -> Vec<String> {
+    if args.len() == 4
+        && !args[1].starts_with('-')
+        && Cli::command().find_subcommand(&args[1]).is_none()
+    {
+        vec![
+            args[0].clone(),
+            String::from("render"),
+            String::from("--from"),
+            args[1].clone(),
+            String::from("--to"),
+            args[2].clone(),
+            String::from("--output"),
+            args[3].clone(),
+        ]
+    } else {
+        args
+    }
 }
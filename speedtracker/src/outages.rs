@@ -0,0 +1,241 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::{Duration, NaiveDateTime};
+use serde::Serialize;
+use std::fmt;
+
+use crate::json_parser::{naive_date_time_to_str, ParsedEntry};
+
+/// why the line is considered down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutageCause {
+    /// speed test run was recorded as failed
+    FailedRun,
+    /// speed test run but measured no download
+    NoDownload,
+    /// no entry for more than the configured number of run intervals
+    NoData,
+}
+
+impl fmt::Display for OutageCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutageCause::FailedRun => "failed run",
+            OutageCause::NoDownload => "no download",
+            OutageCause::NoData => "no data",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// time window the line was down
+#[derive(Debug, Serialize)]
+pub struct Outage {
+    #[serde(serialize_with = "naive_date_time_to_str")]
+    pub start: NaiveDateTime,
+    /// first successful measurement after the outage,
+    /// or the end of the time range if the outage is still ongoing
+    #[serde(serialize_with = "naive_date_time_to_str")]
+    pub end: NaiveDateTime,
+    pub duration_minutes: i64,
+    pub ongoing: bool,
+    pub causes: Vec<OutageCause>,
+}
+
+/// all outages of a time range with totals
+#[derive(Debug, Serialize)]
+pub struct OutageReport {
+    pub outages: Vec<Outage>,
+    pub count: usize,
+    pub total_minutes: i64,
+    pub longest_minutes: i64,
+}
+
+/// find outages in the entries: failed runs, runs without download
+/// and gaps of more than max_missed_intervals run intervals between two entries
+/// or between the last entry and range_end, the end of the time range.
+/// consecutive hits are merged into one outage
+pub fn detect_outages(
+    data: &[ParsedEntry],
+    range_end: NaiveDateTime,
+    run_interval_minutes: u32,
+    max_missed_intervals: u32,
+) -> OutageReport {
+    let interval = Duration::minutes(i64::from(run_interval_minutes));
+    let max_gap =
+        Duration::minutes(i64::from(run_interval_minutes) * i64::from(max_missed_intervals));
+    let mut entries: Vec<&ParsedEntry> = data.iter().collect();
+    entries.sort_by_key(|e| e.timestamp);
+
+    let mut outages: Vec<Outage> = Vec::new();
+    let mut previous: Option<&ParsedEntry> = None;
+    for entry in entries {
+        if let Some(prev) = previous {
+            if entry.timestamp - prev.timestamp > max_gap {
+                // the first run missing is the start:
+                add_hit(
+                    &mut outages,
+                    prev.timestamp + interval,
+                    entry.timestamp,
+                    OutageCause::NoData,
+                );
+            }
+        }
+        match outage_cause(entry) {
+            Some(cause) => add_hit(&mut outages, entry.timestamp, entry.timestamp, cause),
+            None => {
+                // line is up again, an outage ends with the first successful run:
+                if let Some(last) = outages.last_mut() {
+                    if last.ongoing {
+                        last.end = entry.timestamp;
+                        last.ongoing = false;
+                    }
+                }
+            }
+        }
+        previous = Some(entry);
+    }
+    // no entries since the last one, e.g. the line or the device is still down:
+    if let Some(last) = previous {
+        if range_end - last.timestamp > max_gap {
+            add_hit(
+                &mut outages,
+                last.timestamp + interval,
+                range_end,
+                OutageCause::NoData,
+            );
+        }
+    }
+    for outage in outages.iter_mut() {
+        outage.duration_minutes = (outage.end - outage.start).num_minutes();
+    }
+    OutageReport {
+        count: outages.len(),
+        total_minutes: outages.iter().map(|o| o.duration_minutes).sum(),
+        longest_minutes: outages
+            .iter()
+            .map(|o| o.duration_minutes)
+            .max()
+            .unwrap_or(0),
+        outages,
+    }
+}
+
+/// e.g. '2d 3h 15min'
+pub fn format_duration(minutes: i64) -> String {
+    let (days, hours, mins) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    let mut rs: Vec<String> = Vec::new();
+    if days > 0 {
+        rs.push(format!("{}d", days));
+    }
+    if hours > 0 {
+        rs.push(format!("{}h", hours));
+    }
+    if mins > 0 || rs.is_empty() {
+        rs.push(format!("{}min", mins));
+    }
+    rs.join(" ")
+}
+
+fn outage_cause(entry: &ParsedEntry) -> Option<OutageCause> {
+    match &entry.performance {
+        _ if entry.is_failed_run() => Some(OutageCause::FailedRun),
+        Some(p) if p.download.unwrap_or_default() <= 0.0 => Some(OutageCause::NoDownload),
+        Some(_) => None,
+        // entries written before failed runs were recorded:
+        None => Some(OutageCause::FailedRun),
+    }
+}
+
+/// extend the last outage if it is ongoing or overlaps, otherwise start a new one
+fn add_hit(
+    outages: &mut Vec<Outage>,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    cause: OutageCause,
+) {
+    if let Some(last) = outages.last_mut() {
+        if last.ongoing || start <= last.end {
+            last.end = last.end.max(end);
+            last.ongoing = true;
+            if !last.causes.contains(&cause) {
+                last.causes.push(cause);
+            }
+            return;
+        }
+    }
+    outages.push(Outage {
+        start,
+        end,
+        duration_minutes: 0,
+        ongoing: true,
+        causes: vec![cause],
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_parser::JsonParser;
+    use chrono::NaiveDate;
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 18)
+            .and_then(|d| d.and_hms_opt(hour, minute, 0))
+            .unwrap()
+    }
+
+    /// a successful run every 10 minutes from 08:00 to the given time
+    fn entries_until(hour: u32, minute: u32) -> Vec<ParsedEntry> {
+        (0..)
+            .map(|i| at(8, 0) + Duration::minutes(10 * i))
+            .take_while(|t| *t <= at(hour, minute))
+            .map(|t| {
+                JsonParser::parse(&format!(
+                    r#"{{"timestamp":"{}","performance":{{"latency":10,"download":1e8,"upload":1e7}}}}"#,
+                    t.format("%Y-%m-%d %H:%M:%S")
+                ))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn missing_entries_at_the_end_of_the_range_are_an_ongoing_outage() {
+        let report = detect_outages(&entries_until(12, 0), at(14, 0), 10, 3);
+        assert_eq!(report.count, 1);
+        let outage = &report.outages[0];
+        assert_eq!((outage.start, outage.end), (at(12, 10), at(14, 0)));
+        assert_eq!(outage.duration_minutes, 110);
+        assert!(outage.ongoing);
+        assert_eq!(outage.causes, vec![OutageCause::NoData]);
+    }
+
+    #[test]
+    fn a_short_gap_at_the_end_of_the_range_is_no_outage() {
+        let report = detect_outages(&entries_until(13, 40), at(14, 0), 10, 3);
+        assert_eq!(report.count, 0);
+    }
+}
@@ -0,0 +1,875 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::NaiveDateTime;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Output;
+
+use crate::file_locator::{format_searched, FileLocator, LocatedFile};
+use crate::json_parser::{naive_date_time_to_str, Client, Performance, RunStatus, Server};
+use crate::lib_constants::*;
+
+/// program measuring the speed, the output of every backend is stored in the format of 'speedtestJson'
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// 'speedtestJson' of the SpeedTest submodule
+    SpeedtestJson,
+    /// Ookla 'speedtest -f json'
+    Ookla,
+    /// 'speedtest-cli --json'
+    SpeedtestCli,
+    /// 'librespeed-cli --json'
+    Librespeed,
+    /// 'iperf3 -J' against an own server
+    Iperf3,
+}
+
+impl Backend {
+    /// program started if no command is configured
+    fn program_name(&self) -> &'static str {
+        match self {
+            Backend::SpeedtestJson => SPEED_TEST_CMD,
+            Backend::Ookla => OOKLA_CMD,
+            Backend::SpeedtestCli => SPEEDTEST_CLI_CMD,
+            Backend::Librespeed => LIBRESPEED_CMD,
+            Backend::Iperf3 => IPERF3_CMD,
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Backend::SpeedtestJson => "speedtest-json",
+            Backend::Ookla => "ookla",
+            Backend::SpeedtestCli => "speedtest-cli",
+            Backend::Librespeed => "librespeed",
+            Backend::Iperf3 => "iperf3",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// which speed test is run in mode 1 and how
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunnerConfig {
+    #[serde(default = "default_backend")]
+    pub backend: Backend,
+    /// path of the program, empty for the program of the backend
+    /// ('speedtestJson' is located like the config, the others are searched in PATH)
+    #[serde(default = "String::new")]
+    pub command: String,
+    /// server to measure against, empty to let the backend choose (iperf3 needs one)
+    #[serde(default = "String::new")]
+    pub server: String,
+    /// additional arguments of the program
+    #[serde(default = "Vec::new")]
+    pub args: Vec<String>,
+}
+
+fn default_backend() -> Backend {
+    DEFAULT_BACKEND
+}
+
+impl RunnerConfig {
+    /// the configured command, the located 'speedtestJson' or the program name of the backend
+    pub fn program(&self, locator: &FileLocator) -> Result<PathBuf, String> {
+        if !self.command.is_empty() {
+            return Ok(PathBuf::from(&self.command));
+        }
+        match self.backend {
+            Backend::SpeedtestJson => {
+                locator
+                    .locate(LocatedFile::SpeedTestCmd)
+                    .map_err(|searched| {
+                        format!(
+                            "{} not found, {}",
+                            SPEED_TEST_CMD,
+                            format_searched(&searched)
+                        )
+                    })
+            }
+            backend => Ok(PathBuf::from(backend.program_name())),
+        }
+    }
+}
+
+/// a speed test program: how it is called and how its output becomes a record
+pub trait SpeedTestRunner {
+    /// backend stored in the run info of every entry
+    fn backend(&self) -> Backend;
+    /// arguments of every call of the program, most backends measure with one call
+    fn calls(&self) -> Vec<Vec<String>>;
+    /// status and error message of a finished call
+    fn status(&self, output: &Output) -> (RunStatus, String) {
+        if output.status.success() {
+            // warnings of a successful run are no error:
+            (RunStatus::Ok, String::new())
+        } else {
+            (
+                RunStatus::Crashed,
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            )
+        }
+    }
+    /// record with client, server and performance from the outputs of all calls,
+    /// the timestamp is the start of the run
+    fn record(
+        &self,
+        outputs: &[String],
+        timestamp: NaiveDateTime,
+    ) -> Result<Map<String, Value>, String>;
+}
+
+/// runner of the configured backend
+pub fn speed_test_runner(config: &RunnerConfig) -> Box<dyn SpeedTestRunner> {
+    let (server, args) = (config.server.clone(), config.args.clone());
+    match config.backend {
+        Backend::SpeedtestJson => Box::new(SpeedtestJson { args }),
+        Backend::Ookla => Box::new(Ookla { server, args }),
+        Backend::SpeedtestCli => Box::new(SpeedtestCli { server, args }),
+        Backend::Librespeed => Box::new(Librespeed { server, args }),
+        Backend::Iperf3 => Box::new(Iperf3 { server, args }),
+    }
+}
+
+/// record in the format of 'speedtestJson'
+#[derive(Serialize)]
+struct Record {
+    #[serde(serialize_with = "naive_date_time_to_str")]
+    timestamp: NaiveDateTime,
+    client: Client,
+    server: Server,
+    performance: Performance,
+}
+
+impl Record {
+    fn into_map(self) -> Result<Map<String, Value>, String> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => Ok(map),
+            Ok(_) => Err(String::from("record is no json object")),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// parse the output of a call, the error names what is wrong and shows the beginning of the output
+fn parse_output<T: DeserializeOwned>(output: &str) -> Result<T, String> {
+    serde_json::from_str(output.trim()).map_err(|e| invalid_output(output, &e))
+}
+
+fn invalid_output(output: &str, error: &dyn fmt::Display) -> String {
+    format!(
+        "no valid json output ({}): '{}'",
+        error,
+        output
+            .trim()
+            .chars()
+            .take(RUN_STDERR_EXCERPT_LENGTH)
+            .collect::<String>()
+    )
+}
+
+/// the only output, every backend is called at least once
+fn first(outputs: &[String]) -> &str {
+    outputs.first().map(String::as_str).unwrap_or_default()
+}
+
+/// arguments of the backend, the server (if configured) and the additional arguments
+fn call(fixed: &[&str], server: Option<(&str, &str)>, args: &[String]) -> Vec<String> {
+    let mut rs: Vec<String> = fixed.iter().map(|a| String::from(*a)).collect();
+    if let Some((flag, value)) = server.filter(|(_, value)| !value.is_empty()) {
+        rs.push(String::from(flag));
+        rs.push(String::from(value));
+    }
+    rs.extend(args.iter().cloned());
+    rs
+}
+
+fn milliseconds(value: f64) -> u32 {
+    value.round() as u32
+}
+
+// speedtestJson:
+
+/// writes the record format itself, an error output means no server was found
+struct SpeedtestJson {
+    args: Vec<String>,
+}
+
+impl SpeedTestRunner for SpeedtestJson {
+    fn backend(&self) -> Backend {
+        Backend::SpeedtestJson
+    }
+    fn calls(&self) -> Vec<Vec<String>> {
+        vec![self.args.clone()]
+    }
+    fn status(&self, output: &Output) -> (RunStatus, String) {
+        let err_msg = String::from_utf8_lossy(&output.stderr).into_owned();
+        let status = if !output.status.success() {
+            // speed_test crashed:
+            RunStatus::Crashed
+        } else if !err_msg.trim().is_empty() {
+            // speed_test run normally but could not find a server e.g:
+            RunStatus::NoServer
+        } else {
+            RunStatus::Ok
+        };
+        (status, err_msg)
+    }
+    fn record(
+        &self,
+        outputs: &[String],
+        _timestamp: NaiveDateTime,
+    ) -> Result<Map<String, Value>, String> {
+        match serde_json::from_str(first(outputs).trim()) {
+            Ok(Value::Object(result)) if result.contains_key("timestamp") => Ok(result),
+            _ => Err(invalid_output(first(outputs), &"no record")),
+        }
+    }
+}
+
+// Ookla speedtest:
+
+#[derive(Deserialize)]
+struct OoklaResult {
+    ping: OoklaPing,
+    download: OoklaBandwidth,
+    upload: OoklaBandwidth,
+    isp: String,
+    interface: OoklaInterface,
+    server: OoklaServer,
+}
+
+/// in ms
+#[derive(Deserialize)]
+struct OoklaPing {
+    latency: f64,
+    jitter: f64,
+}
+
+/// in bytes per second
+#[derive(Deserialize)]
+struct OoklaBandwidth {
+    bandwidth: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OoklaInterface {
+    external_ip: String,
+}
+
+#[derive(Deserialize)]
+struct OoklaServer {
+    host: String,
+    name: String,
+    location: String,
+}
+
+/// the licenses have to be accepted, otherwise it waits for input
+struct Ookla {
+    server: String,
+    args: Vec<String>,
+}
+
+impl SpeedTestRunner for Ookla {
+    fn backend(&self) -> Backend {
+        Backend::Ookla
+    }
+    fn calls(&self) -> Vec<Vec<String>> {
+        vec![call(
+            &["--format=json", "--accept-license", "--accept-gdpr"],
+            Some(("--server-id", &self.server)),
+            &self.args,
+        )]
+    }
+    fn record(
+        &self,
+        outputs: &[String],
+        timestamp: NaiveDateTime,
+    ) -> Result<Map<String, Value>, String> {
+        // the result is the last line, log messages may come before:
+        let line = first(outputs).trim().lines().last().unwrap_or_default();
+        let result: OoklaResult = parse_output(line)?;
+        Record {
+            timestamp,
+            client: Client {
+                wlan: None,
+                ip: result.interface.external_ip,
+                lat: String::new(),
+                lon: String::new(),
+                isp: result.isp,
+            },
+            server: Server {
+                name: result.server.location,
+                sponsor: result.server.name,
+                distance: String::new(),
+                host: result.server.host,
+            },
+            performance: Performance {
+                latency: Some(milliseconds(result.ping.latency)),
+                jitter: Some(milliseconds(result.ping.jitter)),
+                download_config: None,
+                upload_config: None,
+                download: Some(result.download.bandwidth * BITS_PER_BYTE),
+                upload: Some(result.upload.bandwidth * BITS_PER_BYTE),
+            },
+        }
+        .into_map()
+    }
+}
+
+// speedtest-cli:
+
+/// speeds in bits per second, ping in ms
+#[derive(Deserialize)]
+struct SpeedtestCliResult {
+    download: f64,
+    upload: f64,
+    ping: f64,
+    server: SpeedtestCliServer,
+    client: SpeedtestCliClient,
+}
+
+#[derive(Deserialize)]
+struct SpeedtestCliServer {
+    name: String,
+    sponsor: String,
+    /// distance in km
+    d: f64,
+    host: String,
+}
+
+#[derive(Deserialize)]
+struct SpeedtestCliClient {
+    ip: String,
+    lat: String,
+    lon: String,
+    isp: String,
+}
+
+/// measures no jitter
+struct SpeedtestCli {
+    server: String,
+    args: Vec<String>,
+}
+
+impl SpeedTestRunner for SpeedtestCli {
+    fn backend(&self) -> Backend {
+        Backend::SpeedtestCli
+    }
+    fn calls(&self) -> Vec<Vec<String>> {
+        vec![call(
+            &["--json"],
+            Some(("--server", &self.server)),
+            &self.args,
+        )]
+    }
+    fn record(
+        &self,
+        outputs: &[String],
+        timestamp: NaiveDateTime,
+    ) -> Result<Map<String, Value>, String> {
+        let result: SpeedtestCliResult = parse_output(first(outputs))?;
+        Record {
+            timestamp,
+            client: Client {
+                wlan: None,
+                ip: result.client.ip,
+                lat: result.client.lat,
+                lon: result.client.lon,
+                isp: result.client.isp,
+            },
+            server: Server {
+                name: result.server.name,
+                sponsor: result.server.sponsor,
+                distance: format!("{:.2}", result.server.d),
+                host: result.server.host,
+            },
+            performance: Performance {
+                latency: Some(milliseconds(result.ping)),
+                jitter: None,
+                download_config: None,
+                upload_config: None,
+                download: Some(result.download),
+                upload: Some(result.upload),
+            },
+        }
+        .into_map()
+    }
+}
+
+// librespeed-cli:
+
+/// speeds in Mbit/s, ping and jitter in ms
+#[derive(Deserialize)]
+struct LibrespeedResult {
+    server: LibrespeedServer,
+    client: LibrespeedClient,
+    ping: f64,
+    jitter: f64,
+    download: f64,
+    upload: f64,
+}
+
+#[derive(Deserialize)]
+struct LibrespeedServer {
+    name: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct LibrespeedClient {
+    ip: String,
+    /// 'latitude,longitude'
+    #[serde(default)]
+    loc: String,
+    /// number and name of the autonomous system, e.g. 'AS3320 Deutsche Telekom AG'
+    #[serde(default)]
+    org: String,
+}
+
+/// writes a list with one result per server
+struct Librespeed {
+    server: String,
+    args: Vec<String>,
+}
+
+impl SpeedTestRunner for Librespeed {
+    fn backend(&self) -> Backend {
+        Backend::Librespeed
+    }
+    fn calls(&self) -> Vec<Vec<String>> {
+        vec![call(
+            &["--json"],
+            Some(("--server", &self.server)),
+            &self.args,
+        )]
+    }
+    fn record(
+        &self,
+        outputs: &[String],
+        timestamp: NaiveDateTime,
+    ) -> Result<Map<String, Value>, String> {
+        let results: Vec<LibrespeedResult> = parse_output(first(outputs))?;
+        let result = results
+            .into_iter()
+            .next()
+            .ok_or_else(|| invalid_output(first(outputs), &"no result"))?;
+        let (lat, lon) = result.client.loc.split_once(',').unwrap_or_default();
+        Record {
+            timestamp,
+            client: Client {
+                wlan: None,
+                ip: result.client.ip,
+                lat: String::from(lat),
+                lon: String::from(lon),
+                isp: result.client.org,
+            },
+            server: Server {
+                name: result.server.name,
+                sponsor: String::new(),
+                distance: String::new(),
+                host: result.server.url,
+            },
+            performance: Performance {
+                latency: Some(milliseconds(result.ping)),
+                jitter: Some(milliseconds(result.jitter)),
+                download_config: None,
+                upload_config: None,
+                download: Some(result.download * MEGA_BIT_FACTOR),
+                upload: Some(result.upload * MEGA_BIT_FACTOR),
+            },
+        }
+        .into_map()
+    }
+}
+
+// iperf3:
+
+#[derive(Deserialize)]
+struct Iperf3Result {
+    start: Iperf3Start,
+    end: Iperf3End,
+}
+
+#[derive(Deserialize)]
+struct Iperf3Start {
+    connected: Vec<Iperf3Connection>,
+    connecting_to: Iperf3Host,
+}
+
+#[derive(Deserialize)]
+struct Iperf3Connection {
+    local_host: String,
+}
+
+#[derive(Deserialize)]
+struct Iperf3Host {
+    host: String,
+    port: u32,
+}
+
+#[derive(Deserialize)]
+struct Iperf3End {
+    streams: Vec<Iperf3Stream>,
+    sum_received: Iperf3Sum,
+}
+
+#[derive(Deserialize)]
+struct Iperf3Stream {
+    sender: Iperf3Sender,
+}
+
+#[derive(Deserialize)]
+struct Iperf3Sender {
+    /// round trip time of the tcp connection in microseconds (not on every platform)
+    mean_rtt: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct Iperf3Sum {
+    bits_per_second: f64,
+}
+
+/// error of a failed call
+#[derive(Deserialize)]
+struct Iperf3Error {
+    error: String,
+}
+
+/// measures the upload and then the download (reverse mode) against the configured server,
+/// the latency is the round trip time of the upload (missing if iperf3 reports none)
+struct Iperf3 {
+    server: String,
+    args: Vec<String>,
+}
+
+impl SpeedTestRunner for Iperf3 {
+    fn backend(&self) -> Backend {
+        Backend::Iperf3
+    }
+    fn calls(&self) -> Vec<Vec<String>> {
+        vec![
+            call(&["-J"], Some(("-c", &self.server)), &self.args),
+            call(&["-J", "-R"], Some(("-c", &self.server)), &self.args),
+        ]
+    }
+    fn status(&self, output: &Output) -> (RunStatus, String) {
+        if output.status.success() {
+            return (RunStatus::Ok, String::new());
+        }
+        // errors are part of the json output, e.g. the server is not reachable or busy:
+        match serde_json::from_slice::<Iperf3Error>(&output.stdout) {
+            Ok(e) => (RunStatus::NoServer, e.error),
+            Err(_) => (
+                RunStatus::Crashed,
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ),
+        }
+    }
+    fn record(
+        &self,
+        outputs: &[String],
+        timestamp: NaiveDateTime,
+    ) -> Result<Map<String, Value>, String> {
+        let (upload_output, download_output) = match outputs {
+            [upload, download] => (upload, download),
+            _ => return Err(format!("{} outputs instead of 2", outputs.len())),
+        };
+        let upload: Iperf3Result = parse_output(upload_output)?;
+        let download: Iperf3Result = parse_output(download_output)?;
+        let rtts: Vec<f64> = upload
+            .end
+            .streams
+            .iter()
+            .filter_map(|s| s.sender.mean_rtt)
+            .collect();
+        // iperf3 reports no round trip time on some systems, e.g. macOS and BSD:
+        let latency = if rtts.is_empty() {
+            None
+        } else {
+            Some(rtts.iter().sum::<f64>() / rtts.len() as f64 / MICROSECONDS_PER_MILLISECOND)
+        };
+        let connecting_to = upload.start.connecting_to;
+        Record {
+            timestamp,
+            client: Client {
+                wlan: None,
+                ip: upload
+                    .start
+                    .connected
+                    .into_iter()
+                    .next()
+                    .map(|c| c.local_host)
+                    .unwrap_or_default(),
+                lat: String::new(),
+                lon: String::new(),
+                isp: String::new(),
+            },
+            server: Server {
+                name: connecting_to.host.clone(),
+                sponsor: String::new(),
+                distance: String::new(),
+                host: format!("{}:{}", connecting_to.host, connecting_to.port),
+            },
+            performance: Performance {
+                latency: latency.map(milliseconds),
+                jitter: None,
+                download_config: None,
+                upload_config: None,
+                download: Some(download.end.sum_received.bits_per_second),
+                upload: Some(upload.end.sum_received.bits_per_second),
+            },
+        }
+        .into_map()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const OOKLA_OUTPUT: &str = r#"{"type":"result","timestamp":"2026-10-18T08:22:31Z","ping":{"jitter":0.392,"latency":9.738,"low":9.351,"high":10.213},"download":{"bandwidth":11734283,"bytes":107453448,"elapsed":9407,"latency":{"iqm":21.262,"low":9.634,"high":227.623,"jitter":4.245}},"upload":{"bandwidth":4698524,"bytes":37436160,"elapsed":8008,"latency":{"iqm":35.118,"low":10.102,"high":301.543,"jitter":6.873}},"packetLoss":0,"isp":"Deutsche Telekom AG","interface":{"internalIp":"192.168.178.20","name":"eth0","macAddr":"DC:A6:32:01:02:03","isVpn":false,"externalIp":"93.200.1.2"},"server":{"id":30907,"host":"speedtest.example.de","port":8080,"name":"Deutsche Telekom","location":"Frankfurt am Main","country":"Germany","ip":"62.157.1.2"},"result":{"id":"0f2b2e6a","url":"https://www.speedtest.net/result/c/0f2b2e6a","persisted":true}}"#;
+
+    const SPEEDTEST_CLI_OUTPUT: &str = r#"{"download": 93456789.12, "upload": 38765432.1, "ping": 14.523, "server": {"url": "http://speedtest.example.de:8080/speedtest/upload.php", "lat": "50.1109", "lon": "8.6821", "name": "Frankfurt", "country": "Germany", "cc": "DE", "sponsor": "Example GmbH", "id": "12345", "host": "speedtest.example.de:8080", "d": 23.456, "latency": 14.523}, "timestamp": "2026-10-18T08:22:31.123456Z", "bytes_sent": 48234496, "bytes_received": 117064836, "share": null, "client": {"ip": "93.200.1.2", "lat": "50.0", "lon": "8.5", "isp": "Deutsche Telekom", "isprating": "3.7", "rating": "0", "ispdlavg": "0", "ispulavg": "0", "loggedin": "0", "country": "DE"}}
+"#;
+
+    const LIBRESPEED_OUTPUT: &str = r#"[{"timestamp":"2026-10-18T10:22:31.123456789+02:00","server":{"name":"Frankfurt, Germany (Clouvider)","url":"http://fra.speedtest.clouvider.net/backend"},"client":{"ip":"93.200.1.2","hostname":"p5dc80102.dip0.t-ipconnect.de","city":"Frankfurt am Main","region":"Hesse","country":"DE","loc":"50.1155,8.6842","org":"AS3320 Deutsche Telekom AG","postal":"60311","timezone":"Europe/Berlin"},"bytes_sent":44040192,"bytes_received":117440512,"ping":12.5,"jitter":1.73,"upload":35.23,"download":93.94,"share":""}]"#;
+
+    /// iperf3 -J -c 192.168.178.2, shortened to one interval
+    const IPERF3_UPLOAD_OUTPUT: &str = r#"{"start":{"connected":[{"socket":5,"local_host":"192.168.178.20","local_port":50432,"remote_host":"192.168.178.2","remote_port":5201}],"version":"iperf 3.9","system_info":"Linux pi 5.15.84-v7l+ #1613 SMP armv7l","timestamp":{"time":"Sun, 18 Oct 2026 08:22:31 GMT","timesecs":1792311751},"connecting_to":{"host":"192.168.178.2","port":5201},"cookie":"kq3h2xv5","tcp_mss_default":1448,"sock_bufsize":0,"sndbuf_actual":16384,"rcvbuf_actual":131072,"test_start":{"protocol":"TCP","num_streams":1,"blksize":131072,"omit":0,"duration":10,"bytes":0,"blocks":0,"reverse":0,"tos":0}},"intervals":[{"streams":[{"socket":5,"start":0,"end":10.0,"seconds":10.0,"bytes":1178599424,"bits_per_second":942879539.2,"retransmits":0,"snd_cwnd":1048576,"rtt":684,"rttvar":120,"pmtu":1500,"omitted":false,"sender":true}],"sum":{"start":0,"end":10.0,"seconds":10.0,"bytes":1178599424,"bits_per_second":942879539.2,"retransmits":0,"omitted":false,"sender":true}}],"end":{"streams":[{"sender":{"socket":5,"start":0,"end":10.0,"seconds":10.0,"bytes":1178599424,"bits_per_second":942879539.2,"retransmits":0,"max_snd_cwnd":1048576,"max_rtt":1521,"min_rtt":312,"mean_rtt":684,"sender":true},"receiver":{"socket":5,"start":0,"end":10.04,"seconds":10.0,"bytes":1176502272,"bits_per_second":937452000.0,"sender":true}}],"sum_sent":{"start":0,"end":10.0,"seconds":10.0,"bytes":1178599424,"bits_per_second":942879539.2,"retransmits":0,"sender":true},"sum_received":{"start":0,"end":10.04,"seconds":10.04,"bytes":1176502272,"bits_per_second":937452000.0,"sender":true},"cpu_utilization_percent":{"host_total":12.5,"host_user":0.8,"host_system":11.7,"remote_total":20.1,"remote_user":1.2,"remote_system":18.9},"sender_tcp_congestion":"cubic","receiver_tcp_congestion":"cubic"}}"#;
+
+    /// iperf3 -J -R -c 192.168.178.2, the client receives
+    const IPERF3_DOWNLOAD_OUTPUT: &str = r#"{"start":{"connected":[{"socket":5,"local_host":"192.168.178.20","local_port":50434,"remote_host":"192.168.178.2","remote_port":5201}],"version":"iperf 3.9","system_info":"Linux pi 5.15.84-v7l+ #1613 SMP armv7l","timestamp":{"time":"Sun, 18 Oct 2026 08:22:42 GMT","timesecs":1792311762},"connecting_to":{"host":"192.168.178.2","port":5201},"cookie":"p9w4m2zt","tcp_mss_default":1448,"sock_bufsize":0,"sndbuf_actual":16384,"rcvbuf_actual":131072,"test_start":{"protocol":"TCP","num_streams":1,"blksize":131072,"omit":0,"duration":10,"bytes":0,"blocks":0,"reverse":1,"tos":0}},"intervals":[],"end":{"streams":[{"sender":{"socket":5,"start":0,"end":10.04,"seconds":10.04,"bytes":1124073472,"bits_per_second":895676000.0,"retransmits":3,"sender":false},"receiver":{"socket":5,"start":0,"end":10.0,"seconds":10.0,"bytes":1121976320,"bits_per_second":897581056.0,"sender":false}}],"sum_sent":{"start":0,"end":10.04,"seconds":10.04,"bytes":1124073472,"bits_per_second":895676000.0,"retransmits":3,"sender":false},"sum_received":{"start":0,"end":10.0,"seconds":10.0,"bytes":1121976320,"bits_per_second":897581056.0,"sender":false},"cpu_utilization_percent":{"host_total":30.2,"host_user":2.1,"host_system":28.1,"remote_total":8.4,"remote_user":0.5,"remote_system":7.9},"sender_tcp_congestion":"cubic","receiver_tcp_congestion":"cubic"}}"#;
+
+    const SPEEDTEST_JSON_OUTPUT: &str = r#"{"timestamp":"2026-10-18 08:22:31","client":{"wlan":"home","ip":"93.200.1.2","lat":"50.0","lon":"8.5","isp":"Deutsche Telekom"},"server":{"name":"Frankfurt","sponsor":"Example GmbH","distance":"23.46","host":"speedtest.example.de:8080"},"performance":{"latency":14,"jitter":2,"downloadConfig":"x","uploadConfig":"y","download":93456789.0,"upload":38765432.0}}"#;
+
+    fn timestamp() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 18)
+            .and_then(|d| d.and_hms_opt(10, 22, 30))
+            .unwrap()
+    }
+
+    fn runner(backend: Backend, server: &str) -> Box<dyn SpeedTestRunner> {
+        speed_test_runner(&RunnerConfig {
+            backend,
+            command: String::new(),
+            server: String::from(server),
+            args: Vec::new(),
+        })
+    }
+
+    fn record(backend: Backend, outputs: &[&str]) -> Result<Map<String, Value>, String> {
+        let outputs: Vec<String> = outputs.iter().map(|o| String::from(*o)).collect();
+        runner(backend, "").record(&outputs, timestamp())
+    }
+
+    /// the record as it is read from a data file
+    fn parsed(backend: Backend, outputs: &[&str]) -> crate::json_parser::ParsedEntry {
+        let record = record(backend, outputs).unwrap();
+        crate::json_parser::JsonParser::parse(&Value::Object(record).to_string()).unwrap()
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn speedtest_json_output_is_stored_unchanged() {
+        let entry = parsed(Backend::SpeedtestJson, &[SPEEDTEST_JSON_OUTPUT]);
+        assert_eq!(entry.timestamp.to_string(), "2026-10-18 08:22:31");
+        let performance = entry.performance.unwrap();
+        assert_eq!(performance.latency, Some(14));
+        assert_eq!(performance.download_config.as_deref(), Some("x"));
+        assert_eq!(entry.client.unwrap().wlan.as_deref(), Some("home"));
+    }
+
+    #[test]
+    fn ookla_bandwidth_in_bytes_per_second_is_converted_to_bits() {
+        let entry = parsed(Backend::Ookla, &[OOKLA_OUTPUT]);
+        assert_eq!(entry.timestamp, timestamp());
+        let performance = entry.performance.unwrap();
+        assert_eq!(performance.latency, Some(10));
+        assert_eq!(performance.jitter, Some(0));
+        assert_close(performance.download, 11734283.0 * 8.0);
+        assert_close(performance.upload, 4698524.0 * 8.0);
+        let client = entry.client.unwrap();
+        assert_eq!(client.ip, "93.200.1.2");
+        assert_eq!(client.isp, "Deutsche Telekom AG");
+        let server = entry.server.unwrap();
+        assert_eq!(server.name, "Frankfurt am Main");
+        assert_eq!(server.sponsor, "Deutsche Telekom");
+        assert_eq!(server.host, "speedtest.example.de");
+    }
+
+    #[test]
+    fn ookla_log_lines_before_the_result_are_skipped() {
+        let output = format!(
+            "{}\n{}\n",
+            r#"{"type":"log","timestamp":"2026-10-18T08:22:20Z","message":"Server Selection - Trying to find the best server","level":"info"}"#,
+            OOKLA_OUTPUT
+        );
+        assert!(record(Backend::Ookla, &[&output]).is_ok());
+    }
+
+    #[test]
+    fn speedtest_cli_speeds_are_stored_in_bits_per_second() {
+        let entry = parsed(Backend::SpeedtestCli, &[SPEEDTEST_CLI_OUTPUT]);
+        let performance = entry.performance.unwrap();
+        assert_eq!(performance.latency, Some(15));
+        assert_eq!(performance.jitter, None);
+        assert_close(performance.download, 93456789.12);
+        assert_close(performance.upload, 38765432.1);
+        let client = entry.client.unwrap();
+        assert_eq!((client.lat.as_str(), client.lon.as_str()), ("50.0", "8.5"));
+        let server = entry.server.unwrap();
+        assert_eq!(server.distance, "23.46");
+        assert_eq!(server.sponsor, "Example GmbH");
+        assert_eq!(server.host, "speedtest.example.de:8080");
+    }
+
+    #[test]
+    fn librespeed_speeds_in_mbit_are_converted_to_bits() {
+        let entry = parsed(Backend::Librespeed, &[LIBRESPEED_OUTPUT]);
+        let performance = entry.performance.unwrap();
+        assert_eq!(performance.latency, Some(13));
+        assert_eq!(performance.jitter, Some(2));
+        assert_close(performance.download, 93.94e6);
+        assert_close(performance.upload, 35.23e6);
+        let client = entry.client.unwrap();
+        assert_eq!(
+            (client.lat.as_str(), client.lon.as_str()),
+            ("50.1155", "8.6842")
+        );
+        assert_eq!(client.isp, "AS3320 Deutsche Telekom AG");
+        assert_eq!(
+            entry.server.unwrap().host,
+            "http://fra.speedtest.clouvider.net/backend"
+        );
+    }
+
+    #[test]
+    fn iperf3_measures_upload_and_download_with_two_calls() {
+        let calls = runner(Backend::Iperf3, "192.168.178.2").calls();
+        assert_eq!(
+            calls,
+            vec![
+                vec!["-J", "-c", "192.168.178.2"],
+                vec!["-J", "-R", "-c", "192.168.178.2"]
+            ]
+        );
+        let entry = parsed(
+            Backend::Iperf3,
+            &[IPERF3_UPLOAD_OUTPUT, IPERF3_DOWNLOAD_OUTPUT],
+        );
+        let performance = entry.performance.unwrap();
+        // mean_rtt of 684 microseconds:
+        assert_eq!(performance.latency, Some(1));
+        assert_eq!(performance.jitter, None);
+        assert_close(performance.upload, 937452000.0);
+        assert_close(performance.download, 897581056.0);
+        assert_eq!(entry.client.unwrap().ip, "192.168.178.20");
+        assert_eq!(entry.server.unwrap().host, "192.168.178.2:5201");
+    }
+
+    #[test]
+    fn iperf3_without_round_trip_time_keeps_the_throughput() {
+        // e.g. on macOS the streams have no mean_rtt:
+        let without_rtt = IPERF3_UPLOAD_OUTPUT.replace(r#""mean_rtt":684,"#, "");
+        let entry = parsed(Backend::Iperf3, &[&without_rtt, IPERF3_DOWNLOAD_OUTPUT]);
+        let performance = entry.performance.unwrap();
+        assert_eq!(performance.latency, None);
+        assert_close(performance.upload, 937452000.0);
+        assert_close(performance.download, 897581056.0);
+    }
+
+    #[test]
+    fn server_and_args_are_passed_to_the_program() {
+        let config = RunnerConfig {
+            backend: Backend::Ookla,
+            command: String::new(),
+            server: String::from("30907"),
+            args: vec![String::from("--interface=eth0")],
+        };
+        assert_eq!(
+            speed_test_runner(&config).calls(),
+            vec![vec![
+                "--format=json",
+                "--accept-license",
+                "--accept-gdpr",
+                "--server-id",
+                "30907",
+                "--interface=eth0"
+            ]]
+        );
+    }
+
+    #[test]
+    fn malformed_or_partial_output_is_an_error() {
+        let truncated = &OOKLA_OUTPUT[..OOKLA_OUTPUT.len() / 2];
+        let without_upload = r#"{"ping":{"jitter":0.4,"latency":9.7},"download":{"bandwidth":11734283},"isp":"x","interface":{"externalIp":"1.2.3.4"},"server":{"host":"h","name":"n","location":"l"}}"#;
+        let cases: Vec<(Backend, Vec<&str>)> = vec![
+            (Backend::SpeedtestJson, vec!["no server found"]),
+            (
+                Backend::SpeedtestJson,
+                vec![r#"{"performance":{"latency":1}}"#],
+            ),
+            (Backend::Ookla, vec![truncated]),
+            (Backend::Ookla, vec![without_upload]),
+            (Backend::Ookla, vec![""]),
+            (Backend::SpeedtestCli, vec![&SPEEDTEST_CLI_OUTPUT[..100]]),
+            (Backend::Librespeed, vec!["[]"]),
+            (Backend::Librespeed, vec![r#"[{"ping":12.5}]"#]),
+            (Backend::Iperf3, vec![IPERF3_UPLOAD_OUTPUT]),
+            (
+                Backend::Iperf3,
+                vec![
+                    IPERF3_UPLOAD_OUTPUT,
+                    r#"{"start":{"connected":[],"version":"iperf 3.9"},"intervals":[],"end":{},"error":"the server is busy running a test. try again later"}"#,
+                ],
+            ),
+        ];
+        for (backend, outputs) in cases {
+            match record(backend, &outputs) {
+                Ok(r) => panic!("{} accepted {:?}: {:?}", backend, outputs, r),
+                Err(msg) => assert!(!msg.is_empty()),
+            }
+        }
+        let msg = record(Backend::Ookla, &[truncated]).unwrap_err();
+        assert!(msg.starts_with("no valid json output"), "{}", msg);
+    }
+}
@@ -0,0 +1,417 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::lib_constants::*;
+
+/// a statistic that can be shown as column of a chart's statistic table
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Statistic {
+    Count,
+    Min,
+    Max,
+    Average,
+    Median,
+    /// e.g. P95, linear interpolated between the closest ranks
+    Percentile(u8),
+    /// interquartile range P75 - P25
+    Iqr,
+    /// population standard deviation (divided by n)
+    StandardDeviation,
+    /// sample standard deviation (divided by n - 1)
+    SampleStandardDeviation,
+}
+
+impl TryFrom<String> for Statistic {
+    type Error = String;
+
+    fn try_from(key: String) -> Result<Self, Self::Error> {
+        let percentile = key
+            .strip_prefix('p')
+            .and_then(|p| p.parse::<u8>().ok())
+            .filter(|p| *p <= 100);
+        match (key.as_str(), percentile) {
+            (_, Some(p)) => Ok(Statistic::Percentile(p)),
+            (STATISTIC_KEY_COUNT, _) => Ok(Statistic::Count),
+            (STATISTIC_KEY_MIN, _) => Ok(Statistic::Min),
+            (STATISTIC_KEY_MAX, _) => Ok(Statistic::Max),
+            (STATISTIC_KEY_AVERAGE, _) => Ok(Statistic::Average),
+            (STATISTIC_KEY_MEDIAN, _) => Ok(Statistic::Median),
+            (STATISTIC_KEY_IQR, _) => Ok(Statistic::Iqr),
+            (STATISTIC_KEY_STD, _) => Ok(Statistic::StandardDeviation),
+            (STATISTIC_KEY_SAMPLE_STD, _) => Ok(Statistic::SampleStandardDeviation),
+            _ => Err(format!(
+                "unknown statistic '{}', use one of {}, {}, {}, {}, {}, {}, {}, {} or p0 to p100",
+                key,
+                STATISTIC_KEY_COUNT,
+                STATISTIC_KEY_MIN,
+                STATISTIC_KEY_MAX,
+                STATISTIC_KEY_AVERAGE,
+                STATISTIC_KEY_MEDIAN,
+                STATISTIC_KEY_IQR,
+                STATISTIC_KEY_STD,
+                STATISTIC_KEY_SAMPLE_STD
+            )),
+        }
+    }
+}
+
+impl From<Statistic> for String {
+    fn from(statistic: Statistic) -> Self {
+        match statistic {
+            Statistic::Count => String::from(STATISTIC_KEY_COUNT),
+            Statistic::Min => String::from(STATISTIC_KEY_MIN),
+            Statistic::Max => String::from(STATISTIC_KEY_MAX),
+            Statistic::Average => String::from(STATISTIC_KEY_AVERAGE),
+            Statistic::Median => String::from(STATISTIC_KEY_MEDIAN),
+            Statistic::Percentile(p) => format!("p{}", p),
+            Statistic::Iqr => String::from(STATISTIC_KEY_IQR),
+            Statistic::StandardDeviation => String::from(STATISTIC_KEY_STD),
+            Statistic::SampleStandardDeviation => String::from(STATISTIC_KEY_SAMPLE_STD),
+        }
+    }
+}
+
+/// header of the column
+impl fmt::Display for Statistic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statistic::Count => write!(f, "{}", STATISTIC_SAMPLES),
+            Statistic::Min => write!(f, "{}", STATISTIC_MIN),
+            Statistic::Max => write!(f, "{}", STATISTIC_MAX),
+            Statistic::Average => write!(f, "{}", STATISTIC_AVG),
+            Statistic::Median => write!(f, "{}", STATISTIC_MEDIAN),
+            Statistic::Percentile(p) => write!(f, "P{}", p),
+            Statistic::Iqr => write!(f, "{}", STATISTIC_IQR),
+            Statistic::StandardDeviation => write!(f, "{}", STATISTIC_STD),
+            Statistic::SampleStandardDeviation => write!(f, "{}", STATISTIC_SAMPLE_STD),
+        }
+    }
+}
+
+/// descriptive statistics of the measured values of a chart
+#[derive(Debug, Default)]
+pub struct Summary {
+    sorted: Vec<f64>,
+}
+
+impl Summary {
+    pub fn new(mut values: Vec<f64>) -> Self {
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        Summary { sorted: values }
+    }
+
+    /// value of the statistic, none if there are too few samples
+    pub fn value(&self, statistic: Statistic) -> Option<f64> {
+        match statistic {
+            Statistic::Count => Some(self.count() as f64),
+            Statistic::Min => self.sorted.first().copied(),
+            Statistic::Max => self.sorted.last().copied(),
+            Statistic::Average => self.average(),
+            Statistic::Median => self.median(),
+            Statistic::Percentile(p) => self.percentile(f64::from(p)),
+            Statistic::Iqr => self.iqr(),
+            Statistic::StandardDeviation => self.standard_deviation(),
+            Statistic::SampleStandardDeviation => self.sample_standard_deviation(),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub fn average(&self) -> Option<f64> {
+        if self.sorted.is_empty() {
+            None
+        } else {
+            Some(self.sorted.iter().sum::<f64>() / self.sorted.len() as f64)
+        }
+    }
+
+    /// middle value, the average of both middle values for an even count
+    pub fn median(&self) -> Option<f64> {
+        self.percentile(50.0)
+    }
+
+    /// percentile p (0 to 100) linear interpolated between the closest ranks
+    /// (the method of most spreadsheets, PERCENTILE.INC)
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        let last = self.sorted.len().checked_sub(1)?;
+        let rank = p.clamp(0.0, 100.0) / 100.0 * last as f64;
+        let lower = rank.floor() as usize;
+        let upper = rank.ceil() as usize;
+        let fraction = rank - lower as f64;
+        Some(self.sorted[lower] + (self.sorted[upper] - self.sorted[lower]) * fraction)
+    }
+
+    pub fn iqr(&self) -> Option<f64> {
+        Some(self.percentile(75.0)? - self.percentile(25.0)?)
+    }
+
+    /// population standard deviation, none for less than two values
+    pub fn standard_deviation(&self) -> Option<f64> {
+        self.deviation(0)
+    }
+
+    /// sample standard deviation, none for less than two values
+    pub fn sample_standard_deviation(&self) -> Option<f64> {
+        self.deviation(1)
+    }
+
+    /// standard deviation with the sum of squares divided by n - correction
+    fn deviation(&self, correction: usize) -> Option<f64> {
+        if self.sorted.len() < 2 {
+            return None;
+        }
+        let average = self.average()?;
+        let squares: f64 = self
+            .sorted
+            .iter()
+            .map(|x| {
+                let y = x - average;
+                y * y
+            })
+            .sum();
+        Some(f64::sqrt(squares / (self.sorted.len() - correction) as f64))
+    }
+}
+
+/// median of unsorted values
+pub fn median(values: &[f64]) -> Option<f64> {
+    Summary::new(values.to_vec()).median()
+}
+
+/// result of a two-sided Mann-Whitney U test
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MannWhitney {
+    /// U of the first sample
+    pub u: f64,
+    /// standardized U, positive if the first sample tends to be larger
+    pub z: f64,
+    pub p_value: f64,
+}
+
+/// two-sided Mann-Whitney U test with the normal approximation (tie and continuity corrected),
+/// none if a sample is empty, all values equal gives no difference (p-value 1)
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> Option<MannWhitney> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|v| (*v, true))
+        .chain(b.iter().map(|v| (*v, false)))
+        .collect();
+    all.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+
+    // equal values get the average of their ranks:
+    let mut rank_sum_a = 0.0;
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j + 1 < all.len() && all[j + 1].0 == all[i].0 {
+            j += 1;
+        }
+        let rank = (i + j) as f64 / 2.0 + 1.0;
+        rank_sum_a += rank * all[i..=j].iter().filter(|(_, in_a)| *in_a).count() as f64;
+        let t = (j - i + 1) as f64;
+        ties += t * t * t - t;
+        i = j + 1;
+    }
+    let u = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let n = n1 + n2;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return Some(MannWhitney {
+            u,
+            z: 0.0,
+            p_value: 1.0,
+        });
+    }
+    let distance = u - n1 * n2 / 2.0;
+    let z = distance.signum() * (distance.abs() - 0.5).max(0.0) / variance.sqrt();
+    Some(MannWhitney {
+        u,
+        z,
+        p_value: (2.0 * (1.0 - normal_cdf(z.abs()))).min(1.0),
+    })
+}
+
+/// distribution function of the standard normal distribution,
+/// erf approximated by Abramowitz and Stegun 7.1.26 (error below 1.5e-7)
+pub fn normal_cdf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs() / std::f64::consts::SQRT_2);
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - polynomial * (-x * x / 2.0).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(values: &[f64]) -> Summary {
+        Summary::new(values.to_vec())
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("a value");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, found {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn median_of_odd_count_is_middle_value() {
+        assert_close(summary(&[5.0, 1.0, 3.0]).median(), 3.0);
+    }
+
+    #[test]
+    fn median_of_even_count_is_average_of_middle_values() {
+        assert_close(summary(&[4.0, 1.0, 3.0, 2.0]).median(), 2.5);
+    }
+
+    #[test]
+    fn empty_sample_has_no_statistics() {
+        let s = summary(&[]);
+        assert_eq!(s.count(), 0);
+        assert_eq!(s.median(), None);
+        assert_eq!(s.average(), None);
+        assert_eq!(s.value(Statistic::Min), None);
+        assert_eq!(s.value(Statistic::Percentile(95)), None);
+        assert_eq!(s.iqr(), None);
+        assert_eq!(s.standard_deviation(), None);
+    }
+
+    #[test]
+    fn single_value() {
+        let s = summary(&[7.0]);
+        assert_close(s.median(), 7.0);
+        assert_close(s.percentile(5.0), 7.0);
+        assert_close(s.percentile(99.0), 7.0);
+        assert_close(s.iqr(), 0.0);
+        assert_eq!(s.standard_deviation(), None);
+        assert_eq!(s.sample_standard_deviation(), None);
+    }
+
+    #[test]
+    fn min_max_and_count() {
+        let s = summary(&[3.0, -1.0, 10.0, 4.0]);
+        assert_close(s.value(Statistic::Min), -1.0);
+        assert_close(s.value(Statistic::Max), 10.0);
+        assert_close(s.value(Statistic::Count), 4.0);
+    }
+
+    #[test]
+    fn percentiles_are_interpolated() {
+        // 1, 2, ..., 11: rank of p is p / 10
+        let values: Vec<f64> = (1..=11).map(f64::from).collect();
+        let s = summary(&values);
+        assert_close(s.percentile(0.0), 1.0);
+        assert_close(s.percentile(5.0), 1.5);
+        assert_close(s.percentile(25.0), 3.5);
+        assert_close(s.percentile(75.0), 8.5);
+        assert_close(s.percentile(95.0), 10.5);
+        assert_close(s.percentile(99.0), 10.9);
+        assert_close(s.percentile(100.0), 11.0);
+        assert_close(s.iqr(), 5.0);
+    }
+
+    #[test]
+    fn population_and_sample_standard_deviation() {
+        let s = summary(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_close(s.average(), 5.0);
+        assert_close(s.standard_deviation(), 2.0);
+        assert_close(s.sample_standard_deviation(), (32.0f64 / 7.0).sqrt());
+    }
+
+    #[test]
+    fn median_of_unsorted_slice() {
+        assert_close(median(&[9.0, 1.0, 5.0, 7.0]), 6.0);
+    }
+
+    #[test]
+    fn normal_cdf_of_known_quantiles() {
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-7);
+        assert!((normal_cdf(1.96) - 0.975).abs() < 1e-4);
+        assert!((normal_cdf(-1.96) - 0.025).abs() < 1e-4);
+    }
+
+    #[test]
+    fn mann_whitney_u_of_separated_samples_is_significant() {
+        let test = mann_whitney_u(&[1.0, 2.0, 3.0, 4.0, 5.0], &[6.0, 7.0, 8.0, 9.0, 10.0]).unwrap();
+        assert_close(Some(test.u), 0.0);
+        assert!(test.z < 0.0);
+        // (12.5 - 0.5) / sqrt(25 / 12 * 11) = 2.507
+        assert!((test.p_value - 0.0122).abs() < 1e-3, "p = {}", test.p_value);
+    }
+
+    #[test]
+    fn mann_whitney_u_of_equal_samples_is_not_significant() {
+        let test = mann_whitney_u(&[1.0, 2.0, 3.0, 3.0], &[3.0, 2.0, 1.0, 3.0]).unwrap();
+        assert_close(Some(test.u), 8.0);
+        assert!(test.p_value > 0.999, "p = {}", test.p_value);
+        assert_close(mann_whitney_u(&[2.0, 2.0], &[2.0]).map(|t| t.p_value), 1.0);
+        assert!(mann_whitney_u(&[], &[1.0]).is_none());
+    }
+
+    #[test]
+    fn statistic_keys_round_trip() {
+        for key in &[
+            "n",
+            "min",
+            "max",
+            "average",
+            "median",
+            "p10",
+            "p99",
+            "iqr",
+            "standard-deviation",
+            "sample-standard-deviation",
+        ] {
+            let statistic = Statistic::try_from(String::from(*key)).unwrap();
+            assert_eq!(String::from(statistic), *key);
+        }
+    }
+
+    #[test]
+    fn invalid_statistic_keys_are_rejected() {
+        assert!(Statistic::try_from(String::from("p101")).is_err());
+        assert!(Statistic::try_from(String::from("mode")).is_err());
+    }
+}
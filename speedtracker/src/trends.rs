@@ -0,0 +1,121 @@
+// MIT License
+//
+// Copyright (c) 2022 Florian Mantz
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chrono::NaiveDateTime;
+
+use crate::chart_config::WindowUnit;
+use crate::statistics::median;
+
+/// median of the window ending at every sample, samples are sorted by timestamp.
+/// the window is the last 'window' samples or the samples of the last 'window' hours,
+/// one value per sample, None if the window is empty
+pub fn rolling_median(
+    samples: &[(NaiveDateTime, f64)],
+    window: u32,
+    unit: WindowUnit,
+) -> Vec<Option<f64>> {
+    let mut start = 0;
+    samples
+        .iter()
+        .enumerate()
+        .map(|(i, (timestamp, _))| {
+            start = match unit {
+                WindowUnit::Samples => (i + 1).saturating_sub(window.max(1) as usize),
+                WindowUnit::Hours => {
+                    let hours = chrono::Duration::hours(i64::from(window));
+                    start
+                        + samples[start..=i]
+                            .iter()
+                            .take_while(|(t, _)| *timestamp - *t >= hours)
+                            .count()
+                }
+            };
+            let values: Vec<f64> = samples[start..=i].iter().map(|(_, v)| *v).collect();
+            median(&values)
+        })
+        .collect()
+}
+
+/// exponentially weighted moving average of every sample, samples are sorted by timestamp.
+/// for samples the weight of a new sample is 2 / (window + 1),
+/// for hours the weight of the past decays by 1/e every 'window' hours,
+/// so irregular intervals between runs are taken into account
+pub fn ewma(samples: &[(NaiveDateTime, f64)], window: u32, unit: WindowUnit) -> Vec<f64> {
+    let mut average: Option<(NaiveDateTime, f64)> = None;
+    samples
+        .iter()
+        .map(|&(timestamp, value)| {
+            let next = match average {
+                None => value,
+                Some((last, avg)) => {
+                    let alpha = match unit {
+                        WindowUnit::Samples => 2.0 / (f64::from(window) + 1.0),
+                        WindowUnit::Hours => {
+                            let hours = (timestamp - last).num_seconds() as f64 / 3600.0;
+                            1.0 - (-hours / f64::from(window)).exp()
+                        }
+                    };
+                    avg + alpha * (value - avg)
+                }
+            };
+            average = Some((timestamp, next));
+            next
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    /// a sample every 30 minutes
+    fn samples(values: &[f64]) -> Vec<(NaiveDateTime, f64)> {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 1)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .unwrap();
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (start + Duration::minutes(30 * i as i64), *v))
+            .collect()
+    }
+
+    #[test]
+    fn rolling_median_has_one_value_per_sample() {
+        let samples = samples(&[1.0, 5.0, 3.0, 9.0, 7.0]);
+        assert_eq!(
+            rolling_median(&samples, 3, WindowUnit::Samples),
+            vec![Some(1.0), Some(3.0), Some(3.0), Some(5.0), Some(7.0)]
+        );
+        assert_eq!(
+            rolling_median(&samples, 1, WindowUnit::Hours),
+            vec![Some(1.0), Some(3.0), Some(4.0), Some(6.0), Some(8.0)]
+        );
+        // an empty window keeps the values on their samples:
+        assert_eq!(
+            rolling_median(&samples, 0, WindowUnit::Hours),
+            vec![None; 5]
+        );
+    }
+}